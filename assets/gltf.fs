@@ -0,0 +1,154 @@
+#version 330 core
+out vec4 FragColor;
+
+in vec3 FragPos;
+in vec3 Normal;
+in vec4 Tangent;
+in vec2 TexCoord;
+in vec4 Color;
+
+// must match MAX_LIGHTS in lighting.rs
+#define MAX_LIGHTS 16
+
+#define DIRECTIONAL 0
+#define POINT 1
+#define SPOT 2
+
+const float PI = 3.14159265359;
+
+// same layout as in lit.fs, set by Lighting::upload
+struct Light {
+    int type;
+    vec3 position;
+    // from the light into the scene
+    vec3 direction;
+    vec3 color;
+    float constant;
+    float linear;
+    float quadratic;
+    // cosines of the spot cone angles
+    float innerCutoff;
+    float outerCutoff;
+};
+
+uniform Light lights[MAX_LIGHTS];
+uniform int lightCount;
+uniform vec3 ambient;
+uniform vec3 viewPos;
+
+// glTF metallic-roughness material. Missing textures are bound to a white texture, so the factors apply alone
+uniform sampler2D baseColorTexture;
+// roughness in the green channel, metalness in the blue channel
+uniform sampler2D metallicRoughnessTexture;
+uniform sampler2D normalTexture;
+uniform sampler2D occlusionTexture;
+uniform sampler2D emissiveTexture;
+uniform vec4 baseColorFactor;
+uniform float metallicFactor;
+uniform float roughnessFactor;
+uniform bool hasNormalTexture;
+uniform float normalScale;
+uniform float occlusionStrength;
+uniform vec3 emissiveFactor;
+// fragments with less alpha are discarded, 0 unless the alpha mode is MASK
+uniform float alphaCutoff;
+// alpha is only kept for the BLEND alpha mode, OPAQUE and MASK surfaces are written opaque
+uniform bool alphaBlend;
+
+// Trowbridge-Reitz GGX normal distribution
+float distributionGGX(float NdotH, float alpha)
+{
+    float a2 = alpha * alpha;
+    float d = NdotH * NdotH * (a2 - 1.0) + 1.0;
+    return a2 / (PI * d * d);
+}
+
+// Smith's shadowing-masking with the Schlick-GGX approximation for each direction
+float geometrySmith(float NdotV, float NdotL, float alpha)
+{
+    float k = alpha / 2.0;
+    return (NdotV / (NdotV * (1.0 - k) + k)) * (NdotL / (NdotL * (1.0 - k) + k));
+}
+
+vec3 fresnelSchlick(float cosTheta, vec3 F0)
+{
+    return F0 + (1.0 - F0) * pow(1.0 - cosTheta, 5.0);
+}
+
+vec3 surfaceNormal()
+{
+    vec3 normal = normalize(Normal);
+    if (hasNormalTexture && dot(Tangent.xyz, Tangent.xyz) > 0.0) {
+        vec3 tangent = normalize(Tangent.xyz - dot(Tangent.xyz, normal) * normal);
+        vec3 bitangent = cross(normal, tangent) * Tangent.w;
+        vec3 mapped = texture(normalTexture, TexCoord).rgb * 2.0 - 1.0;
+        mapped.xy *= normalScale;
+        normal = normalize(mat3(tangent, bitangent, normal) * mapped);
+    }
+    // lit from both sides, double sided materials are seen from below too
+    return gl_FrontFacing ? normal : -normal;
+}
+
+// Cook-Torrance specular plus Lambert diffuse for one light
+vec3 shade(Light light, vec3 normal, vec3 viewDir, vec3 albedo, float metallic, float roughness)
+{
+    vec3 lightDir;
+    float intensity = 1.0;
+    if (light.type == DIRECTIONAL) {
+        lightDir = normalize(-light.direction);
+    } else {
+        vec3 toLight = light.position - FragPos;
+        float distance = length(toLight);
+        lightDir = toLight / distance;
+        intensity = 1.0 / (light.constant + light.linear * distance + light.quadratic * distance * distance);
+
+        if (light.type == SPOT) {
+            float theta = dot(lightDir, normalize(-light.direction));
+            intensity *= clamp((theta - light.outerCutoff) / (light.innerCutoff - light.outerCutoff), 0.0, 1.0);
+        }
+    }
+
+    float NdotL = max(dot(normal, lightDir), 0.0);
+    if (NdotL <= 0.0) {
+        return vec3(0.0);
+    }
+    float NdotV = max(dot(normal, viewDir), 0.0001);
+    vec3 halfway = normalize(lightDir + viewDir);
+    float alpha = roughness * roughness;
+
+    vec3 F0 = mix(vec3(0.04), albedo, metallic);
+    vec3 F = fresnelSchlick(max(dot(halfway, viewDir), 0.0), F0);
+    vec3 specular = distributionGGX(max(dot(normal, halfway), 0.0), alpha) * geometrySmith(NdotV, NdotL, alpha) * F
+        / (4.0 * NdotV * NdotL);
+    // metals have no diffuse reflection
+    vec3 diffuse = (1.0 - F) * (1.0 - metallic) * albedo / PI;
+
+    // the light colors are tuned for lit.fs, where a white surface facing a light shows the light's color. The π
+    // keeps that brightness for the Lambert term here
+    return (diffuse + specular) * light.color * intensity * PI * NdotL;
+}
+
+void main()
+{
+    vec4 baseColor = texture(baseColorTexture, TexCoord) * baseColorFactor * Color;
+    if (baseColor.a < alphaCutoff) {
+        discard;
+    }
+
+    vec4 metallicRoughness = texture(metallicRoughnessTexture, TexCoord);
+    float metallic = clamp(metallicRoughness.b * metallicFactor, 0.0, 1.0);
+    // fully smooth surfaces would turn the highlights into infinitely thin spikes
+    float roughness = clamp(metallicRoughness.g * roughnessFactor, 0.04, 1.0);
+    float occlusion = mix(1.0, texture(occlusionTexture, TexCoord).r, occlusionStrength);
+
+    vec3 normal = surfaceNormal();
+    vec3 viewDir = normalize(viewPos - FragPos);
+
+    vec3 color = ambient * baseColor.rgb * occlusion;
+    for (int i = 0; i < min(lightCount, MAX_LIGHTS); i++) {
+        color += shade(lights[i], normal, viewDir, baseColor.rgb, metallic, roughness);
+    }
+    color += texture(emissiveTexture, TexCoord).rgb * emissiveFactor;
+
+    FragColor = vec4(color, alphaBlend ? baseColor.a : 1.0);
+}
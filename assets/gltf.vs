@@ -0,0 +1,29 @@
+#version 330 core
+layout (location = 0) in vec3 aPos;
+layout (location = 1) in vec2 aTexCoord;
+layout (location = 2) in vec3 aNormal;
+layout (location = 3) in vec4 aTangent;
+layout (location = 4) in vec4 aColor;
+
+out vec3 FragPos;
+out vec3 Normal;
+// xyz in world space, w the bitangent sign
+out vec4 Tangent;
+out vec2 TexCoord;
+out vec4 Color;
+
+uniform mat4 model;
+uniform mat4 view;
+uniform mat4 projection;
+
+void main()
+{
+    vec4 worldPos = model * vec4(aPos, 1.0);
+    FragPos = worldPos.xyz;
+    // the inverse transpose keeps normals perpendicular under non-uniform scaling
+    Normal = mat3(transpose(inverse(model))) * aNormal;
+    Tangent = vec4(mat3(model) * aTangent.xyz, aTangent.w);
+    TexCoord = aTexCoord;
+    Color = aColor;
+    gl_Position = projection * view * worldPos;
+}
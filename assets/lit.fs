@@ -0,0 +1,86 @@
+#version 330 core
+out vec4 FragColor;
+
+in vec3 FragPos;
+in vec3 Normal;
+in vec2 TexCoord;
+
+// must match MAX_LIGHTS in lighting.rs
+#define MAX_LIGHTS 16
+
+#define DIRECTIONAL 0
+#define POINT 1
+#define SPOT 2
+
+struct Material {
+    sampler2D diffuse;
+    // highlight strength in the red channel
+    sampler2D specular;
+    float shininess;
+};
+
+struct Light {
+    int type;
+    vec3 position;
+    // from the light into the scene
+    vec3 direction;
+    vec3 color;
+    float constant;
+    float linear;
+    float quadratic;
+    // cosines of the spot cone angles
+    float innerCutoff;
+    float outerCutoff;
+};
+
+uniform Material material;
+uniform Light lights[MAX_LIGHTS];
+uniform int lightCount;
+uniform vec3 ambient;
+uniform vec3 viewPos;
+
+// Blinn-Phong: diffuse from the angle to the light, specular from the angle between the normal and the halfway vector
+vec3 shade(Light light, vec3 normal, vec3 viewDir, vec3 albedo, float specularStrength)
+{
+    vec3 lightDir;
+    float intensity = 1.0;
+    if (light.type == DIRECTIONAL) {
+        lightDir = normalize(-light.direction);
+    } else {
+        vec3 toLight = light.position - FragPos;
+        float distance = length(toLight);
+        lightDir = toLight / distance;
+        intensity = 1.0 / (light.constant + light.linear * distance + light.quadratic * distance * distance);
+
+        if (light.type == SPOT) {
+            float theta = dot(lightDir, normalize(-light.direction));
+            intensity *= clamp((theta - light.outerCutoff) / (light.innerCutoff - light.outerCutoff), 0.0, 1.0);
+        }
+    }
+
+    float diffuse = max(dot(normal, lightDir), 0.0);
+    vec3 halfway = normalize(lightDir + viewDir);
+    // no highlights on faces turned away from the light
+    float specular = diffuse > 0.0 ? pow(max(dot(normal, halfway), 0.0), material.shininess) : 0.0;
+
+    return light.color * intensity * (albedo * diffuse + specularStrength * specular);
+}
+
+void main()
+{
+    vec3 normal = normalize(Normal);
+    // lit from both sides, e.g. for planes seen from below
+    if (!gl_FrontFacing) {
+        normal = -normal;
+    }
+    vec3 viewDir = normalize(viewPos - FragPos);
+
+    vec4 albedo = texture(material.diffuse, TexCoord);
+    float specularStrength = texture(material.specular, TexCoord).r;
+
+    vec3 color = ambient * albedo.rgb;
+    for (int i = 0; i < min(lightCount, MAX_LIGHTS); i++) {
+        color += shade(lights[i], normal, viewDir, albedo.rgb, specularStrength);
+    }
+    FragColor = vec4(color, albedo.a);
+}
@@ -0,0 +1,23 @@
+#version 330 core
+out float AdaptedLuminance;
+
+// log luminance of the frame, its smallest mip level is the average
+uniform sampler2D luminance;
+uniform float averageLevel;
+// adapted luminance of the previous frame
+uniform sampler2D previous;
+uniform float deltaTime;
+// how fast the eye adapts to brighter and darker scenes, per second
+uniform float speedUp;
+uniform float speedDown;
+// take the frame average as is, e.g. on the first frame
+uniform bool reset;
+
+void main()
+{
+    float average = exp(textureLod(luminance, vec2(0.5), averageLevel).r);
+    float adapted = texture(previous, vec2(0.5)).r;
+    float speed = average > adapted ? speedUp : speedDown;
+    float blend = reset ? 1.0 : 1.0 - exp(-deltaTime * speed);
+    AdaptedLuminance = mix(adapted, average, blend);
+}
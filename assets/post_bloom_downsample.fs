@@ -0,0 +1,40 @@
+#version 330 core
+out vec4 FragColor;
+
+in vec2 TexCoords;
+
+uniform sampler2D image;
+// one pixel of `image`
+uniform vec2 texelSize;
+// the first downsample keeps only what is brighter than the threshold
+uniform bool prefilter;
+uniform float threshold;
+// width of the soft transition below the threshold, 0 is a hard cut
+uniform float knee;
+
+vec3 sampleAt(float x, float y)
+{
+    return texture(image, TexCoords + vec2(x, y) * texelSize).rgb;
+}
+
+void main()
+{
+    // 13 tap filter from Jimenez's "Next generation post processing in Call of Duty: Advanced Warfare": five
+    // overlapping 2x2 boxes, which avoids the flickering of a plain 2x2 downsample on moving highlights
+    vec3 a = sampleAt(-2.0, 2.0), b = sampleAt(0.0, 2.0), c = sampleAt(2.0, 2.0);
+    vec3 d = sampleAt(-2.0, 0.0), e = sampleAt(0.0, 0.0), f = sampleAt(2.0, 0.0);
+    vec3 g = sampleAt(-2.0, -2.0), h = sampleAt(0.0, -2.0), i = sampleAt(2.0, -2.0);
+    vec3 j = sampleAt(-1.0, 1.0), k = sampleAt(1.0, 1.0), l = sampleAt(-1.0, -1.0), m = sampleAt(1.0, -1.0);
+
+    vec3 color = e * 0.125 + (a + c + g + i) * 0.03125 + (b + d + f + h) * 0.0625 + (j + k + l + m) * 0.125;
+
+    if (prefilter) {
+        // quadratic curve between threshold - knee and threshold + knee, linear above
+        float brightness = max(color.r, max(color.g, color.b));
+        float soft = clamp(brightness - threshold + knee, 0.0, 2.0 * knee);
+        soft = soft * soft / (4.0 * knee + 0.00001);
+        color *= max(soft, brightness - threshold) / max(brightness, 0.00001);
+    }
+
+    FragColor = vec4(color, 1.0);
+}
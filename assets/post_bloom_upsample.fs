@@ -0,0 +1,26 @@
+#version 330 core
+out vec4 FragColor;
+
+in vec2 TexCoords;
+
+uniform sampler2D image;
+// one pixel of `image`
+uniform vec2 texelSize;
+// spread of the filter in pixels of `image`
+uniform float radius;
+// multiplier of the result, added to the target by blending
+uniform float intensity;
+
+vec3 sampleAt(float x, float y)
+{
+    return texture(image, TexCoords + vec2(x, y) * texelSize * radius).rgb;
+}
+
+void main()
+{
+    // 3x3 tent filter
+    vec3 color = sampleAt(0.0, 0.0) * 4.0
+               + (sampleAt(-1.0, 0.0) + sampleAt(1.0, 0.0) + sampleAt(0.0, -1.0) + sampleAt(0.0, 1.0)) * 2.0
+               + sampleAt(-1.0, -1.0) + sampleAt(1.0, -1.0) + sampleAt(-1.0, 1.0) + sampleAt(1.0, 1.0);
+    FragColor = vec4(color * (intensity / 16.0), 1.0);
+}
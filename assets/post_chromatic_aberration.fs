@@ -0,0 +1,18 @@
+#version 330 core
+out vec4 FragColor;
+
+in vec2 TexCoords;
+
+uniform sampler2D image;
+// shift of the red and blue channels at the corners, in texture coordinates
+uniform float strength;
+
+void main()
+{
+    // like a lens, the channels separate more towards the edges
+    vec2 shift = (TexCoords - 0.5) * 2.0 * strength;
+    float red = texture(image, TexCoords + shift).r;
+    float green = texture(image, TexCoords).g;
+    float blue = texture(image, TexCoords - shift).b;
+    FragColor = vec4(red, green, blue, 1.0);
+}
@@ -0,0 +1,20 @@
+#version 330 core
+out vec4 FragColor;
+
+in vec2 TexCoords;
+
+uniform sampler2D image;
+// maps an input color (as texture coordinates) to the graded color
+uniform sampler3D lut;
+uniform float lutSize;
+// blend between the original and the graded color
+uniform float amount;
+
+void main()
+{
+    vec3 color = clamp(texture(image, TexCoords).rgb, 0.0, 1.0);
+    // sample between the centers of the first and last texels so 0 and 1 map exactly to the table ends
+    vec3 coordinates = color * ((lutSize - 1.0) / lutSize) + 0.5 / lutSize;
+    vec3 graded = texture(lut, coordinates).rgb;
+    FragColor = vec4(mix(color, graded, amount), 1.0);
+}
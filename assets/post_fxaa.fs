@@ -0,0 +1,48 @@
+#version 330 core
+out vec4 FragColor;
+
+in vec2 TexCoords;
+
+uniform sampler2D image;
+uniform vec2 texelSize;
+// longest blur along an edge, in pixels
+uniform float spanMax;
+// scales down the search direction in bright areas
+uniform float reduceMul;
+// lower bound of that reduction, keeps dark edges from blurring too far
+uniform float reduceMin;
+
+float luma(vec3 color)
+{
+    return dot(color, vec3(0.299, 0.587, 0.114));
+}
+
+// The compact FXAA variant by Timothy Lottes: estimates the edge direction from the luma of the four diagonal
+// neighbors and blurs along it. Expects tone mapped [0, 1] colors
+void main()
+{
+    vec3 rgbNW = texture(image, TexCoords + vec2(-1.0, -1.0) * texelSize).rgb;
+    vec3 rgbNE = texture(image, TexCoords + vec2(1.0, -1.0) * texelSize).rgb;
+    vec3 rgbSW = texture(image, TexCoords + vec2(-1.0, 1.0) * texelSize).rgb;
+    vec3 rgbSE = texture(image, TexCoords + vec2(1.0, 1.0) * texelSize).rgb;
+    vec3 rgbM = texture(image, TexCoords).rgb;
+
+    float lumaNW = luma(rgbNW), lumaNE = luma(rgbNE), lumaSW = luma(rgbSW), lumaSE = luma(rgbSE), lumaM = luma(rgbM);
+    float lumaMin = min(lumaM, min(min(lumaNW, lumaNE), min(lumaSW, lumaSE)));
+    float lumaMax = max(lumaM, max(max(lumaNW, lumaNE), max(lumaSW, lumaSE)));
+
+    // perpendicular to the luma gradient, i.e. along the edge
+    vec2 direction = vec2(-((lumaNW + lumaNE) - (lumaSW + lumaSE)), (lumaNW + lumaSW) - (lumaNE + lumaSE));
+    float directionReduce = max((lumaNW + lumaNE + lumaSW + lumaSE) * 0.25 * reduceMul, reduceMin);
+    float inverseSmallest = 1.0 / (min(abs(direction.x), abs(direction.y)) + directionReduce);
+    direction = clamp(direction * inverseSmallest, vec2(-spanMax), vec2(spanMax)) * texelSize;
+
+    vec3 rgbA = 0.5 * (texture(image, TexCoords + direction * (1.0 / 3.0 - 0.5)).rgb
+                     + texture(image, TexCoords + direction * (2.0 / 3.0 - 0.5)).rgb);
+    vec3 rgbB = rgbA * 0.5 + 0.25 * (texture(image, TexCoords - direction * 0.5).rgb
+                                   + texture(image, TexCoords + direction * 0.5).rgb);
+
+    // the wider blur crossed another edge if it left the local luma range, fall back to the narrow one
+    float lumaB = luma(rgbB);
+    FragColor = vec4((lumaB < lumaMin || lumaB > lumaMax) ? rgbA : rgbB, 1.0);
+}
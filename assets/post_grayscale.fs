@@ -0,0 +1,16 @@
+#version 330 core
+out vec4 FragColor;
+
+in vec2 TexCoords;
+
+uniform sampler2D image;
+// 0 keeps the colors, 1 is fully gray
+uniform float amount;
+
+void main()
+{
+    vec3 color = texture(image, TexCoords).rgb;
+    // Rec. 709 luma weights
+    float luminance = dot(color, vec3(0.2126, 0.7152, 0.0722));
+    FragColor = vec4(mix(color, vec3(luminance), amount), 1.0);
+}
@@ -0,0 +1,14 @@
+#version 330 core
+out float LogLuminance;
+
+in vec2 TexCoords;
+
+uniform sampler2D image;
+
+void main()
+{
+    vec3 color = texture(image, TexCoords).rgb;
+    // averaging the logarithm (the geometric mean) keeps a few bright pixels from dominating
+    float luminance = dot(color, vec3(0.2126, 0.7152, 0.0722));
+    LogLuminance = log(max(luminance, 0.0001));
+}
@@ -0,0 +1,20 @@
+#version 330 core
+out vec4 FragColor;
+
+in vec2 TexCoords;
+
+uniform sampler2D image;
+uniform vec2 texelSize;
+uniform float amount;
+
+void main()
+{
+    vec3 center = texture(image, TexCoords).rgb;
+    vec3 neighbors = texture(image, TexCoords + vec2(texelSize.x, 0.0)).rgb
+                   + texture(image, TexCoords - vec2(texelSize.x, 0.0)).rgb
+                   + texture(image, TexCoords + vec2(0.0, texelSize.y)).rgb
+                   + texture(image, TexCoords - vec2(0.0, texelSize.y)).rgb;
+    // unsharp mask: push the pixel away from the average of its neighbors
+    vec3 color = center + amount * (4.0 * center - neighbors);
+    FragColor = vec4(max(color, vec3(0.0)), 1.0);
+}
@@ -0,0 +1,60 @@
+#version 330 core
+out vec4 FragColor;
+
+in vec2 TexCoords;
+
+uniform sampler2D image;
+// 0 Reinhard, 1 ACES filmic, 2 Uncharted 2
+uniform int operator;
+// manual exposure multiplier
+uniform float exposure;
+// with auto exposure the scene is scaled so the adapted luminance maps to `key` (middle gray)
+uniform bool autoExposure;
+uniform sampler2D adaptedLuminance;
+uniform float key;
+
+vec3 reinhard(vec3 color)
+{
+    return color / (1.0 + color);
+}
+
+// Krzysztof Narkowicz's fit of the ACES reference rendering transform
+vec3 acesFilmic(vec3 color)
+{
+    return clamp((color * (2.51 * color + 0.03)) / (color * (2.43 * color + 0.59) + 0.14), 0.0, 1.0);
+}
+
+// John Hable's filmic curve from Uncharted 2
+vec3 hable(vec3 x)
+{
+    const float A = 0.15, B = 0.50, C = 0.10, D = 0.20, E = 0.02, F = 0.30;
+    return ((x * (A * x + C * B) + D * E) / (x * (A * x + B) + D * F)) - E / F;
+}
+
+vec3 uncharted2(vec3 color)
+{
+    // linear white point, the curve is normalized so it maps to 1
+    const float W = 11.2;
+    const float exposureBias = 2.0;
+    return hable(exposureBias * color) / hable(vec3(W));
+}
+
+void main()
+{
+    vec3 color = texture(image, TexCoords).rgb;
+
+    float scale = exposure;
+    if (autoExposure) {
+        scale *= key / max(texture(adaptedLuminance, vec2(0.5)).r, 0.0001);
+    }
+    color *= scale;
+
+    if (operator == 0) {
+        color = reinhard(color);
+    } else if (operator == 1) {
+        color = acesFilmic(color);
+    } else {
+        color = uncharted2(color);
+    }
+    FragColor = vec4(color, 1.0);
+}
@@ -0,0 +1,22 @@
+#version 330 core
+out vec4 FragColor;
+
+in vec2 TexCoords;
+
+uniform sampler2D image;
+uniform vec2 texelSize;
+// darkening at the corners, 0 to 1
+uniform float strength;
+// distance from the center where the darkening starts, 0.5 is the middle of an edge
+uniform float radius;
+// width of the falloff
+uniform float softness;
+
+void main()
+{
+    vec3 color = texture(image, TexCoords).rgb;
+    // corrected for the aspect ratio so the vignette is round
+    vec2 offset = (TexCoords - 0.5) * vec2(texelSize.y / texelSize.x, 1.0);
+    float falloff = smoothstep(radius, radius + softness, length(offset));
+    FragColor = vec4(color * (1.0 - strength * falloff), 1.0);
+}
@@ -0,0 +1,249 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::ops::Deref;
+use std::path::{ Path, PathBuf };
+use std::rc::{ Rc, Weak };
+
+use super::mesh::{ Mesh, Vertex };
+use super::ply;
+use super::shader::Shader;
+use super::stl;
+use super::texture::{ Texture2D, TextureData, TextureOptions };
+
+// Shared reference to a loaded asset. Cloning is cheap, the GPU resource is freed when the last handle drops
+pub struct Handle<T>
+{
+    asset: Rc<T>,
+}
+
+impl<T> Handle<T>
+{
+    // shares an asset that isn't tracked by an AssetManager
+    pub fn new(asset: T) -> Handle<T>
+    {
+        Handle { asset: Rc::new(asset) }
+    }
+
+    // true when both handles refer to the same loaded asset
+    pub fn ptr_eq(&self, other: &Handle<T>) -> bool
+    {
+        Rc::ptr_eq(&self.asset, &other.asset)
+    }
+
+    pub fn strong_count(&self) -> usize
+    {
+        Rc::strong_count(&self.asset)
+    }
+}
+
+impl<T> Clone for Handle<T>
+{
+    fn clone(&self) -> Handle<T>
+    {
+        Handle { asset: Rc::clone(&self.asset) }
+    }
+}
+
+impl<T> Deref for Handle<T>
+{
+    type Target = T;
+
+    fn deref(&self) -> &T
+    {
+        &self.asset
+    }
+}
+
+struct Entry<T>
+{
+    asset: Weak<T>,
+    bytes: usize,
+}
+
+// Assets of one type keyed by their source. Only weak references are kept so the cache never keeps an asset alive
+struct Cache<K, T>
+{
+    entries: HashMap<K, Entry<T>>,
+}
+
+impl<K: Eq + Hash, T> Cache<K, T>
+{
+    fn new() -> Cache<K, T>
+    {
+        Cache { entries: HashMap::new() }
+    }
+
+    fn get(&self, key: &K) -> Option<Handle<T>>
+    {
+        self.entries.get(key).and_then(|entry| entry.asset.upgrade()).map(|asset| Handle { asset })
+    }
+
+    fn get_or_load<L, S>(&mut self, key: K, load: L, size: S) -> Result<Handle<T>, String>
+    where L: FnOnce() -> Result<T, String>,
+          S: FnOnce(&T) -> usize
+    {
+        if let Some(handle) = self.get(&key) {
+            return Ok(handle);
+        }
+
+        let asset = Rc::new(load()?);
+        self.entries.insert(key, Entry { asset: Rc::downgrade(&asset), bytes: size(&asset) });
+        Ok(Handle { asset })
+    }
+
+    // removes the entries whose last handle was dropped
+    fn prune(&mut self)
+    {
+        self.entries.retain(|_, entry| entry.asset.strong_count() > 0);
+    }
+
+    // (live asset count, bytes of the live assets)
+    fn usage(&self) -> (usize, usize)
+    {
+        self.entries.values()
+            .filter(|entry| entry.asset.strong_count() > 0)
+            .fold((0, 0), |(count, bytes), entry| (count + 1, bytes + entry.bytes))
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct AssetStats
+{
+    pub textures: usize,
+    pub shaders: usize,
+    pub meshes: usize,
+    // texture and vertex/index buffer memory of the live assets (shader programs are not counted)
+    pub bytes_resident: usize,
+}
+
+impl fmt::Display for AssetStats
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        write!(f, "{} textures, {} shaders, {} meshes, {:.2} MiB resident",
+            self.textures, self.shaders, self.meshes, self.bytes_resident as f64 / (1024.0 * 1024.0))
+    }
+}
+
+// Loads textures, shaders and meshes once per canonical path and hands out shared handles to them
+pub struct AssetManager
+{
+    // the same image loaded with different options (e.g. sRGB and linear) is a different texture
+    textures: Cache<(PathBuf, TextureOptions), Texture2D>,
+    shaders: Cache<(PathBuf, PathBuf), Shader>,
+    meshes: Cache<PathBuf, Mesh>,
+}
+
+// reads an .stl or .ply file without uploading it
+pub fn read_mesh(path: &Path) -> Result<Mesh, String>
+{
+    let extension = path.extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase());
+    match extension.as_deref() {
+        Some("stl") => stl::load_stl(path),
+        Some("ply") => ply::load_ply(path),
+        _ => Err(format!("Unsupported mesh format: {}", path.display())),
+    }
+}
+
+fn mesh_size(mesh: &Mesh) -> usize
+{
+    mesh.vertices.len() * std::mem::size_of::<Vertex>() + mesh.indices.len() * std::mem::size_of::<u32>()
+}
+
+fn canonical_path(path: &Path) -> Result<PathBuf, String>
+{
+    path.canonicalize().map_err(|e| format!("Failed to resolve {}: {}", path.display(), e))
+}
+
+impl AssetManager
+{
+    pub fn new() -> AssetManager
+    {
+        AssetManager {
+            textures: Cache::new(),
+            shaders: Cache::new(),
+            meshes: Cache::new(),
+        }
+    }
+
+    pub fn texture(&mut self, path: &Path, options: TextureOptions) -> Result<Handle<Texture2D>, String>
+    {
+        let key = (canonical_path(path)?, options);
+        self.textures.get_or_load(key, || Texture2D::from_file(path, options), |texture| texture.byte_size())
+    }
+
+    // the texture if it is already loaded, without touching the file
+    pub fn cached_texture(&self, path: &Path, options: TextureOptions) -> Option<Handle<Texture2D>>
+    {
+        self.textures.get(&(canonical_path(path).ok()?, options))
+    }
+
+    // uploads image data decoded elsewhere (e.g. on a loader thread). When the same texture got loaded in the
+    // meantime the existing one is returned and `data` is dropped
+    pub fn texture_from_data(&mut self, path: &Path, options: TextureOptions, data: &TextureData) -> Result<Handle<Texture2D>, String>
+    {
+        let key = (canonical_path(path)?, options);
+        self.textures.get_or_load(key, || Ok(Texture2D::from_data(data, options)), |texture| texture.byte_size())
+    }
+
+    pub fn shader(&mut self, vertex_path: &Path, fragment_path: &Path) -> Result<Handle<Shader>, String>
+    {
+        let key = (canonical_path(vertex_path)?, canonical_path(fragment_path)?);
+        let (vertex, fragment) = (key.0.to_str().map(String::from), key.1.to_str().map(String::from));
+
+        self.shaders.get_or_load(key, || {
+            let vertex = vertex.ok_or("Shader path is not valid UTF-8")?;
+            let fragment = fragment.ok_or("Shader path is not valid UTF-8")?;
+            let mut shader = Shader::new();
+            shader.create_program(&vertex, &fragment);
+            Ok(shader)
+        }, |_| 0)
+    }
+
+    // loads an .stl or .ply file and uploads it
+    pub fn mesh(&mut self, path: &Path) -> Result<Handle<Mesh>, String>
+    {
+        let key = canonical_path(path)?;
+
+        self.meshes.get_or_load(key, || {
+            let mut mesh = read_mesh(path)?;
+            mesh.setup_mesh();
+            Ok(mesh)
+        }, mesh_size)
+    }
+
+    pub fn cached_mesh(&self, path: &Path) -> Option<Handle<Mesh>>
+    {
+        self.meshes.get(&canonical_path(path).ok()?)
+    }
+
+    // uploads a mesh read elsewhere (see `read_mesh`), unless the same file got loaded in the meantime
+    pub fn mesh_from_data(&mut self, path: &Path, mesh: Mesh) -> Result<Handle<Mesh>, String>
+    {
+        let key = canonical_path(path)?;
+
+        self.meshes.get_or_load(key, || {
+            let mut mesh = mesh;
+            mesh.setup_mesh();
+            Ok(mesh)
+        }, mesh_size)
+    }
+
+    // forgets the assets that are no longer referenced. Their GPU resources were already freed with the last handle
+    pub fn collect_garbage(&mut self)
+    {
+        self.textures.prune();
+        self.shaders.prune();
+        self.meshes.prune();
+    }
+
+    pub fn stats(&self) -> AssetStats
+    {
+        let (textures, texture_bytes) = self.textures.usage();
+        let (shaders, _) = self.shaders.usage();
+        let (meshes, mesh_bytes) = self.meshes.usage();
+
+        AssetStats { textures, shaders, meshes, bytes_resident: texture_bytes + mesh_bytes }
+    }
+}
@@ -0,0 +1,306 @@
+use threadpool::ThreadPool;
+
+use std::cell::RefCell;
+use std::collections::{ HashMap, VecDeque };
+use std::path::{ Path, PathBuf };
+use std::rc::Rc;
+use std::sync::mpsc::{ channel, Receiver, Sender };
+use std::time::{ Duration, Instant };
+
+use super::assets::{ self, AssetManager, Handle };
+use super::mesh::Mesh;
+use super::texture::{ PixelFormat, Texture2D, TextureData, TextureFilter, TextureOptions };
+
+pub enum LoadState<T> {
+    Loading,
+    Ready(Handle<T>),
+    Failed(String),
+}
+
+type Callback<T> = Box<dyn FnOnce(Result<&Handle<T>, &str>)>;
+
+struct Slot<T>
+{
+    state: RefCell<LoadState<T>>,
+    callbacks: RefCell<Vec<Callback<T>>>,
+}
+
+impl<T> Slot<T>
+{
+    fn new(state: LoadState<T>) -> Rc<Slot<T>>
+    {
+        Rc::new(Slot { state: RefCell::new(state), callbacks: RefCell::new(Vec::new()) })
+    }
+
+    fn complete(&self, result: Result<Handle<T>, String>)
+    {
+        let state = match result {
+            Ok(handle) => LoadState::Ready(handle),
+            Err(e) => LoadState::Failed(e),
+        };
+        *self.state.borrow_mut() = state;
+
+        // callbacks may register more callbacks on the same handle, take them out first
+        let callbacks: Vec<Callback<T>> = self.callbacks.borrow_mut().drain(..).collect();
+        let state = self.state.borrow();
+        for callback in callbacks {
+            match &*state {
+                LoadState::Ready(handle) => callback(Ok(handle)),
+                LoadState::Failed(e) => callback(Err(e)),
+                LoadState::Loading => {},
+            }
+        }
+    }
+}
+
+// An asset that may still be loading. Poll it every frame or register a callback with `on_ready`
+pub struct AsyncHandle<T>
+{
+    slot: Rc<Slot<T>>,
+    // used while loading and after a failure
+    placeholder: Option<Handle<T>>,
+}
+
+impl<T> Clone for AsyncHandle<T>
+{
+    fn clone(&self) -> AsyncHandle<T>
+    {
+        AsyncHandle { slot: Rc::clone(&self.slot), placeholder: self.placeholder.clone() }
+    }
+}
+
+impl<T> AsyncHandle<T>
+{
+    pub fn is_loading(&self) -> bool
+    {
+        matches!(*self.slot.state.borrow(), LoadState::Loading)
+    }
+
+    pub fn is_ready(&self) -> bool
+    {
+        matches!(*self.slot.state.borrow(), LoadState::Ready(_))
+    }
+
+    pub fn error(&self) -> Option<String>
+    {
+        match &*self.slot.state.borrow() {
+            LoadState::Failed(e) => Some(e.clone()),
+            _ => None,
+        }
+    }
+
+    // the loaded asset, None while loading or after a failure
+    pub fn get(&self) -> Option<Handle<T>>
+    {
+        match &*self.slot.state.borrow() {
+            LoadState::Ready(handle) => Some(handle.clone()),
+            _ => None,
+        }
+    }
+
+    // the loaded asset, or the placeholder until then
+    pub fn current(&self) -> Option<Handle<T>>
+    {
+        self.get().or_else(|| self.placeholder.clone())
+    }
+
+    // runs `callback` on the GL thread once the load finished (right away if it already did)
+    pub fn on_ready<F>(&self, callback: F)
+    where F: FnOnce(Result<&Handle<T>, &str>) + 'static
+    {
+        match &*self.slot.state.borrow() {
+            LoadState::Ready(handle) => callback(Ok(handle)),
+            LoadState::Failed(e) => callback(Err(e)),
+            LoadState::Loading => self.slot.callbacks.borrow_mut().push(Box::new(callback)),
+        }
+    }
+}
+
+impl AsyncHandle<Texture2D>
+{
+    // binds the loaded texture, or the placeholder while it is loading
+    pub fn bind(&self, unit: u32)
+    {
+        match &*self.slot.state.borrow() {
+            LoadState::Ready(texture) => texture.bind(unit),
+            _ => if let Some(placeholder) = &self.placeholder { placeholder.bind(unit) },
+        }
+    }
+}
+
+// decoded on a worker thread, waiting for the GL upload
+enum Decoded {
+    Texture(TextureData),
+    Mesh(Mesh),
+}
+
+enum Request {
+    Texture { path: PathBuf, options: TextureOptions, slot: Rc<Slot<Texture2D>> },
+    Mesh { path: PathBuf, slot: Rc<Slot<Mesh>> },
+}
+
+// Decodes textures and meshes on a thread pool. GL calls stay on the thread owning the context: `update` uploads
+// the finished results there, a few per frame, and stores them in the AssetManager so they are shared with
+// synchronous loads of the same file
+pub struct AsyncLoader
+{
+    pool: ThreadPool,
+    sender: Sender<(u64, Result<Decoded, String>)>,
+    receiver: Receiver<(u64, Result<Decoded, String>)>,
+    requests: HashMap<u64, Request>,
+    // decoded results not uploaded yet because the frame budget ran out
+    uploads: VecDeque<(u64, Result<Decoded, String>)>,
+    next_id: u64,
+    placeholder: Handle<Texture2D>,
+}
+
+// 8x8 magenta and black checkerboard, hard to miss when a texture never finishes loading
+fn placeholder_texture() -> Texture2D
+{
+    let pixels: Vec<u8> = (0..64)
+        .flat_map(|i| if (i % 8 + i / 8) % 2 == 0 { [255, 0, 255, 255] } else { [0, 0, 0, 255] })
+        .collect();
+    let format = PixelFormat { internal_format: gl::RGBA8, format: gl::RGBA, type_: gl::UNSIGNED_BYTE, pixel_size: 4 };
+    let options = TextureOptions {
+        min_filter: TextureFilter::Nearest,
+        mag_filter: TextureFilter::Nearest,
+        mipmap_filter: None,
+        ..Default::default()
+    };
+    Texture2D::from_pixels(8, 8, format, &pixels, options)
+}
+
+impl AsyncLoader
+{
+    // `threads` decoding threads, 0 picks one per CPU. Needs a current GL context for the placeholder texture
+    pub fn new(threads: usize) -> AsyncLoader
+    {
+        let threads = if threads == 0 {
+            std::thread::available_parallelism().map(|n| n.get()).unwrap_or(4)
+        } else {
+            threads
+        };
+        let (sender, receiver) = channel();
+
+        AsyncLoader {
+            pool: ThreadPool::with_name("asset loader".to_string(), threads),
+            sender,
+            receiver,
+            requests: HashMap::new(),
+            uploads: VecDeque::new(),
+            next_id: 0,
+            placeholder: Handle::new(placeholder_texture()),
+        }
+    }
+
+    fn submit<F>(&mut self, request: Request, decode: F)
+    where F: FnOnce() -> Result<Decoded, String> + Send + 'static
+    {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.requests.insert(id, request);
+
+        let sender = self.sender.clone();
+        self.pool.execute(move || {
+            // a panicking decoder would otherwise leave the request loading forever
+            let decoded = std::panic::catch_unwind(std::panic::AssertUnwindSafe(decode))
+                .unwrap_or_else(|_| Err("Asset loader thread panicked".to_string()));
+            // the loader may be gone already, nobody wants the result then
+            let _ = sender.send((id, decoded));
+        });
+    }
+
+    pub fn texture(&mut self, assets: &AssetManager, path: &Path, options: TextureOptions) -> AsyncHandle<Texture2D>
+    {
+        let placeholder = Some(self.placeholder.clone());
+        if let Some(texture) = assets.cached_texture(path, options) {
+            return AsyncHandle { slot: Slot::new(LoadState::Ready(texture)), placeholder };
+        }
+
+        let slot = Slot::new(LoadState::Loading);
+        let request = Request::Texture { path: path.to_path_buf(), options, slot: Rc::clone(&slot) };
+        let path = path.to_path_buf();
+        self.submit(request, move || TextureData::load(&path).map(Decoded::Texture));
+
+        AsyncHandle { slot, placeholder }
+    }
+
+    // .stl or .ply, there is no placeholder mesh
+    pub fn mesh(&mut self, assets: &AssetManager, path: &Path) -> AsyncHandle<Mesh>
+    {
+        if let Some(mesh) = assets.cached_mesh(path) {
+            return AsyncHandle { slot: Slot::new(LoadState::Ready(mesh)), placeholder: None };
+        }
+
+        let slot = Slot::new(LoadState::Loading);
+        let request = Request::Mesh { path: path.to_path_buf(), slot: Rc::clone(&slot) };
+        let path = path.to_path_buf();
+        self.submit(request, move || assets::read_mesh(&path).map(Decoded::Mesh));
+
+        AsyncHandle { slot, placeholder: None }
+    }
+
+    fn finish(&mut self, assets: &mut AssetManager, id: u64, decoded: Result<Decoded, String>)
+    {
+        let request = match self.requests.remove(&id) {
+            Some(request) => request,
+            None => return,
+        };
+
+        match (request, decoded) {
+            (Request::Texture { path, options, slot }, Ok(Decoded::Texture(data))) => {
+                slot.complete(assets.texture_from_data(&path, options, &data));
+            },
+            (Request::Mesh { path, slot }, Ok(Decoded::Mesh(mesh))) => {
+                slot.complete(assets.mesh_from_data(&path, mesh));
+            },
+            (Request::Texture { slot, .. }, Err(e)) => {
+                println!("{}", e);
+                slot.complete(Err(e));
+            },
+            (Request::Mesh { slot, .. }, Err(e)) => {
+                println!("{}", e);
+                slot.complete(Err(e));
+            },
+            _ => unreachable!("decoded asset doesn't match its request"),
+        }
+    }
+
+    // Call once per frame on the GL thread. Uploads finished loads until `budget` is used up, at least one per call
+    // so loading always progresses. Returns the number of finished loads
+    pub fn update(&mut self, assets: &mut AssetManager, budget: Duration) -> usize
+    {
+        self.uploads.extend(self.receiver.try_iter());
+
+        let start = Instant::now();
+        let mut finished = 0;
+        while let Some((id, decoded)) = self.uploads.pop_front() {
+            self.finish(assets, id, decoded);
+            finished += 1;
+            if start.elapsed() >= budget {
+                break;
+            }
+        }
+        finished
+    }
+
+    // blocks until every pending load is uploaded, e.g. before rendering a frame that must be complete
+    pub fn finish_all(&mut self, assets: &mut AssetManager)
+    {
+        while let Some((id, decoded)) = self.uploads.pop_front() {
+            self.finish(assets, id, decoded);
+        }
+        while !self.requests.is_empty() {
+            match self.receiver.recv() {
+                Ok((id, decoded)) => self.finish(assets, id, decoded),
+                Err(_) => break,
+            }
+        }
+    }
+
+    // loads requested and not uploaded yet
+    pub fn pending(&self) -> usize
+    {
+        self.requests.len()
+    }
+}
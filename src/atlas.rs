@@ -0,0 +1,387 @@
+use image::RgbaImage;
+use nalgebra_glm::Vec2;
+
+use super::texture::{ Texture2DArray, TextureOptions };
+
+// Bottom-left skyline packer. The skyline is the top edge of the packed rectangles, stored as horizontal segments
+// from left to right; new rectangles are placed where they end up the lowest
+pub struct SkylinePacker
+{
+    pub width: u32,
+    pub height: u32,
+    // (x, y, width) of each segment
+    skyline: Vec<(u32, u32, u32)>,
+}
+
+impl SkylinePacker
+{
+    pub fn new(width: u32, height: u32) -> SkylinePacker
+    {
+        SkylinePacker { width, height, skyline: vec![(0, 0, width)] }
+    }
+
+    // y where a rectangle of `width` starting at segment `index` would rest, None when it leaves the page
+    fn fit(&self, index: usize, width: u32, height: u32) -> Option<u32>
+    {
+        let x = self.skyline[index].0;
+        if x + width > self.width {
+            return None;
+        }
+
+        let mut y = 0;
+        let mut remaining = width as i64;
+        for &(_, segment_y, segment_width) in &self.skyline[index..] {
+            if remaining <= 0 {
+                break;
+            }
+            y = y.max(segment_y);
+            remaining -= segment_width as i64;
+        }
+
+        if y + height > self.height { None } else { Some(y) }
+    }
+
+    // position of the top left corner of the placed rectangle, None when it doesn't fit anymore
+    pub fn insert(&mut self, width: u32, height: u32) -> Option<(u32, u32)>
+    {
+        if width == 0 || height == 0 {
+            return Some((0, 0));
+        }
+
+        // lowest top edge first, then the narrowest segment to waste less space
+        let mut best: Option<(usize, u32)> = None;
+        for index in 0..self.skyline.len() {
+            if let Some(y) = self.fit(index, width, height) {
+                let better = match best {
+                    None => true,
+                    Some((best_index, best_y)) => {
+                        y < best_y || (y == best_y && self.skyline[index].2 < self.skyline[best_index].2)
+                    },
+                };
+                if better {
+                    best = Some((index, y));
+                }
+            }
+        }
+
+        let (index, y) = best?;
+        let x = self.skyline[index].0;
+        self.skyline.insert(index, (x, y + height, width));
+
+        // cut the segments now covered by the new one
+        let right = x + width;
+        let next = index + 1;
+        while next < self.skyline.len() {
+            let (segment_x, segment_y, segment_width) = self.skyline[next];
+            if segment_x >= right {
+                break;
+            }
+            let segment_right = segment_x + segment_width;
+            if segment_right <= right {
+                self.skyline.remove(next);
+            } else {
+                self.skyline[next] = (right, segment_y, segment_right - right);
+                break;
+            }
+        }
+
+        // merge neighbours at the same height
+        let mut i = 0;
+        while i + 1 < self.skyline.len() {
+            if self.skyline[i].1 == self.skyline[i + 1].1 {
+                self.skyline[i].2 += self.skyline[i + 1].2;
+                self.skyline.remove(i + 1);
+            } else {
+                i += 1;
+            }
+        }
+
+        Some((x, y))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AtlasOptions
+{
+    pub page_width: u32,
+    pub page_height: u32,
+    // transparent pixels left between neighbouring sub-images
+    pub padding: u32,
+    // border around each sub-image filled with copies of its edge pixels, so linear filtering at the edge doesn't
+    // pick up the neighbours
+    pub gutter: u32,
+    // sub-image cells start at (and span) multiples of this, a power of two
+    pub alignment: u32,
+}
+
+impl Default for AtlasOptions
+{
+    fn default() -> AtlasOptions
+    {
+        AtlasOptions { page_width: 1024, page_height: 1024, padding: 0, gutter: 1, alignment: 1 }
+    }
+}
+
+impl AtlasOptions
+{
+    // gutter and alignment such that the first `mip_levels` levels never mix texels of different sub-images: at
+    // level n a cell aligned to 2^(levels - 1) still covers whole texels and keeps at least one gutter texel
+    pub fn mip_safe(page_width: u32, page_height: u32, mip_levels: u32) -> AtlasOptions
+    {
+        let alignment = 1 << (mip_levels.max(1) - 1);
+        AtlasOptions { page_width, page_height, padding: 0, gutter: alignment, alignment }
+    }
+
+    // number of mip levels that stay separated, see `mip_safe`
+    pub fn safe_mip_levels(&self) -> u32
+    {
+        self.alignment.min(self.gutter.max(1)).trailing_zeros() + 1
+    }
+}
+
+// Where a sub-image ended up. Coordinates are in pixels of the page, without the gutter
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AtlasRegion
+{
+    pub page: usize,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    // texture coordinates of the corners for pages uploaded top row first (`flip_vertically: false`), so v grows
+    // downwards like y
+    pub uv_min: Vec2,
+    pub uv_max: Vec2,
+}
+
+pub struct Atlas
+{
+    pub options: AtlasOptions,
+    pub pages: Vec<RgbaImage>,
+    // one per input image, in input order
+    pub regions: Vec<AtlasRegion>,
+}
+
+fn align(value: u32, alignment: u32) -> u32
+{
+    value.div_ceil(alignment) * alignment
+}
+
+// Packs the images into as many pages as needed. Images are placed tallest first, which suits the skyline packer
+pub fn build_atlas(images: &[RgbaImage], options: &AtlasOptions) -> Result<Atlas, String>
+{
+    if !options.alignment.is_power_of_two() {
+        return Err(format!("Atlas alignment {} is not a power of two", options.alignment));
+    }
+
+    let cell_size = |image: &RgbaImage| {
+        let (width, height) = image.dimensions();
+        let border = 2 * options.gutter + options.padding;
+        (align(width + border, options.alignment), align(height + border, options.alignment))
+    };
+
+    let mut order: Vec<usize> = (0..images.len()).collect();
+    order.sort_by_key(|&i| {
+        let (width, height) = images[i].dimensions();
+        (std::cmp::Reverse(height), std::cmp::Reverse(width))
+    });
+
+    let mut packers: Vec<SkylinePacker> = Vec::new();
+    let mut placements = vec![(0, 0, 0); images.len()];
+
+    for &i in &order {
+        let (cell_width, cell_height) = cell_size(&images[i]);
+        if cell_width > options.page_width || cell_height > options.page_height {
+            let (width, height) = images[i].dimensions();
+            return Err(format!("Image {} ({}x{}) doesn't fit in a {}x{} atlas page",
+                i, width, height, options.page_width, options.page_height));
+        }
+
+        let placed = packers.iter_mut().enumerate()
+            .find_map(|(page, packer)| packer.insert(cell_width, cell_height).map(|(x, y)| (page, x, y)));
+        placements[i] = match placed {
+            Some(placement) => placement,
+            None => {
+                let mut packer = SkylinePacker::new(options.page_width, options.page_height);
+                let (x, y) = packer.insert(cell_width, cell_height).unwrap();
+                packers.push(packer);
+                (packers.len() - 1, x, y)
+            },
+        };
+    }
+
+    let mut pages: Vec<RgbaImage> = packers.iter().map(|_| RgbaImage::new(options.page_width, options.page_height)).collect();
+    let (page_width, page_height) = (options.page_width as f32, options.page_height as f32);
+
+    let regions = images.iter().zip(&placements).map(|(image, &(page, cell_x, cell_y))| {
+        let (width, height) = image.dimensions();
+        let gutter = options.gutter;
+
+        // the image and its gutter, gutter pixels repeat the closest edge pixel
+        if width > 0 && height > 0 {
+            for y in 0..height + 2 * gutter {
+                for x in 0..width + 2 * gutter {
+                    let source_x = x.saturating_sub(gutter).min(width - 1);
+                    let source_y = y.saturating_sub(gutter).min(height - 1);
+                    pages[page].put_pixel(cell_x + x, cell_y + y, *image.get_pixel(source_x, source_y));
+                }
+            }
+        }
+
+        let (x, y) = (cell_x + gutter, cell_y + gutter);
+        AtlasRegion {
+            page,
+            x,
+            y,
+            width,
+            height,
+            uv_min: nalgebra_glm::vec2(x as f32 / page_width, y as f32 / page_height),
+            uv_max: nalgebra_glm::vec2((x + width) as f32 / page_width, (y + height) as f32 / page_height),
+        }
+    }).collect();
+
+    Ok(Atlas { options: *options, pages, regions })
+}
+
+impl Atlas
+{
+    pub fn from_files(paths: &[&std::path::Path], options: &AtlasOptions) -> Result<Atlas, String>
+    {
+        let mut images = Vec::new();
+        for path in paths {
+            let image = image::open(path).map_err(|e| format!("Failed to load image {}: {}", path.display(), e))?;
+            images.push(image.to_rgba8());
+        }
+        build_atlas(&images, options)
+    }
+
+    // one array layer per page. The pages are kept unflipped (top row first) to match the region UVs, and the mip
+    // chain stops at the last level the gutters protect
+    pub fn upload(&self, options: TextureOptions) -> Result<Texture2DArray, String>
+    {
+        let options = TextureOptions { flip_vertically: false, flip_horizontally: false, ..options };
+        let texture = Texture2DArray::from_images(&self.pages, options)?;
+
+        if options.mipmap_filter.is_some() {
+            let max_level = self.options.safe_mip_levels().min(texture.mip_levels) - 1;
+            unsafe {
+                gl::BindTexture(gl::TEXTURE_2D_ARRAY, texture.id);
+                gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_MAX_LEVEL, max_level as i32);
+            }
+        }
+
+        Ok(texture)
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    // (x, y, width, height) rectangles overlap when they share any pixel
+    fn overlap(a: (u32, u32, u32, u32), b: (u32, u32, u32, u32)) -> bool
+    {
+        a.0 < b.0 + b.2 && b.0 < a.0 + a.2 && a.1 < b.1 + b.3 && b.1 < a.1 + a.3
+    }
+
+    // image whose pixels encode their own position
+    fn numbered(width: u32, height: u32, tag: u8) -> RgbaImage
+    {
+        RgbaImage::from_fn(width, height, |x, y| image::Rgba([x as u8, y as u8, tag, 255]))
+    }
+
+    #[test]
+    fn packed_rectangles_stay_apart_and_inside()
+    {
+        let mut packer = SkylinePacker::new(128, 128);
+        let mut placed = Vec::new();
+        let mut seed = 7u32;
+        for _ in 0..60 {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            let (width, height) = (1 + (seed >> 8) % 24, 1 + (seed >> 20) % 24);
+            if let Some((x, y)) = packer.insert(width, height) {
+                assert!(x + width <= 128 && y + height <= 128);
+                placed.push((x, y, width, height));
+            }
+        }
+
+        assert!(placed.len() > 20, "only {} rectangles placed", placed.len());
+        for (i, &a) in placed.iter().enumerate() {
+            for &b in &placed[i + 1..] {
+                assert!(!overlap(a, b), "{:?} overlaps {:?}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn full_pages_reject_rectangles()
+    {
+        let mut packer = SkylinePacker::new(64, 64);
+        let corners: Vec<Option<(u32, u32)>> = (0..4).map(|_| packer.insert(32, 32)).collect();
+        assert_eq!(corners, [Some((0, 0)), Some((32, 0)), Some((0, 32)), Some((32, 32))]);
+        assert_eq!(packer.insert(1, 1), None);
+
+        assert_eq!(SkylinePacker::new(64, 64).insert(65, 1), None);
+        assert_eq!(SkylinePacker::new(64, 64).insert(1, 65), None);
+    }
+
+    #[test]
+    fn overflowing_images_start_new_pages()
+    {
+        let options = AtlasOptions { page_width: 64, page_height: 64, padding: 0, gutter: 0, alignment: 1 };
+        let images: Vec<RgbaImage> = (0..5).map(|i| numbered(40, 40, i)).collect();
+        let atlas = build_atlas(&images, &options).unwrap();
+
+        assert_eq!(atlas.pages.len(), 5);
+        let pages: Vec<usize> = atlas.regions.iter().map(|region| region.page).collect();
+        assert_eq!(pages, [0, 1, 2, 3, 4]);
+
+        assert!(build_atlas(&[numbered(65, 8, 0)], &options).is_err());
+    }
+
+    #[test]
+    fn gutters_repeat_the_edge_pixels()
+    {
+        let options = AtlasOptions { page_width: 32, page_height: 32, padding: 1, gutter: 2, alignment: 1 };
+        let images = [numbered(3, 2, 1), numbered(4, 4, 2)];
+        let atlas = build_atlas(&images, &options).unwrap();
+        assert_eq!(atlas.pages.len(), 1);
+
+        for (image, region) in images.iter().zip(&atlas.regions) {
+            assert_eq!((region.width, region.height), image.dimensions());
+            assert_eq!(region.uv_min, nalgebra_glm::vec2(region.x as f32 / 32.0, region.y as f32 / 32.0));
+            assert_eq!(region.uv_max.x, (region.x + region.width) as f32 / 32.0);
+
+            let page = &atlas.pages[region.page];
+            for y in 0..region.height + 4 {
+                for x in 0..region.width + 4 {
+                    let source = (x.saturating_sub(2).min(region.width - 1), y.saturating_sub(2).min(region.height - 1));
+                    assert_eq!(page.get_pixel(region.x - 2 + x, region.y - 2 + y), image.get_pixel(source.0, source.1));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn mip_safe_cells_are_aligned()
+    {
+        let options = AtlasOptions::mip_safe(256, 256, 3);
+        assert_eq!((options.alignment, options.gutter), (4, 4));
+        assert_eq!(options.safe_mip_levels(), 3);
+
+        let images: Vec<RgbaImage> = [(5, 3), (17, 9), (1, 1), (8, 8), (30, 2)].iter()
+            .enumerate()
+            .map(|(i, &(width, height))| numbered(width, height, i as u8))
+            .collect();
+        let atlas = build_atlas(&images, &options).unwrap();
+        for region in &atlas.regions {
+            // the cell, gutter included, starts on the alignment
+            assert_eq!(((region.x - options.gutter) % 4, (region.y - options.gutter) % 4), (0, 0));
+        }
+
+        let unaligned = AtlasOptions { alignment: 3, ..options };
+        assert!(build_atlas(&images, &unaligned).is_err());
+        assert_eq!(AtlasOptions::default().safe_mip_levels(), 1);
+    }
+}
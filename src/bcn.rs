@@ -0,0 +1,953 @@
+// CPU decoders for the BC1-BC7 block compression formats, used when the GL driver can't sample them directly.
+// Every block covers 4x4 pixels and decodes to RGBA8, or to RGBA floats for the BC6H half float format
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BlockFormat {
+    // `alpha` selects the 1 bit punch-through alpha variant
+    Bc1 { alpha: bool },
+    Bc2,
+    Bc3,
+    Bc4 { signed: bool },
+    Bc5 { signed: bool },
+    Bc6h { signed: bool },
+    Bc7,
+}
+
+impl BlockFormat
+{
+    pub fn block_size(self) -> usize
+    {
+        match self {
+            BlockFormat::Bc1 { .. } | BlockFormat::Bc4 { .. } => 8,
+            _ => 16,
+        }
+    }
+
+    // size in bytes of a `width` x `height` image. Saturates for sizes no file can hold, so the bounds checks of
+    // the loaders fail instead of overflowing on bogus headers
+    pub fn image_size(self, width: u32, height: u32) -> usize
+    {
+        let blocks_x = width.div_ceil(4).max(1) as usize;
+        let blocks_y = height.div_ceil(4).max(1) as usize;
+        blocks_x.saturating_mul(blocks_y).saturating_mul(self.block_size())
+    }
+}
+
+// decodes a whole image into tightly packed RGBA8 rows, top row first like the block data. Signed formats are
+// remapped from [-1, 1] to [0, 255], BC6H is clamped to [0, 1]
+pub fn decode_image(format: BlockFormat, width: u32, height: u32, data: &[u8]) -> Result<Vec<u8>, String>
+{
+    let decode: fn(&[u8], &mut [[u8; 4]; 16]) = match format {
+        BlockFormat::Bc1 { alpha: true } => |block, out| decode_bc1(block, out, true),
+        BlockFormat::Bc1 { alpha: false } => |block, out| decode_bc1(block, out, false),
+        BlockFormat::Bc2 => decode_bc2,
+        BlockFormat::Bc3 => decode_bc3,
+        BlockFormat::Bc4 { signed: true } => |block, out| decode_bc4(block, out, true),
+        BlockFormat::Bc4 { signed: false } => |block, out| decode_bc4(block, out, false),
+        BlockFormat::Bc5 { signed: true } => |block, out| decode_bc5(block, out, true),
+        BlockFormat::Bc5 { signed: false } => |block, out| decode_bc5(block, out, false),
+        BlockFormat::Bc6h { signed: true } => |block, out| clamp_to_rgba8(block, out, true),
+        BlockFormat::Bc6h { signed: false } => |block, out| clamp_to_rgba8(block, out, false),
+        BlockFormat::Bc7 => decode_bc7,
+    };
+
+    decode_blocks(format, width, height, data, decode)
+}
+
+// decodes a BC6H image into tightly packed RGBA float rows, alpha is always 1
+pub fn decode_image_f32(format: BlockFormat, width: u32, height: u32, data: &[u8]) -> Result<Vec<f32>, String>
+{
+    let decode: fn(&[u8], &mut [[f32; 4]; 16]) = match format {
+        BlockFormat::Bc6h { signed: true } => |block, out| decode_bc6h(block, out, true),
+        BlockFormat::Bc6h { signed: false } => |block, out| decode_bc6h(block, out, false),
+        _ => return Err(format!("{:?} is not a float format", format)),
+    };
+
+    decode_blocks(format, width, height, data, decode)
+}
+
+fn decode_blocks<T: Copy + Default>(format: BlockFormat, width: u32, height: u32, data: &[u8],
+    decode: fn(&[u8], &mut [[T; 4]; 16])) -> Result<Vec<T>, String>
+{
+    let block_size = format.block_size();
+    if data.len() < format.image_size(width, height) {
+        return Err(format!("{:?} data too short for {}x{}", format, width, height));
+    }
+
+    let blocks_x = width.div_ceil(4).max(1);
+    let mut pixels = vec![T::default(); width as usize * height as usize * 4];
+    let mut texels = [[T::default(); 4]; 16];
+
+    for (i, block) in data.chunks_exact(block_size).take(format.image_size(width, height) / block_size).enumerate() {
+        decode(block, &mut texels);
+
+        let (bx, by) = (i as u32 % blocks_x * 4, i as u32 / blocks_x * 4);
+        for (j, texel) in texels.iter().enumerate() {
+            let (x, y) = (bx + j as u32 % 4, by + j as u32 / 4);
+            if x < width && y < height {
+                let offset = (y as usize * width as usize + x as usize) * 4;
+                pixels[offset..offset + 4].copy_from_slice(texel);
+            }
+        }
+    }
+
+    Ok(pixels)
+}
+
+fn rgb565(value: u16) -> [u8; 4]
+{
+    let r = ((value >> 11) & 0x1f) as u32;
+    let g = ((value >> 5) & 0x3f) as u32;
+    let b = (value & 0x1f) as u32;
+    [((r << 3) | (r >> 2)) as u8, ((g << 2) | (g >> 4)) as u8, ((b << 3) | (b >> 2)) as u8, 255]
+}
+
+// color part shared by BC1-BC3. BC2 and BC3 always use the four color mode
+fn decode_bc1(block: &[u8], out: &mut [[u8; 4]; 16], allow_transparent: bool)
+{
+    let c0 = u16::from_le_bytes([block[0], block[1]]);
+    let c1 = u16::from_le_bytes([block[2], block[3]]);
+    let (a, b) = (rgb565(c0), rgb565(c1));
+
+    let mut palette = [a, b, [0; 4], [0; 4]];
+    let mix = |wa: u32, wb: u32, div: u32| {
+        let mut c = [0u8; 4];
+        for i in 0..3 {
+            c[i] = ((a[i] as u32 * wa + b[i] as u32 * wb) / div) as u8;
+        }
+        c[3] = 255;
+        c
+    };
+
+    if c0 > c1 || !allow_transparent {
+        palette[2] = mix(2, 1, 3);
+        palette[3] = mix(1, 2, 3);
+    } else {
+        palette[2] = mix(1, 1, 2);
+        palette[3] = [0, 0, 0, 0];
+    }
+
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+    for (i, texel) in out.iter_mut().enumerate() {
+        *texel = palette[((indices >> (2 * i)) & 3) as usize];
+    }
+}
+
+fn decode_bc2(block: &[u8], out: &mut [[u8; 4]; 16])
+{
+    decode_bc1(&block[8..16], out, false);
+
+    let alpha = u64::from_le_bytes([block[0], block[1], block[2], block[3], block[4], block[5], block[6], block[7]]);
+    for (i, texel) in out.iter_mut().enumerate() {
+        texel[3] = (((alpha >> (4 * i)) & 0xf) * 17) as u8;
+    }
+}
+
+// 8 byte single channel block of BC3 alpha, BC4 and BC5. Signed blocks store snorm endpoints in [-127, 127]
+// (-128 is clamped to -127), their values are remapped to [0, 255] once interpolated
+fn decode_channel(block: &[u8], signed: bool) -> [u8; 16]
+{
+    let (a0, a1, min, max) = if signed {
+        ((block[0] as i8).max(-127) as i32, (block[1] as i8).max(-127) as i32, -127, 127)
+    } else {
+        (block[0] as i32, block[1] as i32, 0, 255)
+    };
+    let mut palette = [0i32; 8];
+    palette[0] = a0;
+    palette[1] = a1;
+
+    if a0 > a1 {
+        for i in 1..7 {
+            palette[i + 1] = ((7 - i as i32) * a0 + i as i32 * a1) / 7;
+        }
+    } else {
+        for i in 1..5 {
+            palette[i + 1] = ((5 - i as i32) * a0 + i as i32 * a1) / 5;
+        }
+        palette[6] = min;
+        palette[7] = max;
+    }
+
+    let mut remapped = [0u8; 8];
+    for (out, value) in remapped.iter_mut().zip(palette.iter()) {
+        *out = if signed { (((value + 127) * 255 + 127) / 254) as u8 } else { *value as u8 };
+    }
+
+    let mut bits = [0u8; 8];
+    bits[..6].copy_from_slice(&block[2..8]);
+    let indices = u64::from_le_bytes(bits);
+
+    let mut values = [0u8; 16];
+    for (i, value) in values.iter_mut().enumerate() {
+        *value = remapped[((indices >> (3 * i)) & 7) as usize];
+    }
+    values
+}
+
+fn decode_bc3(block: &[u8], out: &mut [[u8; 4]; 16])
+{
+    decode_bc1(&block[8..16], out, false);
+
+    let alpha = decode_channel(&block[0..8], false);
+    for (texel, a) in out.iter_mut().zip(alpha.iter()) {
+        texel[3] = *a;
+    }
+}
+
+// a single red channel, sampled as (r, 0, 0, 1) like a GL_RED texture
+fn decode_bc4(block: &[u8], out: &mut [[u8; 4]; 16], signed: bool)
+{
+    let red = decode_channel(block, signed);
+    for (texel, r) in out.iter_mut().zip(red.iter()) {
+        *texel = [*r, 0, 0, 255];
+    }
+}
+
+fn decode_bc5(block: &[u8], out: &mut [[u8; 4]; 16], signed: bool)
+{
+    let red = decode_channel(&block[0..8], signed);
+    let green = decode_channel(&block[8..16], signed);
+    for (i, texel) in out.iter_mut().enumerate() {
+        *texel = [red[i], green[i], 0, 255];
+    }
+}
+
+// BC7 partitions for two subsets, bit i is the subset of pixel i
+const PARTITIONS_2: [u16; 64] = [
+    0xcccc, 0x8888, 0xeeee, 0xecc8, 0xc880, 0xfeec, 0xfec8, 0xec80,
+    0xc800, 0xffec, 0xfe80, 0xe800, 0xffe8, 0xff00, 0xfff0, 0xf000,
+    0xf710, 0x008e, 0x7100, 0x08ce, 0x008c, 0x7310, 0x3100, 0x8cce,
+    0x088c, 0x3110, 0x6666, 0x366c, 0x17e8, 0x0ff0, 0x718e, 0x399c,
+    0xaaaa, 0xf0f0, 0x5a5a, 0x33cc, 0x3c3c, 0x55aa, 0x9696, 0xa55a,
+    0x73ce, 0x13c8, 0x324c, 0x3bdc, 0x6996, 0xc33c, 0x9966, 0x0660,
+    0x0272, 0x04e4, 0x4e40, 0x2720, 0xc936, 0x936c, 0x39c6, 0x639c,
+    0x9336, 0x9cc6, 0x817e, 0xe718, 0xccf0, 0x0fcc, 0x7744, 0xee22,
+];
+
+// BC7 partitions for three subsets, two bits per pixel with pixel 0 in the lowest bits
+const PARTITIONS_3: [u32; 64] = [
+    0xaa685050, 0x6a5a5040, 0x5a5a4200, 0x5450a0a8, 0xa5a50000, 0xa0a05050, 0x5555a0a0, 0x5a5a5050,
+    0xaa550000, 0xaa555500, 0xaaaa5500, 0x90909090, 0x94949494, 0xa4a4a4a4, 0xa9a59450, 0x2a0a4250,
+    0xa5945040, 0x0a425054, 0xa5a5a500, 0x55a0a0a0, 0xa8a85454, 0x6a6a4040, 0xa4a45000, 0x1a1a0500,
+    0x0050a4a4, 0xaaa59090, 0x14696914, 0x69691400, 0xa08585a0, 0xaa821414, 0x50a4a450, 0x6a5a0200,
+    0xa9a58000, 0x5090a0a8, 0xa8a09050, 0x24242424, 0x00aa5500, 0x24924924, 0x24499224, 0x50a50a50,
+    0x500aa550, 0xaaaa4444, 0x66660000, 0xa5a0a5a0, 0x50a050a0, 0x69286928, 0x44aaaa44, 0x66666600,
+    0xaa444444, 0x54a854a8, 0x95809580, 0x96969600, 0xa85454a8, 0x80959580, 0xaa141414, 0x96960000,
+    0xaaaa1414, 0xa05050a0, 0xa0a5a5a0, 0x96000000, 0x40804080, 0xa9a8a9a8, 0xaaaaaa44, 0x2a4a5254,
+];
+
+// index of the second subset's anchor pixel for two subsets
+const ANCHORS_2: [u8; 64] = [
+    15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15,
+    15, 2, 8, 2, 2, 8, 8, 15, 2, 8, 2, 2, 8, 8, 2, 2,
+    15, 15, 6, 8, 2, 8, 15, 15, 2, 8, 2, 2, 2, 15, 15, 6,
+    6, 2, 6, 8, 15, 15, 2, 2, 15, 15, 15, 15, 15, 2, 2, 15,
+];
+
+// anchor pixels of the second and third subsets for three subsets
+const ANCHORS_3A: [u8; 64] = [
+    3, 3, 15, 15, 8, 3, 15, 15, 8, 8, 6, 6, 6, 5, 3, 3,
+    3, 3, 8, 15, 3, 3, 6, 10, 5, 8, 8, 6, 8, 5, 15, 15,
+    8, 15, 3, 5, 6, 10, 8, 15, 15, 3, 15, 5, 15, 15, 15, 15,
+    3, 15, 5, 5, 5, 8, 5, 10, 5, 10, 8, 13, 15, 12, 3, 3,
+];
+
+const ANCHORS_3B: [u8; 64] = [
+    15, 8, 8, 3, 15, 15, 3, 8, 15, 15, 15, 15, 15, 15, 15, 8,
+    15, 8, 15, 3, 15, 8, 15, 8, 3, 15, 6, 10, 15, 15, 10, 8,
+    15, 3, 15, 10, 10, 8, 9, 10, 6, 15, 8, 15, 3, 6, 6, 8,
+    15, 3, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 3, 15, 15, 8,
+];
+
+const WEIGHTS_2: [u32; 4] = [0, 21, 43, 64];
+const WEIGHTS_3: [u32; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+const WEIGHTS_4: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+struct Bc7Mode
+{
+    subsets: usize,
+    partition_bits: u32,
+    rotation_bits: u32,
+    index_selection_bits: u32,
+    color_bits: u32,
+    alpha_bits: u32,
+    endpoint_pbits: bool,
+    shared_pbits: bool,
+    index_bits: u32,
+    secondary_index_bits: u32,
+}
+
+const BC7_MODES: [Bc7Mode; 8] = [
+    // mode 0
+    Bc7Mode { subsets: 3, partition_bits: 4, rotation_bits: 0, index_selection_bits: 0, color_bits: 4, alpha_bits: 0,
+        endpoint_pbits: true, shared_pbits: false, index_bits: 3, secondary_index_bits: 0 },
+    // mode 1
+    Bc7Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 6, alpha_bits: 0,
+        endpoint_pbits: false, shared_pbits: true, index_bits: 3, secondary_index_bits: 0 },
+    // mode 2
+    Bc7Mode { subsets: 3, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 5, alpha_bits: 0,
+        endpoint_pbits: false, shared_pbits: false, index_bits: 2, secondary_index_bits: 0 },
+    // mode 3
+    Bc7Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 7, alpha_bits: 0,
+        endpoint_pbits: true, shared_pbits: false, index_bits: 2, secondary_index_bits: 0 },
+    // mode 4
+    Bc7Mode { subsets: 1, partition_bits: 0, rotation_bits: 2, index_selection_bits: 1, color_bits: 5, alpha_bits: 6,
+        endpoint_pbits: false, shared_pbits: false, index_bits: 2, secondary_index_bits: 3 },
+    // mode 5
+    Bc7Mode { subsets: 1, partition_bits: 0, rotation_bits: 2, index_selection_bits: 0, color_bits: 7, alpha_bits: 8,
+        endpoint_pbits: false, shared_pbits: false, index_bits: 2, secondary_index_bits: 2 },
+    // mode 6
+    Bc7Mode { subsets: 1, partition_bits: 0, rotation_bits: 0, index_selection_bits: 0, color_bits: 7, alpha_bits: 7,
+        endpoint_pbits: true, shared_pbits: false, index_bits: 4, secondary_index_bits: 0 },
+    // mode 7
+    Bc7Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 5, alpha_bits: 5,
+        endpoint_pbits: true, shared_pbits: false, index_bits: 2, secondary_index_bits: 0 },
+];
+
+// reads the block LSB first
+struct BitReader
+{
+    bits: u128,
+    position: u32,
+}
+
+impl BitReader
+{
+    fn read(&mut self, count: u32) -> u32
+    {
+        if count == 0 {
+            return 0;
+        }
+        let value = (self.bits >> self.position) as u32 & ((1u32 << count) - 1);
+        self.position += count;
+        value
+    }
+}
+
+fn weights(bits: u32) -> &'static [u32]
+{
+    match bits {
+        2 => &WEIGHTS_2,
+        3 => &WEIGHTS_3,
+        _ => &WEIGHTS_4,
+    }
+}
+
+fn interpolate(e0: u32, e1: u32, weight: u32) -> u8
+{
+    (((64 - weight) * e0 + weight * e1 + 32) >> 6) as u8
+}
+
+fn decode_bc7(block: &[u8], out: &mut [[u8; 4]; 16])
+{
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&block[..16]);
+    let mut reader = BitReader { bits: u128::from_le_bytes(bytes), position: 0 };
+
+    // the mode is the number of zero bits before the first set bit
+    let mode_index = match (0..8).find(|&m| reader.read(1) == 1) {
+        Some(m) => m,
+        None => {
+            // reserved mode, decodes to transparent black
+            *out = [[0; 4]; 16];
+            return;
+        }
+    };
+    let mode = &BC7_MODES[mode_index];
+
+    let partition = reader.read(mode.partition_bits) as usize;
+    let rotation = reader.read(mode.rotation_bits);
+    let index_selection = reader.read(mode.index_selection_bits);
+
+    // endpoints[subset * 2 + end][channel]
+    let mut endpoints = [[0u32; 4]; 6];
+    let endpoint_count = mode.subsets * 2;
+    for channel in 0..3 {
+        for endpoint in endpoints.iter_mut().take(endpoint_count) {
+            endpoint[channel] = reader.read(mode.color_bits);
+        }
+    }
+    for endpoint in endpoints.iter_mut().take(endpoint_count) {
+        endpoint[3] = if mode.alpha_bits > 0 { reader.read(mode.alpha_bits) } else { 255 };
+    }
+
+    // p-bits add one low bit to every channel of an endpoint
+    let mut pbits = [0u32; 6];
+    if mode.endpoint_pbits {
+        for pbit in pbits.iter_mut().take(endpoint_count) {
+            *pbit = reader.read(1);
+        }
+    } else if mode.shared_pbits {
+        for subset in 0..mode.subsets {
+            let pbit = reader.read(1);
+            pbits[subset * 2] = pbit;
+            pbits[subset * 2 + 1] = pbit;
+        }
+    }
+
+    let has_pbits = mode.endpoint_pbits || mode.shared_pbits;
+    for (endpoint, pbit) in endpoints.iter_mut().zip(pbits.iter()).take(endpoint_count) {
+        for (channel, value) in endpoint.iter_mut().enumerate() {
+            let mut bits = if channel < 3 { mode.color_bits } else { mode.alpha_bits };
+            if bits == 0 {
+                continue;
+            }
+            if has_pbits {
+                *value = (*value << 1) | pbit;
+                bits += 1;
+            }
+            // replicate the high bits into the low bits to expand to 8 bits
+            *value <<= 8 - bits;
+            *value |= *value >> bits;
+        }
+    }
+
+    let subset_of = |pixel: usize| -> usize {
+        match mode.subsets {
+            2 => ((PARTITIONS_2[partition] >> pixel) & 1) as usize,
+            3 => ((PARTITIONS_3[partition] >> (2 * pixel)) & 3) as usize,
+            _ => 0,
+        }
+    };
+    let is_anchor = |pixel: usize| -> bool {
+        pixel == 0 || match mode.subsets {
+            2 => pixel == ANCHORS_2[partition] as usize,
+            3 => pixel == ANCHORS_3A[partition] as usize || pixel == ANCHORS_3B[partition] as usize,
+            _ => false,
+        }
+    };
+
+    // anchor pixels store one bit less, their high bit is implicitly 0
+    let mut indices = [0u32; 16];
+    for (pixel, index) in indices.iter_mut().enumerate() {
+        *index = reader.read(if is_anchor(pixel) { mode.index_bits - 1 } else { mode.index_bits });
+    }
+    let mut secondary = [0u32; 16];
+    if mode.secondary_index_bits > 0 {
+        for (pixel, index) in secondary.iter_mut().enumerate() {
+            *index = reader.read(if pixel == 0 { mode.secondary_index_bits - 1 } else { mode.secondary_index_bits });
+        }
+    }
+
+    for (pixel, texel) in out.iter_mut().enumerate() {
+        let subset = subset_of(pixel);
+        let (e0, e1) = (endpoints[subset * 2], endpoints[subset * 2 + 1]);
+
+        // with two index sets the index selection bit picks which one is used for color and which for alpha
+        let (color_weight, alpha_weight) = if mode.secondary_index_bits == 0 {
+            let w = weights(mode.index_bits)[indices[pixel] as usize];
+            (w, w)
+        } else if index_selection == 0 {
+            (weights(mode.index_bits)[indices[pixel] as usize], weights(mode.secondary_index_bits)[secondary[pixel] as usize])
+        } else {
+            (weights(mode.secondary_index_bits)[secondary[pixel] as usize], weights(mode.index_bits)[indices[pixel] as usize])
+        };
+
+        let mut color = [
+            interpolate(e0[0], e1[0], color_weight),
+            interpolate(e0[1], e1[1], color_weight),
+            interpolate(e0[2], e1[2], color_weight),
+            interpolate(e0[3], e1[3], alpha_weight),
+        ];
+
+        match rotation {
+            1 => color.swap(0, 3),
+            2 => color.swap(1, 3),
+            3 => color.swap(2, 3),
+            _ => {}
+        }
+
+        *texel = color;
+    }
+}
+
+// BC6H endpoint fields, endpoint * 3 + channel for the endpoints w, x, y and z, then the partition
+const RW: usize = 0;
+const GW: usize = 1;
+const BW: usize = 2;
+const RX: usize = 3;
+const GX: usize = 4;
+const BX: usize = 5;
+const RY: usize = 6;
+const GY: usize = 7;
+const BY: usize = 8;
+const RZ: usize = 9;
+const GZ: usize = 10;
+const BZ: usize = 11;
+const D: usize = 12;
+
+struct Bc6hMode
+{
+    // the mode bits, 2 bits for the first two modes and 5 bits for the others
+    value: u32,
+    regions: usize,
+    // x, y and z are stored as deltas from w
+    transformed: bool,
+    endpoint_bits: u32,
+    delta_bits: [u32; 3],
+    // (field, a, b) reads the bits b to a of a field, in that order. a < b stores them reversed
+    layout: &'static [(usize, u32, u32)],
+}
+
+// bit layouts after the mode bits, as in the BC6H format description
+const BC6H_MODES: [Bc6hMode; 14] = [
+    Bc6hMode { value: 0b00, regions: 2, transformed: true, endpoint_bits: 10, delta_bits: [5, 5, 5], layout: &[
+        (GY, 4, 4), (BY, 4, 4), (BZ, 4, 4), (RW, 9, 0), (GW, 9, 0), (BW, 9, 0), (RX, 4, 0), (GZ, 4, 4),
+        (GY, 3, 0), (GX, 4, 0), (BZ, 0, 0), (GZ, 3, 0), (BX, 4, 0), (BZ, 1, 1), (BY, 3, 0), (RY, 4, 0),
+        (BZ, 2, 2), (RZ, 4, 0), (BZ, 3, 3), (D, 4, 0)] },
+    Bc6hMode { value: 0b01, regions: 2, transformed: true, endpoint_bits: 7, delta_bits: [6, 6, 6], layout: &[
+        (GY, 5, 5), (GZ, 4, 4), (GZ, 5, 5), (RW, 6, 0), (BZ, 0, 0), (BZ, 1, 1), (BY, 4, 4), (GW, 6, 0),
+        (BY, 5, 5), (BZ, 2, 2), (GY, 4, 4), (BW, 6, 0), (BZ, 3, 3), (BZ, 5, 5), (BZ, 4, 4), (RX, 5, 0),
+        (GY, 3, 0), (GX, 5, 0), (GZ, 3, 0), (BX, 5, 0), (BY, 3, 0), (RY, 5, 0), (RZ, 5, 0), (D, 4, 0)] },
+    Bc6hMode { value: 0b00010, regions: 2, transformed: true, endpoint_bits: 11, delta_bits: [5, 4, 4], layout: &[
+        (RW, 9, 0), (GW, 9, 0), (BW, 9, 0), (RX, 4, 0), (RW, 10, 10), (GY, 3, 0), (GX, 3, 0), (GW, 10, 10),
+        (BZ, 0, 0), (GZ, 3, 0), (BX, 3, 0), (BW, 10, 10), (BZ, 1, 1), (BY, 3, 0), (RY, 4, 0), (BZ, 2, 2),
+        (RZ, 4, 0), (BZ, 3, 3), (D, 4, 0)] },
+    Bc6hMode { value: 0b00110, regions: 2, transformed: true, endpoint_bits: 11, delta_bits: [4, 5, 4], layout: &[
+        (RW, 9, 0), (GW, 9, 0), (BW, 9, 0), (RX, 3, 0), (RW, 10, 10), (GZ, 4, 4), (GY, 3, 0), (GX, 4, 0),
+        (GW, 10, 10), (GZ, 3, 0), (BX, 3, 0), (BW, 10, 10), (BZ, 1, 1), (BY, 3, 0), (RY, 3, 0), (BZ, 0, 0),
+        (BZ, 2, 2), (RZ, 3, 0), (GY, 4, 4), (BZ, 3, 3), (D, 4, 0)] },
+    Bc6hMode { value: 0b01010, regions: 2, transformed: true, endpoint_bits: 11, delta_bits: [4, 4, 5], layout: &[
+        (RW, 9, 0), (GW, 9, 0), (BW, 9, 0), (RX, 3, 0), (RW, 10, 10), (BY, 4, 4), (GY, 3, 0), (GX, 3, 0),
+        (GW, 10, 10), (BZ, 0, 0), (GZ, 3, 0), (BX, 4, 0), (BW, 10, 10), (BY, 3, 0), (RY, 3, 0), (BZ, 1, 1),
+        (BZ, 2, 2), (RZ, 3, 0), (BZ, 4, 4), (BZ, 3, 3), (D, 4, 0)] },
+    Bc6hMode { value: 0b01110, regions: 2, transformed: true, endpoint_bits: 9, delta_bits: [5, 5, 5], layout: &[
+        (RW, 8, 0), (BY, 4, 4), (GW, 8, 0), (GY, 4, 4), (BW, 8, 0), (BZ, 4, 4), (RX, 4, 0), (GZ, 4, 4),
+        (GY, 3, 0), (GX, 4, 0), (BZ, 0, 0), (GZ, 3, 0), (BX, 4, 0), (BZ, 1, 1), (BY, 3, 0), (RY, 4, 0),
+        (BZ, 2, 2), (RZ, 4, 0), (BZ, 3, 3), (D, 4, 0)] },
+    Bc6hMode { value: 0b10010, regions: 2, transformed: true, endpoint_bits: 8, delta_bits: [6, 5, 5], layout: &[
+        (RW, 7, 0), (GZ, 4, 4), (BY, 4, 4), (GW, 7, 0), (BZ, 2, 2), (GY, 4, 4), (BW, 7, 0), (BZ, 3, 3),
+        (BZ, 4, 4), (RX, 5, 0), (GY, 3, 0), (GX, 4, 0), (BZ, 0, 0), (GZ, 3, 0), (BX, 4, 0), (BZ, 1, 1),
+        (BY, 3, 0), (RY, 5, 0), (RZ, 5, 0), (D, 4, 0)] },
+    Bc6hMode { value: 0b10110, regions: 2, transformed: true, endpoint_bits: 8, delta_bits: [5, 6, 5], layout: &[
+        (RW, 7, 0), (BZ, 0, 0), (BY, 4, 4), (GW, 7, 0), (GY, 5, 5), (GY, 4, 4), (BW, 7, 0), (GZ, 5, 5),
+        (BZ, 4, 4), (RX, 4, 0), (GZ, 4, 4), (GY, 3, 0), (GX, 5, 0), (GZ, 3, 0), (BX, 4, 0), (BZ, 1, 1),
+        (BY, 3, 0), (RY, 4, 0), (BZ, 2, 2), (RZ, 4, 0), (BZ, 3, 3), (D, 4, 0)] },
+    Bc6hMode { value: 0b11010, regions: 2, transformed: true, endpoint_bits: 8, delta_bits: [5, 5, 6], layout: &[
+        (RW, 7, 0), (BZ, 1, 1), (BY, 4, 4), (GW, 7, 0), (BY, 5, 5), (GY, 4, 4), (BW, 7, 0), (BZ, 5, 5),
+        (BZ, 4, 4), (RX, 4, 0), (GZ, 4, 4), (GY, 3, 0), (GX, 4, 0), (BZ, 0, 0), (GZ, 3, 0), (BX, 5, 0),
+        (BY, 3, 0), (RY, 4, 0), (BZ, 2, 2), (RZ, 4, 0), (BZ, 3, 3), (D, 4, 0)] },
+    Bc6hMode { value: 0b11110, regions: 2, transformed: false, endpoint_bits: 6, delta_bits: [6, 6, 6], layout: &[
+        (RW, 5, 0), (GZ, 4, 4), (BZ, 0, 0), (BZ, 1, 1), (BY, 4, 4), (GW, 5, 0), (GY, 5, 5), (BY, 5, 5),
+        (BZ, 2, 2), (GY, 4, 4), (BW, 5, 0), (GZ, 5, 5), (BZ, 3, 3), (BZ, 5, 5), (BZ, 4, 4), (RX, 5, 0),
+        (GY, 3, 0), (GX, 5, 0), (GZ, 3, 0), (BX, 5, 0), (BY, 3, 0), (RY, 5, 0), (RZ, 5, 0), (D, 4, 0)] },
+    Bc6hMode { value: 0b00011, regions: 1, transformed: false, endpoint_bits: 10, delta_bits: [10, 10, 10], layout: &[
+        (RW, 9, 0), (GW, 9, 0), (BW, 9, 0), (RX, 9, 0), (GX, 9, 0), (BX, 9, 0)] },
+    Bc6hMode { value: 0b00111, regions: 1, transformed: true, endpoint_bits: 11, delta_bits: [9, 9, 9], layout: &[
+        (RW, 9, 0), (GW, 9, 0), (BW, 9, 0), (RX, 8, 0), (RW, 10, 10), (GX, 8, 0), (GW, 10, 10), (BX, 8, 0),
+        (BW, 10, 10)] },
+    Bc6hMode { value: 0b01011, regions: 1, transformed: true, endpoint_bits: 12, delta_bits: [8, 8, 8], layout: &[
+        (RW, 9, 0), (GW, 9, 0), (BW, 9, 0), (RX, 7, 0), (RW, 10, 11), (GX, 7, 0), (GW, 10, 11), (BX, 7, 0),
+        (BW, 10, 11)] },
+    Bc6hMode { value: 0b01111, regions: 1, transformed: true, endpoint_bits: 16, delta_bits: [4, 4, 4], layout: &[
+        (RW, 9, 0), (GW, 9, 0), (BW, 9, 0), (RX, 3, 0), (RW, 10, 15), (GX, 3, 0), (GW, 10, 15), (BX, 3, 0),
+        (BW, 10, 15)] },
+];
+
+fn sign_extend(value: i32, bits: u32) -> i32
+{
+    let shift = 32 - bits;
+    (value << shift) >> shift
+}
+
+// expands an endpoint to the 16 bit range the interpolation works in
+fn unquantize_bc6h(value: i32, bits: u32, signed: bool) -> i32
+{
+    if !signed {
+        if bits >= 15 || value == 0 {
+            value
+        } else if value == (1 << bits) - 1 {
+            0xffff
+        } else {
+            ((value << 16) + 0x8000) >> bits
+        }
+    } else {
+        let magnitude = value.abs();
+        let unquantized = if bits >= 16 || magnitude == 0 {
+            magnitude
+        } else if magnitude >= (1 << (bits - 1)) - 1 {
+            0x7fff
+        } else {
+            ((magnitude << 15) + 0x4000) >> (bits - 1)
+        };
+        if value < 0 { -unquantized } else { unquantized }
+    }
+}
+
+// scales an interpolated value to the half float bit pattern it stands for
+fn finish_bc6h(value: i32, signed: bool) -> f32
+{
+    let bits = if !signed {
+        ((value * 31) >> 6) as u16
+    } else if value < 0 {
+        0x8000 | ((-value * 31) >> 5) as u16
+    } else {
+        ((value * 31) >> 5) as u16
+    };
+    half_to_f32(bits)
+}
+
+fn half_to_f32(bits: u16) -> f32
+{
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((bits >> 10) & 0x1f) as i32;
+    let mantissa = (bits & 0x3ff) as f32 / 1024.0;
+    // BC6H never produces infinities or NaNs, 0x7bff is the largest value it can encode
+    if exponent == 0 {
+        sign * mantissa * 2f32.powi(-14)
+    } else {
+        sign * (1.0 + mantissa) * 2f32.powi(exponent - 15)
+    }
+}
+
+fn decode_bc6h(block: &[u8], out: &mut [[f32; 4]; 16], signed: bool)
+{
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&block[..16]);
+    let mut reader = BitReader { bits: u128::from_le_bytes(bytes), position: 0 };
+
+    let mut value = reader.read(2);
+    if value >= 2 {
+        value |= reader.read(3) << 2;
+    }
+    let mode = match BC6H_MODES.iter().find(|mode| mode.value == value) {
+        Some(mode) => mode,
+        None => {
+            // reserved modes decode to black
+            *out = [[0.0, 0.0, 0.0, 1.0]; 16];
+            return;
+        }
+    };
+
+    let mut fields = [0i32; 13];
+    for &(field, a, b) in mode.layout {
+        if a >= b {
+            for bit in b..=a {
+                fields[field] |= (reader.read(1) << bit) as i32;
+            }
+        } else {
+            for bit in (a..=b).rev() {
+                fields[field] |= (reader.read(1) << bit) as i32;
+            }
+        }
+    }
+
+    // endpoints[region * 2 + end][channel]
+    let mut endpoints = [[0i32; 3]; 4];
+    let endpoint_count = mode.regions * 2;
+    let mask = (1i32 << mode.endpoint_bits) - 1;
+    for channel in 0..3 {
+        let base = if signed { sign_extend(fields[channel], mode.endpoint_bits) } else { fields[channel] };
+        endpoints[0][channel] = base;
+
+        for (index, endpoint) in endpoints.iter_mut().enumerate().take(endpoint_count).skip(1) {
+            let mut value = fields[index * 3 + channel];
+            if signed || mode.transformed {
+                value = sign_extend(value, mode.delta_bits[channel]);
+            }
+            if mode.transformed {
+                value = (base + value) & mask;
+                if signed {
+                    value = sign_extend(value, mode.endpoint_bits);
+                }
+            }
+            endpoint[channel] = value;
+        }
+    }
+    for endpoint in endpoints.iter_mut().take(endpoint_count) {
+        for value in endpoint.iter_mut() {
+            *value = unquantize_bc6h(*value, mode.endpoint_bits, signed);
+        }
+    }
+
+    let partition = fields[D] as usize;
+    let index_bits = if mode.regions == 2 { 3 } else { 4 };
+    let is_anchor = |pixel: usize| pixel == 0 || (mode.regions == 2 && pixel == ANCHORS_2[partition] as usize);
+
+    for (pixel, texel) in out.iter_mut().enumerate() {
+        let index = reader.read(if is_anchor(pixel) { index_bits - 1 } else { index_bits });
+        let weight = weights(index_bits)[index as usize] as i32;
+        let region = if mode.regions == 2 { ((PARTITIONS_2[partition] >> pixel) & 1) as usize } else { 0 };
+        let (e0, e1) = (endpoints[region * 2], endpoints[region * 2 + 1]);
+
+        for channel in 0..3 {
+            texel[channel] = finish_bc6h(((64 - weight) * e0[channel] + weight * e1[channel] + 32) >> 6, signed);
+        }
+        texel[3] = 1.0;
+    }
+}
+
+fn clamp_to_rgba8(block: &[u8], out: &mut [[u8; 4]; 16], signed: bool)
+{
+    let mut texels = [[0.0f32; 4]; 16];
+    decode_bc6h(block, &mut texels, signed);
+    for (texel, value) in out.iter_mut().zip(texels.iter()) {
+        for channel in 0..4 {
+            texel[channel] = (value[channel].clamp(0.0, 1.0) * 255.0 + 0.5) as u8;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    // red and blue endpoints with c0 < c1, which selects BC1's three color mode when alpha is allowed
+    fn bc1_block(index: u32) -> Vec<u8>
+    {
+        let indices = (0..16).fold(0u32, |bits, i| bits | index << (2 * i));
+        let mut block = Vec::new();
+        block.extend_from_slice(&0x001fu16.to_le_bytes());
+        block.extend_from_slice(&0xf800u16.to_le_bytes());
+        block.extend_from_slice(&indices.to_le_bytes());
+        block
+    }
+
+    fn texels(pixels: &[u8]) -> Vec<[u8; 4]>
+    {
+        pixels.chunks_exact(4).map(|p| [p[0], p[1], p[2], p[3]]).collect()
+    }
+
+    #[test]
+    fn bc1_punch_through_alpha_follows_the_format()
+    {
+        let block = bc1_block(3);
+        let transparent = decode_image(BlockFormat::Bc1 { alpha: true }, 4, 4, &block).unwrap();
+        assert!(texels(&transparent).iter().all(|&t| t == [0, 0, 0, 0]));
+
+        // without alpha the block always uses four colors, index 3 is two thirds of the way to red
+        let opaque = decode_image(BlockFormat::Bc1 { alpha: false }, 4, 4, &block).unwrap();
+        assert!(texels(&opaque).iter().all(|&t| t == [170, 0, 85, 255]));
+
+        // index 2 is the midpoint in three color mode
+        let midpoint = decode_image(BlockFormat::Bc1 { alpha: true }, 4, 4, &bc1_block(2)).unwrap();
+        assert!(texels(&midpoint).iter().all(|&t| t == [127, 0, 127, 255]));
+    }
+
+    #[test]
+    fn bc3_interpolates_alpha()
+    {
+        // eight alpha values from 255 down to 3, pixel i uses index i % 8
+        let indices = (0..16u64).fold(0u64, |bits, i| bits | (i % 8) << (3 * i));
+        let mut block = vec![255, 3];
+        block.extend_from_slice(&indices.to_le_bytes()[..6]);
+        block.extend_from_slice(&bc1_block(0));
+
+        let alphas: Vec<u8> = texels(&decode_image(BlockFormat::Bc3, 4, 4, &block).unwrap()).iter().map(|t| t[3]).collect();
+        assert_eq!(&alphas[..8], &[255, 3, 219, 183, 147, 111, 75, 39]);
+        assert_eq!(alphas[..8], alphas[8..]);
+    }
+
+    #[test]
+    fn partial_blocks_are_cropped()
+    {
+        // 5x3 needs two blocks side by side, the second one only contributes its first column
+        let mut data = bc1_block(0);
+        data.extend(bc1_block(1));
+        let pixels = texels(&decode_image(BlockFormat::Bc1 { alpha: false }, 5, 3, &data).unwrap());
+
+        assert_eq!(pixels.len(), 15);
+        for row in pixels.chunks_exact(5) {
+            assert!(row[..4].iter().all(|&t| t == [0, 0, 255, 255]));
+            assert_eq!(row[4], [255, 0, 0, 255]);
+        }
+    }
+
+    #[test]
+    fn bc7_mode_6_block()
+    {
+        // mode 6 with endpoints (0, 64, 128, 254) and (254, 192, 128, 0). The anchor pixel 0 stores 3 bits and
+        // can't go past index 7, every other pixel uses index 15, the second endpoint
+        let mut bits = 1u128 << 6;
+        let mut position = 7;
+        for value in [0u128, 127, 32, 96, 64, 64, 127, 0].iter() {
+            bits |= value << position;
+            position += 7;
+        }
+        position += 2;
+        bits |= 0b111 << position;
+        position += 3;
+        for _ in 1..16 {
+            bits |= 0b1111 << position;
+            position += 4;
+        }
+        assert_eq!(position, 128);
+
+        let pixels = texels(&decode_image(BlockFormat::Bc7, 4, 4, &bits.to_le_bytes()).unwrap());
+        assert_eq!(pixels[0], [119, 124, 128, 135]);
+        assert!(pixels[1..].iter().all(|&t| t == [254, 192, 128, 0]));
+    }
+
+    #[test]
+    fn signed_bc4_is_remapped_to_unsigned()
+    {
+        // eight values from 127 down to -127, pixel i uses index i % 8
+        let indices = (0..16u64).fold(0u64, |bits, i| bits | (i % 8) << (3 * i));
+        let mut block = vec![127, (-127i8) as u8];
+        block.extend_from_slice(&indices.to_le_bytes()[..6]);
+
+        let reds: Vec<u8> = texels(&decode_image(BlockFormat::Bc4 { signed: true }, 4, 4, &block).unwrap()).iter().map(|t| t[0]).collect();
+        assert_eq!(&reds[..8], &[255, 0, 218, 182, 146, 109, 73, 37]);
+
+        // -128 is read as -127, the six value mode ends with -1 and 1
+        block[0] = (-128i8) as u8;
+        block[1] = 64;
+        let reds: Vec<u8> = texels(&decode_image(BlockFormat::Bc4 { signed: true }, 4, 4, &block).unwrap()).iter().map(|t| t[0]).collect();
+        assert_eq!(&reds[..8], &[0, 192, 39, 77, 115, 153, 0, 255]);
+    }
+
+    #[test]
+    fn signed_bc5_decodes_both_channels()
+    {
+        // red uses index 0 (-127) and green index 1 (127) everywhere
+        let mut block = vec![(-127i8) as u8, 0, 0, 0, 0, 0, 0, 0];
+        block.extend_from_slice(&[0, 127]);
+        block.extend_from_slice(&0x249249_249249u64.to_le_bytes()[..6]);
+
+        let pixels = texels(&decode_image(BlockFormat::Bc5 { signed: true }, 4, 4, &block).unwrap());
+        assert!(pixels.iter().all(|&t| t == [0, 255, 0, 255]));
+    }
+
+    // appends `count` bits of `value`, LSB first
+    fn push_bits(bits: &mut u128, position: &mut u32, value: u32, count: u32)
+    {
+        *bits |= ((value as u128) & ((1 << count) - 1)) << *position;
+        *position += count;
+    }
+
+    // one region block indices, the anchor pixel 0 uses index 0 and every other pixel the second endpoint
+    fn push_bc6h_indices(bits: &mut u128, position: &mut u32)
+    {
+        push_bits(bits, position, 0, 3);
+        for _ in 1..16 {
+            push_bits(bits, position, 15, 4);
+        }
+        assert_eq!(*position, 128);
+    }
+
+    // mode 11 stores both endpoints as plain 10 bit values
+    fn bc6h_mode_11(w: [u32; 3], x: [u32; 3]) -> [u8; 16]
+    {
+        let (mut bits, mut position) = (0u128, 0);
+        push_bits(&mut bits, &mut position, 0b00011, 5);
+        for value in w.iter().chain(x.iter()) {
+            push_bits(&mut bits, &mut position, *value, 10);
+        }
+        push_bc6h_indices(&mut bits, &mut position);
+        bits.to_le_bytes()
+    }
+
+    fn floats(pixels: &[f32]) -> Vec<[f32; 4]>
+    {
+        pixels.chunks_exact(4).map(|p| [p[0], p[1], p[2], p[3]]).collect()
+    }
+
+    #[test]
+    fn bc6h_layouts_cover_every_bit_once()
+    {
+        for mode in BC6H_MODES.iter() {
+            let mut seen = std::collections::HashSet::new();
+            for &(field, a, b) in mode.layout {
+                for bit in a.min(b)..=a.max(b) {
+                    assert!(seen.insert((field, bit)), "mode {:#b} reads bit {} of field {} twice", mode.value, bit, field);
+                }
+            }
+
+            let mut expected = std::collections::HashSet::new();
+            for channel in 0..3 {
+                expected.extend((0..mode.endpoint_bits).map(|bit| (channel, bit)));
+                for endpoint in 1..mode.regions * 2 {
+                    expected.extend((0..mode.delta_bits[channel]).map(|bit| (endpoint * 3 + channel, bit)));
+                }
+            }
+            if mode.regions == 2 {
+                expected.extend((0..5).map(|bit| (D, bit)));
+            }
+            assert_eq!(seen, expected, "mode {:#b}", mode.value);
+
+            // the indices fill the rest of the 128 bits
+            let mode_bits = if mode.value < 2 { 2 } else { 5 };
+            let header = if mode.regions == 2 { 82 } else { 65 };
+            assert_eq!(mode_bits + seen.len(), header, "mode {:#b}", mode.value);
+        }
+    }
+
+    #[test]
+    fn bc6h_unsigned_endpoints()
+    {
+        // 495 unquantizes to the half float 1.0
+        let block = bc6h_mode_11([0, 0, 0], [495, 495, 0]);
+        let pixels = floats(&decode_image_f32(BlockFormat::Bc6h { signed: false }, 4, 4, &block).unwrap());
+        assert_eq!(pixels[0], [0.0, 0.0, 0.0, 1.0]);
+        assert!(pixels[1..].iter().all(|&t| t == [1.0, 1.0, 0.0, 1.0]));
+
+        let clamped = texels(&decode_image(BlockFormat::Bc6h { signed: false }, 4, 4, &block).unwrap());
+        assert_eq!(clamped[0], [0, 0, 0, 255]);
+        assert!(clamped[1..].iter().all(|&t| t == [255, 255, 0, 255]));
+    }
+
+    #[test]
+    fn bc6h_signed_endpoints()
+    {
+        let block = bc6h_mode_11([(-247i32) as u32 & 0x3ff, 0, 0], [247, 0, 0]);
+        let pixels = floats(&decode_image_f32(BlockFormat::Bc6h { signed: true }, 4, 4, &block).unwrap());
+        // the half float just below 1
+        let value = half_to_f32(0x3bf1);
+        assert_eq!(pixels[0], [-value, 0.0, 0.0, 1.0]);
+        assert!(pixels[1..].iter().all(|&t| t == [value, 0.0, 0.0, 1.0]));
+
+        // negative values clamp to black
+        let clamped = texels(&decode_image(BlockFormat::Bc6h { signed: true }, 4, 4, &block).unwrap());
+        assert_eq!(clamped[0], [0, 0, 0, 255]);
+        assert_eq!(clamped[1], [253, 0, 0, 255]);
+    }
+
+    #[test]
+    fn half_floats()
+    {
+        assert_eq!(half_to_f32(0x3c00), 1.0);
+        assert_eq!(half_to_f32(0xc000), -2.0);
+        assert_eq!(half_to_f32(0x3bf1), 1.0 - 15.0 / 2048.0);
+        assert_eq!(half_to_f32(0x7bff), 65504.0);
+        assert_eq!(half_to_f32(0x0001), 2f32.powi(-24));
+    }
+
+    #[test]
+    fn bc6h_mode_14_deltas_and_reversed_bits()
+    {
+        // a 16 bit red endpoint of 29597 (the half float 0.5) whose bits 10-15 are stored reversed, and a red
+        // delta of 4 for the second endpoint
+        let (mut bits, mut position) = (0u128, 0);
+        push_bits(&mut bits, &mut position, 0b01111, 5);
+        push_bits(&mut bits, &mut position, 29597 & 0x3ff, 10);
+        push_bits(&mut bits, &mut position, 0, 20);
+        push_bits(&mut bits, &mut position, 4, 4);
+        for bit in (10..16).rev() {
+            push_bits(&mut bits, &mut position, 29597 >> bit, 1);
+        }
+        push_bits(&mut bits, &mut position, 0, 20);
+        push_bc6h_indices(&mut bits, &mut position);
+
+        let pixels = floats(&decode_image_f32(BlockFormat::Bc6h { signed: false }, 4, 4, &bits.to_le_bytes()).unwrap());
+        assert_eq!(pixels[0], [0.5, 0.0, 0.0, 1.0]);
+        assert!(pixels[1..].iter().all(|&t| t == [half_to_f32(0x3801), 0.0, 0.0, 1.0]));
+    }
+
+    #[test]
+    fn bc6h_reserved_modes_are_black()
+    {
+        let mut block = [0xffu8; 16];
+        block[0] = 0b10011;
+        let pixels = floats(&decode_image_f32(BlockFormat::Bc6h { signed: false }, 4, 4, &block).unwrap());
+        assert!(pixels.iter().all(|&t| t == [0.0, 0.0, 0.0, 1.0]));
+    }
+
+    #[test]
+    fn short_data_and_non_float_formats_fail()
+    {
+        assert!(decode_image(BlockFormat::Bc1 { alpha: false }, 8, 4, &bc1_block(0)).is_err());
+        assert!(decode_image(BlockFormat::Bc6h { signed: false }, 8, 4, &[0; 16]).is_err());
+        assert!(decode_image_f32(BlockFormat::Bc7, 4, 4, &[0; 16]).is_err());
+    }
+}
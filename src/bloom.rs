@@ -0,0 +1,165 @@
+use nalgebra_glm::Vec2;
+
+use std::path::Path;
+
+use super::assets::{ AssetManager, Handle };
+use super::framebuffer::{ Attachment, Framebuffer, FramebufferDesc };
+use super::shader::Shader;
+use super::utils;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BloomOptions
+{
+    // brightness (largest channel) from which pixels bloom
+    pub threshold: f32,
+    // width of the soft transition around the threshold, in the same units
+    pub knee: f32,
+    // how much of the blurred highlights is added to the scene
+    pub intensity: f32,
+    // spread of the upsampling filter in pixels, larger gives a wider, smoother glow
+    pub radius: f32,
+    // number of half size steps, each widens the glow
+    pub levels: u32,
+}
+
+impl Default for BloomOptions
+{
+    fn default() -> BloomOptions
+    {
+        BloomOptions { threshold: 1.0, knee: 0.5, intensity: 0.8, radius: 1.0, levels: 6 }
+    }
+}
+
+impl BloomOptions
+{
+    // `--bloom` with `--bloom-threshold`, `--bloom-knee`, `--bloom-intensity`, `--bloom-radius` and
+    // `--bloom-levels`. None without `--bloom`
+    pub fn from_args() -> Option<BloomOptions>
+    {
+        if !utils::has_flag("--bloom") {
+            return None;
+        }
+
+        let defaults = BloomOptions::default();
+        let float = |name: &str, default: f32| utils::arg_value(name).and_then(|value| value.parse().ok()).unwrap_or(default);
+        Some(BloomOptions {
+            threshold: float("--bloom-threshold", defaults.threshold),
+            knee: float("--bloom-knee", defaults.knee),
+            intensity: float("--bloom-intensity", defaults.intensity),
+            radius: float("--bloom-radius", defaults.radius),
+            levels: utils::arg_value("--bloom-levels").and_then(|value| value.parse().ok()).unwrap_or(defaults.levels),
+        })
+    }
+}
+
+// Glow around highlights of the HDR scene, added before tone mapping. The bright parts are extracted while
+// downsampling into a chain of half size buffers, then each level is upsampled with a tent filter and added to the
+// next larger one, so every level contributes a wider blur. The largest level is added to the scene
+pub struct Bloom
+{
+    pub options: BloomOptions,
+    downsample: Handle<Shader>,
+    upsample: Handle<Shader>,
+    // half, quarter, ... of the scene size
+    levels: Vec<Framebuffer>,
+    // scene size the levels were made for
+    width: u32,
+    height: u32,
+}
+
+impl Bloom
+{
+    // `shader_dir` holds post.vs, post_bloom_downsample.fs and post_bloom_upsample.fs
+    pub fn new(assets: &mut AssetManager, shader_dir: &Path, width: u32, height: u32, options: BloomOptions) -> Result<Bloom, String>
+    {
+        let downsample = assets.shader(&shader_dir.join("post.vs"), &shader_dir.join("post_bloom_downsample.fs"))?;
+        let upsample = assets.shader(&shader_dir.join("post.vs"), &shader_dir.join("post_bloom_upsample.fs"))?;
+
+        let mut bloom = Bloom { options, downsample, upsample, levels: Vec::new(), width: 0, height: 0 };
+        bloom.resize(width, height)?;
+        Ok(bloom)
+    }
+
+    // recreates the levels for a new scene size or level count, does nothing if neither changed
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), String>
+    {
+        let count = self.options.levels.max(1) as usize;
+        if (width, height) == (self.width, self.height) && self.levels.len() == count.min(self.max_levels(width, height)) {
+            return Ok(());
+        }
+
+        self.levels.clear();
+        let (mut level_width, mut level_height) = (width, height);
+        for _ in 0..count.min(self.max_levels(width, height)) {
+            level_width = (level_width / 2).max(1);
+            level_height = (level_height / 2).max(1);
+            let desc = FramebufferDesc { color: vec![Attachment::texture(gl::RGBA16F)], depth: None, ..Default::default() };
+            self.levels.push(Framebuffer::new(level_width, level_height, desc)?);
+        }
+        self.width = width;
+        self.height = height;
+        Ok(())
+    }
+
+    // levels until the smaller side reaches 2 pixels
+    fn max_levels(&self, width: u32, height: u32) -> usize
+    {
+        (width.min(height).max(2).ilog2() as usize).max(1)
+    }
+
+    // Adds the bloom of `scene` to `scene`. Draws fullscreen triangles, the chain has its vertex array bound and
+    // depth testing off
+    pub fn apply(&mut self, scene: &Framebuffer)
+    {
+        if self.resize(scene.width, scene.height).is_err() || self.levels.is_empty() {
+            return;
+        }
+
+        self.downsample.use_shader();
+        self.downsample.set_int("image", 0);
+        self.downsample.set_float("threshold", self.options.threshold);
+        self.downsample.set_float("knee", self.options.knee);
+        let mut source = scene;
+        for (i, level) in self.levels.iter().enumerate() {
+            level.bind();
+            self.downsample.set_bool("prefilter", i == 0);
+            self.downsample.set_vec2("texelSize", &texel_size(source));
+            source.bind_color_texture(0, 0);
+            unsafe { gl::DrawArrays(gl::TRIANGLES, 0, 3); }
+            source = level;
+        }
+
+        self.upsample.use_shader();
+        self.upsample.set_int("image", 0);
+        self.upsample.set_float("radius", self.options.radius);
+        unsafe {
+            gl::Enable(gl::BLEND);
+            gl::BlendFunc(gl::ONE, gl::ONE);
+        }
+
+        self.upsample.set_float("intensity", 1.0);
+        for pair in self.levels.windows(2).rev() {
+            let (target, source) = (&pair[0], &pair[1]);
+            target.bind();
+            self.upsample.set_vec2("texelSize", &texel_size(source));
+            source.bind_color_texture(0, 0);
+            unsafe { gl::DrawArrays(gl::TRIANGLES, 0, 3); }
+        }
+
+        // the largest level holds the sum of all of them, averaged so the level count doesn't change the brightness
+        scene.bind();
+        self.upsample.set_float("intensity", self.options.intensity / self.levels.len() as f32);
+        self.upsample.set_vec2("texelSize", &texel_size(&self.levels[0]));
+        self.levels[0].bind_color_texture(0, 0);
+        unsafe {
+            gl::DrawArrays(gl::TRIANGLES, 0, 3);
+            gl::Disable(gl::BLEND);
+        }
+    }
+}
+
+// one pixel of `framebuffer` in texture coordinates
+fn texel_size(framebuffer: &Framebuffer) -> Vec2
+{
+    nalgebra_glm::vec2(1.0 / framebuffer.width as f32, 1.0 / framebuffer.height as f32)
+}
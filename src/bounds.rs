@@ -0,0 +1,386 @@
+use nalgebra_glm::{ Mat4, Vec3 };
+
+// Axis aligned bounding box. An empty box has min > max, merging with it returns the other box unchanged
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb
+{
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb
+{
+    pub fn new(min: Vec3, max: Vec3) -> Aabb
+    {
+        Aabb { min, max }
+    }
+
+    pub fn empty() -> Aabb
+    {
+        Aabb {
+            min: nalgebra_glm::vec3(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+            max: nalgebra_glm::vec3(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY),
+        }
+    }
+
+    pub fn from_points<'a, I>(points: I) -> Aabb
+    where I: IntoIterator<Item = &'a Vec3>
+    {
+        let mut aabb = Aabb::empty();
+        for p in points {
+            aabb.expand(p);
+        }
+        aabb
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    pub fn center(&self) -> Vec3
+    {
+        (self.min + self.max) * 0.5
+    }
+
+    // half of the size on each axis
+    pub fn extents(&self) -> Vec3
+    {
+        (self.max - self.min) * 0.5
+    }
+
+    pub fn size(&self) -> Vec3
+    {
+        self.max - self.min
+    }
+
+    pub fn expand(&mut self, point: &Vec3)
+    {
+        self.min = nalgebra_glm::min2(&self.min, point);
+        self.max = nalgebra_glm::max2(&self.max, point);
+    }
+
+    pub fn merge(&self, other: &Aabb) -> Aabb
+    {
+        Aabb {
+            min: nalgebra_glm::min2(&self.min, &other.min),
+            max: nalgebra_glm::max2(&self.max, &other.max),
+        }
+    }
+
+    // box enclosing this box after the affine transform `m` (Arvo's method), e.g. a model matrix
+    pub fn transform(&self, m: &Mat4) -> Aabb
+    {
+        if self.is_empty() {
+            return *self;
+        }
+
+        let mut min = nalgebra_glm::vec3(m[(0, 3)], m[(1, 3)], m[(2, 3)]);
+        let mut max = min;
+
+        for row in 0..3 {
+            for col in 0..3 {
+                let a = m[(row, col)] * self.min[col];
+                let b = m[(row, col)] * self.max[col];
+                min[row] += a.min(b);
+                max[row] += a.max(b);
+            }
+        }
+
+        Aabb { min, max }
+    }
+
+    pub fn contains_point(&self, point: &Vec3) -> bool
+    {
+        (0..3).all(|i| point[i] >= self.min[i] && point[i] <= self.max[i])
+    }
+
+    pub fn contains_aabb(&self, other: &Aabb) -> bool
+    {
+        !other.is_empty() && self.contains_point(&other.min) && self.contains_point(&other.max)
+    }
+
+    pub fn intersects(&self, other: &Aabb) -> bool
+    {
+        (0..3).all(|i| self.min[i] <= other.max[i] && self.max[i] >= other.min[i])
+    }
+
+    pub fn intersects_sphere(&self, sphere: &BoundingSphere) -> bool
+    {
+        if self.is_empty() || sphere.is_empty() {
+            return false;
+        }
+        let closest = nalgebra_glm::clamp_vec(&sphere.center, &self.min, &self.max);
+        nalgebra_glm::distance2(&closest, &sphere.center) <= sphere.radius * sphere.radius
+    }
+
+    // smallest sphere enclosing the box (not the tightest sphere around the original points)
+    pub fn bounding_sphere(&self) -> BoundingSphere
+    {
+        if self.is_empty() {
+            return BoundingSphere::empty();
+        }
+        BoundingSphere { center: self.center(), radius: nalgebra_glm::length(&self.extents()) }
+    }
+}
+
+// Bounding sphere. An empty sphere has a negative radius
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BoundingSphere
+{
+    pub center: Vec3,
+    pub radius: f32,
+}
+
+impl BoundingSphere
+{
+    pub fn new(center: Vec3, radius: f32) -> BoundingSphere
+    {
+        BoundingSphere { center, radius }
+    }
+
+    pub fn empty() -> BoundingSphere
+    {
+        BoundingSphere { center: nalgebra_glm::vec3(0.0f32, 0.0, 0.0), radius: -1.0 }
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.radius < 0.0
+    }
+
+    // Ritter's approximate bounding sphere: start from two far apart points and grow to enclose the rest.
+    // Usually somewhat larger than the minimal sphere, with no tight bound on how much
+    pub fn from_points(points: &[Vec3]) -> BoundingSphere
+    {
+        if points.is_empty() {
+            return BoundingSphere::empty();
+        }
+
+        let farthest_from = |from: &Vec3| {
+            *points.iter()
+                .max_by(|a, b| nalgebra_glm::distance2(from, a).partial_cmp(&nalgebra_glm::distance2(from, b)).unwrap_or(std::cmp::Ordering::Equal))
+                .unwrap()
+        };
+
+        let a = farthest_from(&points[0]);
+        let b = farthest_from(&a);
+
+        let mut sphere = BoundingSphere { center: (a + b) * 0.5, radius: nalgebra_glm::distance(&a, &b) * 0.5 };
+        for p in points {
+            sphere = sphere.expand(p);
+        }
+        sphere
+    }
+
+    // grows the sphere just enough to contain `point`
+    pub fn expand(&self, point: &Vec3) -> BoundingSphere
+    {
+        if self.is_empty() {
+            return BoundingSphere { center: *point, radius: 0.0 };
+        }
+
+        let distance = nalgebra_glm::distance(&self.center, point);
+        if distance <= self.radius {
+            return *self;
+        }
+
+        let radius = (self.radius + distance) * 0.5;
+        let center = self.center + (point - self.center) * ((radius - self.radius) / distance);
+        BoundingSphere { center, radius }
+    }
+
+    pub fn merge(&self, other: &BoundingSphere) -> BoundingSphere
+    {
+        if self.is_empty() {
+            return *other;
+        }
+        if other.is_empty() {
+            return *self;
+        }
+
+        let offset = other.center - self.center;
+        let distance = nalgebra_glm::length(&offset);
+
+        // one sphere inside the other
+        if distance + other.radius <= self.radius {
+            return *self;
+        }
+        if distance + self.radius <= other.radius {
+            return *other;
+        }
+
+        let radius = (distance + self.radius + other.radius) * 0.5;
+        let center = self.center + offset * ((radius - self.radius) / distance);
+        BoundingSphere { center, radius }
+    }
+
+    // sphere enclosing this sphere after the affine transform `m`. Non uniform scales use the largest axis scale
+    pub fn transform(&self, m: &Mat4) -> BoundingSphere
+    {
+        if self.is_empty() {
+            return *self;
+        }
+
+        let center = m * nalgebra_glm::vec4(self.center.x, self.center.y, self.center.z, 1.0);
+        let scale = (0..3)
+            .map(|col| nalgebra_glm::length(&nalgebra_glm::vec3(m[(0, col)], m[(1, col)], m[(2, col)])))
+            .fold(0.0f32, f32::max);
+
+        BoundingSphere { center: nalgebra_glm::vec3(center.x, center.y, center.z), radius: self.radius * scale }
+    }
+
+    pub fn contains_point(&self, point: &Vec3) -> bool
+    {
+        !self.is_empty() && nalgebra_glm::distance2(&self.center, point) <= self.radius * self.radius
+    }
+
+    pub fn contains_sphere(&self, other: &BoundingSphere) -> bool
+    {
+        !self.is_empty() && !other.is_empty() &&
+            nalgebra_glm::distance(&self.center, &other.center) + other.radius <= self.radius
+    }
+
+    pub fn intersects(&self, other: &BoundingSphere) -> bool
+    {
+        if self.is_empty() || other.is_empty() {
+            return false;
+        }
+        let radii = self.radius + other.radius;
+        nalgebra_glm::distance2(&self.center, &other.center) <= radii * radii
+    }
+
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool
+    {
+        aabb.intersects_sphere(self)
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use nalgebra_glm::vec3;
+
+    fn unit_box() -> Aabb
+    {
+        Aabb::new(vec3(-1.0, -1.0, -1.0), vec3(1.0, 1.0, 1.0))
+    }
+
+    fn approx_eq(a: &Vec3, b: &Vec3) -> bool
+    {
+        nalgebra_glm::distance(a, b) < 1e-5
+    }
+
+    #[test]
+    fn merge_with_empty_and_disjoint()
+    {
+        let a = unit_box();
+        assert_eq!(Aabb::empty().merge(&a), a);
+        assert_eq!(a.merge(&Aabb::empty()), a);
+
+        let b = Aabb::new(vec3(2.0, 0.0, -3.0), vec3(4.0, 0.5, -2.0));
+        let merged = a.merge(&b);
+        assert_eq!(merged, Aabb::new(vec3(-1.0, -1.0, -3.0), vec3(4.0, 1.0, 1.0)));
+        assert!(merged.contains_aabb(&a) && merged.contains_aabb(&b));
+    }
+
+    #[test]
+    fn transform_translates_and_scales()
+    {
+        let m = nalgebra_glm::translation(&vec3(1.0, 2.0, 3.0)) * nalgebra_glm::scaling(&vec3(2.0, 1.0, 0.5));
+        let transformed = unit_box().transform(&m);
+        assert!(approx_eq(&transformed.min, &vec3(-1.0, 1.0, 2.5)));
+        assert!(approx_eq(&transformed.max, &vec3(3.0, 3.0, 3.5)));
+        assert!(Aabb::empty().transform(&m).is_empty());
+    }
+
+    #[test]
+    fn transform_rotated_box_encloses_corners()
+    {
+        // rotated 45 degrees around y, the corners reach sqrt(2) on x and z
+        let m = nalgebra_glm::rotation(45.0f32.to_radians(), &vec3(0.0, 1.0, 0.0));
+        let transformed = unit_box().transform(&m);
+        let r = 2.0f32.sqrt();
+        assert!(approx_eq(&transformed.min, &vec3(-r, -1.0, -r)));
+        assert!(approx_eq(&transformed.max, &vec3(r, 1.0, r)));
+
+        // Arvo's box is the tightest box around the transformed corners
+        let mut corners = Aabb::empty();
+        for &x in &[-1.0f32, 1.0] {
+            for &y in &[-1.0f32, 1.0] {
+                for &z in &[-1.0f32, 1.0] {
+                    let p = m * nalgebra_glm::vec4(x, y, z, 1.0);
+                    corners.expand(&vec3(p.x, p.y, p.z));
+                }
+            }
+        }
+        assert!(approx_eq(&transformed.min, &corners.min) && approx_eq(&transformed.max, &corners.max));
+    }
+
+    #[test]
+    fn contains_and_intersects()
+    {
+        let outer = unit_box();
+        let nested = Aabb::new(vec3(-0.5, -0.5, -0.5), vec3(0.5, 0.5, 0.5));
+        let touching = Aabb::new(vec3(1.0, -1.0, -1.0), vec3(2.0, 1.0, 1.0));
+        let disjoint = Aabb::new(vec3(1.5, 0.0, 0.0), vec3(2.0, 1.0, 1.0));
+
+        assert!(outer.contains_aabb(&nested) && !nested.contains_aabb(&outer));
+        assert!(outer.intersects(&nested) && nested.intersects(&outer));
+        assert!(outer.intersects(&touching) && touching.intersects(&outer));
+        assert!(!outer.contains_aabb(&touching));
+        assert!(!outer.intersects(&disjoint) && !disjoint.intersects(&outer));
+        assert!(!outer.intersects(&Aabb::empty()) && !outer.contains_aabb(&Aabb::empty()));
+
+        assert!(outer.contains_point(&vec3(1.0, 0.0, -1.0)));
+        assert!(!outer.contains_point(&vec3(1.001, 0.0, 0.0)));
+
+        let sphere = BoundingSphere::new(vec3(2.0, 0.0, 0.0), 1.0);
+        assert!(outer.intersects_sphere(&sphere) && sphere.intersects_aabb(&outer));
+        assert!(!outer.intersects_sphere(&BoundingSphere::new(vec3(2.0, 2.0, 0.0), 1.0)));
+    }
+
+    #[test]
+    fn sphere_contains_and_intersects()
+    {
+        let a = BoundingSphere::new(vec3(0.0, 0.0, 0.0), 2.0);
+        let nested = BoundingSphere::new(vec3(0.5, 0.0, 0.0), 1.0);
+        let touching = BoundingSphere::new(vec3(3.0, 0.0, 0.0), 1.0);
+        let disjoint = BoundingSphere::new(vec3(4.0, 0.0, 0.0), 1.0);
+
+        assert!(a.contains_sphere(&nested) && !nested.contains_sphere(&a));
+        assert!(a.intersects(&touching) && !a.contains_sphere(&touching));
+        assert!(!a.intersects(&disjoint));
+        assert!(!a.intersects(&BoundingSphere::empty()));
+
+        let merged = a.merge(&disjoint);
+        assert!((merged.radius - 3.5).abs() < 1e-5 && approx_eq(&merged.center, &vec3(1.5, 0.0, 0.0)));
+        assert_eq!(a.merge(&nested), a);
+    }
+
+    #[test]
+    fn sphere_from_points_encloses_every_point()
+    {
+        // deterministic pseudo random cloud, stretched so the first guess has to grow
+        let mut state = 7u32;
+        let mut next = || {
+            state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+            (state >> 8) as f32 / (1 << 24) as f32 * 2.0 - 1.0
+        };
+        let points: Vec<Vec3> = (0..500).map(|_| vec3(next() * 3.0, next(), next() * 0.5 + 4.0)).collect();
+
+        let sphere = BoundingSphere::from_points(&points);
+        for p in &points {
+            assert!(nalgebra_glm::distance(&sphere.center, p) <= sphere.radius * (1.0 + 1e-5), "{:?} outside", p);
+        }
+        // not far from the box around the points
+        let aabb_sphere = Aabb::from_points(&points).bounding_sphere();
+        assert!(sphere.radius <= aabb_sphere.radius * 1.2);
+
+        assert!(BoundingSphere::from_points(&[]).is_empty());
+        let single = BoundingSphere::from_points(&[vec3(1.0, 2.0, 3.0)]);
+        assert_eq!(single.radius, 0.0);
+        assert!(approx_eq(&single.center, &vec3(1.0, 2.0, 3.0)));
+    }
+}
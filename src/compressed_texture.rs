@@ -0,0 +1,247 @@
+use gl::types::*;
+
+use std::path::Path;
+
+use super::bcn::{ self, BlockFormat };
+use super::dds;
+use super::ktx2;
+use super::utils;
+
+// S3TC formats come from GL_EXT_texture_compression_s3tc and GL_EXT_texture_sRGB, which aren't in the core bindings
+const COMPRESSED_RGB_S3TC_DXT1_EXT: GLenum = 0x83F0;
+const COMPRESSED_RGBA_S3TC_DXT1_EXT: GLenum = 0x83F1;
+const COMPRESSED_RGBA_S3TC_DXT3_EXT: GLenum = 0x83F2;
+const COMPRESSED_RGBA_S3TC_DXT5_EXT: GLenum = 0x83F3;
+const COMPRESSED_SRGB_S3TC_DXT1_EXT: GLenum = 0x8C4C;
+const COMPRESSED_SRGB_ALPHA_S3TC_DXT1_EXT: GLenum = 0x8C4D;
+const COMPRESSED_SRGB_ALPHA_S3TC_DXT3_EXT: GLenum = 0x8C4E;
+const COMPRESSED_SRGB_ALPHA_S3TC_DXT5_EXT: GLenum = 0x8C4F;
+
+// One mip level of every layer and face
+pub struct MipLevel
+{
+    pub width: u32,
+    pub height: u32,
+    // block data indexed by `layer * faces + face`
+    pub images: Vec<Vec<u8>>,
+}
+
+// Block compressed texture read from a DDS or KTX2 container, not uploaded yet
+pub struct CompressedImage
+{
+    pub format: BlockFormat,
+    pub srgb: bool,
+    pub width: u32,
+    pub height: u32,
+    // array layers, 1 for a single texture
+    pub layers: u32,
+    // 6 for cube maps, 1 otherwise
+    pub faces: u32,
+    // level 0 is the full size image
+    pub levels: Vec<MipLevel>,
+}
+
+impl CompressedImage
+{
+    pub fn is_cubemap(&self) -> bool
+    {
+        self.faces == 6
+    }
+
+    pub fn is_array(&self) -> bool
+    {
+        self.layers > 1
+    }
+
+    // GL texture target matching the layout
+    pub fn target(&self) -> GLenum
+    {
+        match (self.is_cubemap(), self.is_array()) {
+            (false, false) => gl::TEXTURE_2D,
+            (false, true) => gl::TEXTURE_2D_ARRAY,
+            (true, false) => gl::TEXTURE_CUBE_MAP,
+            (true, true) => gl::TEXTURE_CUBE_MAP_ARRAY,
+        }
+    }
+
+    // GL internal format for uploading the blocks as they are
+    pub fn gl_format(&self) -> GLenum
+    {
+        let srgb = self.srgb;
+        match self.format {
+            BlockFormat::Bc1 { alpha: false } => if srgb { COMPRESSED_SRGB_S3TC_DXT1_EXT } else { COMPRESSED_RGB_S3TC_DXT1_EXT },
+            BlockFormat::Bc1 { alpha: true } => if srgb { COMPRESSED_SRGB_ALPHA_S3TC_DXT1_EXT } else { COMPRESSED_RGBA_S3TC_DXT1_EXT },
+            BlockFormat::Bc2 => if srgb { COMPRESSED_SRGB_ALPHA_S3TC_DXT3_EXT } else { COMPRESSED_RGBA_S3TC_DXT3_EXT },
+            BlockFormat::Bc3 => if srgb { COMPRESSED_SRGB_ALPHA_S3TC_DXT5_EXT } else { COMPRESSED_RGBA_S3TC_DXT5_EXT },
+            BlockFormat::Bc4 { signed } => if signed { gl::COMPRESSED_SIGNED_RED_RGTC1 } else { gl::COMPRESSED_RED_RGTC1 },
+            BlockFormat::Bc5 { signed } => if signed { gl::COMPRESSED_SIGNED_RG_RGTC2 } else { gl::COMPRESSED_RG_RGTC2 },
+            BlockFormat::Bc6h { signed } => if signed { gl::COMPRESSED_RGB_BPTC_SIGNED_FLOAT } else { gl::COMPRESSED_RGB_BPTC_UNSIGNED_FLOAT },
+            BlockFormat::Bc7 => if srgb { gl::COMPRESSED_SRGB_ALPHA_BPTC_UNORM } else { gl::COMPRESSED_RGBA_BPTC_UNORM },
+        }
+    }
+
+    // whether the current GL context can sample the format without decoding it first
+    pub fn is_supported_by_gl(&self) -> bool
+    {
+        match self.format {
+            BlockFormat::Bc1 { .. } | BlockFormat::Bc2 | BlockFormat::Bc3 => {
+                utils::has_gl_extension("GL_EXT_texture_compression_s3tc") &&
+                    (!self.srgb || utils::has_gl_extension("GL_EXT_texture_sRGB"))
+            },
+            // RGTC is core since 3.0 and BPTC since 4.2
+            BlockFormat::Bc4 { .. } | BlockFormat::Bc5 { .. } => true,
+            BlockFormat::Bc6h { .. } | BlockFormat::Bc7 => {
+                utils::gl_version() >= (4, 2) || utils::has_gl_extension("GL_ARB_texture_compression_bptc")
+            },
+        }
+    }
+}
+
+// GL texture created from a CompressedImage. The target depends on the layout of the image (2D, 2D array, cube map
+// or cube map array)
+pub struct CompressedTexture
+{
+    pub id: GLuint,
+    pub target: GLenum,
+    pub width: u32,
+    pub height: u32,
+    pub layers: u32,
+    pub mip_levels: u32,
+    pub internal_format: GLenum,
+    // false when the blocks were decoded on the CPU, to RGBA16F for BC6H and RGBA8 otherwise
+    pub compressed: bool,
+}
+
+impl CompressedTexture
+{
+    // loads a .dds or .ktx2 file
+    pub fn from_file(path: &Path) -> Result<CompressedTexture, String>
+    {
+        let extension = path.extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase());
+        let image = match extension.as_deref() {
+            Some("dds") => dds::load_dds(path)?,
+            Some("ktx2") => ktx2::load_ktx2(path)?,
+            _ => return Err(format!("Unsupported compressed texture format: {}", path.display())),
+        };
+        CompressedTexture::upload(&image)
+    }
+
+    // uploads the blocks with glCompressedTexImage* when the driver supports the format, otherwise decodes every
+    // level first. BC6H keeps its range in a half float texture, the other formats are decoded to RGBA8
+    pub fn upload(image: &CompressedImage) -> Result<CompressedTexture, String>
+    {
+        let compressed = image.is_supported_by_gl();
+        let float = matches!(image.format, BlockFormat::Bc6h { .. });
+        let (internal_format, type_) = if compressed {
+            (image.gl_format(), gl::UNSIGNED_BYTE)
+        } else if float {
+            (gl::RGBA16F, gl::FLOAT)
+        } else if image.srgb {
+            (gl::SRGB8_ALPHA8, gl::UNSIGNED_BYTE)
+        } else {
+            (gl::RGBA8, gl::UNSIGNED_BYTE)
+        };
+
+        // decode before creating the texture so an unsupported format doesn't leak it
+        let mut decoded = Vec::new();
+        if !compressed {
+            for level in &image.levels {
+                let mut images = Vec::new();
+                for data in &level.images {
+                    let pixels = if float {
+                        let floats = bcn::decode_image_f32(image.format, level.width, level.height, data)?;
+                        floats.iter().flat_map(|value| value.to_ne_bytes()).collect()
+                    } else {
+                        bcn::decode_image(image.format, level.width, level.height, data)?
+                    };
+                    images.push(pixels);
+                }
+                decoded.push(images);
+            }
+        }
+
+        let target = image.target();
+        let mut id: GLuint = 0;
+
+        unsafe {
+            gl::GenTextures(1, &mut id);
+            gl::BindTexture(target, id);
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+
+            for (level_index, level) in image.levels.iter().enumerate() {
+                let images = if compressed { &level.images } else { &decoded[level_index] };
+                let level_index = level_index as GLint;
+                let (width, height) = (level.width as GLsizei, level.height as GLsizei);
+
+                if target == gl::TEXTURE_2D_ARRAY || target == gl::TEXTURE_CUBE_MAP_ARRAY {
+                    // arrays upload every layer (and face) of a level at once, as consecutive slices
+                    let data = images.concat();
+                    let depth = (image.layers * image.faces) as GLsizei;
+                    if compressed {
+                        gl::CompressedTexImage3D(target, level_index, internal_format, width, height, depth, 0,
+                            data.len() as GLsizei, data.as_ptr() as *const std::ffi::c_void);
+                    } else {
+                        gl::TexImage3D(target, level_index, internal_format as GLint, width, height, depth, 0,
+                            gl::RGBA, type_, data.as_ptr() as *const std::ffi::c_void);
+                    }
+                } else {
+                    for (face, data) in images.iter().enumerate() {
+                        let face_target = if target == gl::TEXTURE_CUBE_MAP { gl::TEXTURE_CUBE_MAP_POSITIVE_X + face as GLenum } else { target };
+                        if compressed {
+                            gl::CompressedTexImage2D(face_target, level_index, internal_format, width, height, 0,
+                                data.len() as GLsizei, data.as_ptr() as *const std::ffi::c_void);
+                        } else {
+                            gl::TexImage2D(face_target, level_index, internal_format as GLint, width, height, 0,
+                                gl::RGBA, type_, data.as_ptr() as *const std::ffi::c_void);
+                        }
+                    }
+                }
+            }
+
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 4);
+
+            // compressed levels can't be generated by GL, only sample the levels stored in the file
+            let mip_levels = image.levels.len() as GLint;
+            let min_filter = if mip_levels > 1 { gl::LINEAR_MIPMAP_LINEAR } else { gl::LINEAR };
+            gl::TexParameteri(target, gl::TEXTURE_MAX_LEVEL, mip_levels - 1);
+            gl::TexParameteri(target, gl::TEXTURE_MIN_FILTER, min_filter as GLint);
+            gl::TexParameteri(target, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
+            if image.is_cubemap() {
+                gl::TexParameteri(target, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as GLint);
+                gl::TexParameteri(target, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as GLint);
+                gl::TexParameteri(target, gl::TEXTURE_WRAP_R, gl::CLAMP_TO_EDGE as GLint);
+            }
+        }
+
+        if !compressed {
+            println!("{:?} is not supported by the GL driver, decoded to {}", image.format, if float { "RGBA16F" } else { "RGBA8" });
+        }
+
+        Ok(CompressedTexture {
+            id,
+            target,
+            width: image.width,
+            height: image.height,
+            layers: image.layers,
+            mip_levels: image.levels.len() as u32,
+            internal_format,
+            compressed,
+        })
+    }
+
+    pub fn bind(&self, unit: u32)
+    {
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0 + unit);
+            gl::BindTexture(self.target, self.id);
+        }
+    }
+}
+
+impl Drop for CompressedTexture
+{
+    fn drop(&mut self)
+    {
+        unsafe { gl::DeleteTextures(1, &self.id); }
+    }
+}
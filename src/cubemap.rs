@@ -0,0 +1,179 @@
+use gl::types::*;
+use image::{ GenericImageView, RgbaImage };
+use nalgebra_glm::{ Vec3 };
+
+use std::path::Path;
+
+// Face order of the GL cube map targets: +X, -X, +Y, -Y, +Z, -Z
+pub const FACE_NAMES: [&str; 6] = ["right", "left", "top", "bottom", "front", "back"];
+
+// direction through the texel center (x, y) of a face, following the cube map face orientations of the GL spec.
+// Row 0 is the first uploaded row
+pub fn face_direction(face: usize, x: u32, y: u32, size: u32) -> Vec3
+{
+    let s = 2.0 * (x as f32 + 0.5) / size as f32 - 1.0;
+    let t = 2.0 * (y as f32 + 0.5) / size as f32 - 1.0;
+
+    let direction = match face {
+        0 => nalgebra_glm::vec3(1.0, -t, -s),
+        1 => nalgebra_glm::vec3(-1.0, -t, s),
+        2 => nalgebra_glm::vec3(s, 1.0, t),
+        3 => nalgebra_glm::vec3(s, -1.0, -t),
+        4 => nalgebra_glm::vec3(s, -t, 1.0),
+        _ => nalgebra_glm::vec3(-s, -t, -1.0),
+    };
+    nalgebra_glm::normalize(&direction)
+}
+
+// bilinear sample of an equirectangular panorama in the direction `d`. The image center looks down -Z and the
+// top row is straight up
+fn sample_equirectangular(image: &RgbaImage, d: &Vec3) -> [f32; 4]
+{
+    use std::f32::consts::PI;
+
+    let u = 0.5 + d.x.atan2(-d.z) / (2.0 * PI);
+    let v = d.y.clamp(-1.0, 1.0).acos() / PI;
+
+    let (width, height) = image.dimensions();
+    let fx = u * width as f32 - 0.5;
+    let fy = (v * height as f32 - 0.5).clamp(0.0, (height - 1) as f32);
+    let (x0, y0) = (fx.floor(), fy.floor());
+    let (tx, ty) = (fx - x0, fy - y0);
+
+    // wrap around horizontally, clamp at the poles
+    let texel = |x: f32, y: f32| {
+        let x = (x as i64).rem_euclid(width as i64) as u32;
+        let y = (y as u32).min(height - 1);
+        image.get_pixel(x, y).0
+    };
+
+    let (a, b, c, e) = (texel(x0, y0), texel(x0 + 1.0, y0), texel(x0, y0 + 1.0), texel(x0 + 1.0, y0 + 1.0));
+    let mut result = [0.0f32; 4];
+    for (i, value) in result.iter_mut().enumerate() {
+        let top = a[i] as f32 * (1.0 - tx) + b[i] as f32 * tx;
+        let bottom = c[i] as f32 * (1.0 - tx) + e[i] as f32 * tx;
+        *value = top * (1.0 - ty) + bottom * ty;
+    }
+    result
+}
+
+// resamples an equirectangular (2:1 longitude/latitude) panorama into six square faces
+pub fn equirectangular_to_faces(panorama: &RgbaImage, face_size: u32) -> Vec<RgbaImage>
+{
+    (0..6).map(|face| {
+        RgbaImage::from_fn(face_size, face_size, |x, y| {
+            let color = sample_equirectangular(panorama, &face_direction(face, x, y, face_size));
+            image::Rgba([color[0].round() as u8, color[1].round() as u8, color[2].round() as u8, color[3].round() as u8])
+        })
+    }).collect()
+}
+
+pub struct Cubemap
+{
+    pub id: GLuint,
+    // width and height of every face
+    pub size: u32,
+    pub internal_format: GLenum,
+}
+
+impl Cubemap
+{
+    // six square faces of the same size in the order of FACE_NAMES
+    pub fn from_faces(paths: &[&Path], srgb: bool) -> Result<Cubemap, String>
+    {
+        if paths.len() != 6 {
+            return Err(format!("A cube map needs 6 faces, got {}", paths.len()));
+        }
+
+        let mut faces = Vec::new();
+        for path in paths {
+            let image = image::open(path).map_err(|e| format!("Failed to load image {}: {}", path.display(), e))?;
+            faces.push(image.to_rgba8());
+        }
+
+        Cubemap::from_images(&faces, srgb)
+    }
+
+    // loads the faces named right, left, top, bottom, front and back (.jpg, .png, ...) from a directory
+    pub fn from_directory(directory: &Path, srgb: bool) -> Result<Cubemap, String>
+    {
+        let mut paths = Vec::new();
+        for name in FACE_NAMES.iter() {
+            let path = ["jpg", "jpeg", "png", "bmp", "tga"].iter()
+                .map(|extension| directory.join(format!("{}.{}", name, extension)))
+                .find(|path| path.is_file())
+                .ok_or(format!("Missing cube map face {} in {}", name, directory.display()))?;
+            paths.push(path);
+        }
+
+        let paths: Vec<&Path> = paths.iter().map(|p| p.as_path()).collect();
+        Cubemap::from_faces(&paths, srgb)
+    }
+
+    // converts an equirectangular panorama on the CPU, using faces a quarter of the panorama width by default
+    pub fn from_equirectangular(path: &Path, face_size: Option<u32>, srgb: bool) -> Result<Cubemap, String>
+    {
+        let image = image::open(path).map_err(|e| format!("Failed to load image {}: {}", path.display(), e))?;
+        let face_size = face_size.unwrap_or_else(|| (image.width() / 4).max(1));
+        let faces = equirectangular_to_faces(&image.to_rgba8(), face_size);
+        Cubemap::from_images(&faces, srgb)
+    }
+
+    pub fn from_images(faces: &[RgbaImage], srgb: bool) -> Result<Cubemap, String>
+    {
+        let size = faces.first().map(|face| face.width()).unwrap_or(0);
+        if faces.len() != 6 || size == 0 || faces.iter().any(|face| face.dimensions() != (size, size)) {
+            return Err("Cube map faces must be 6 square images of the same size".to_string());
+        }
+
+        let internal_format = if srgb { gl::SRGB8_ALPHA8 } else { gl::RGBA8 };
+        let mut id: GLuint = 0;
+
+        unsafe {
+            gl::GenTextures(1, &mut id);
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, id);
+
+            for (i, face) in faces.iter().enumerate() {
+                gl::TexImage2D(
+                    gl::TEXTURE_CUBE_MAP_POSITIVE_X + i as GLenum,
+                    0,
+                    internal_format as i32,
+                    size as i32,
+                    size as i32,
+                    0,
+                    gl::RGBA,
+                    gl::UNSIGNED_BYTE,
+                    face.as_ptr() as *const std::ffi::c_void
+                );
+            }
+
+            gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+            gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+            gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_WRAP_R, gl::CLAMP_TO_EDGE as i32);
+            gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_MIN_FILTER, gl::LINEAR_MIPMAP_LINEAR as i32);
+            gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+            gl::GenerateMipmap(gl::TEXTURE_CUBE_MAP);
+
+            // filter across face edges instead of clamping to each face
+            gl::Enable(gl::TEXTURE_CUBE_MAP_SEAMLESS);
+        }
+
+        Ok(Cubemap { id, size, internal_format })
+    }
+
+    pub fn bind(&self, unit: u32)
+    {
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0 + unit);
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, self.id);
+        }
+    }
+}
+
+impl Drop for Cubemap
+{
+    fn drop(&mut self)
+    {
+        unsafe { gl::DeleteTextures(1, &self.id); }
+    }
+}
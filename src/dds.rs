@@ -0,0 +1,227 @@
+use std::path::Path;
+
+use super::bcn::BlockFormat;
+use super::compressed_texture::{ CompressedImage, MipLevel };
+
+const MAGIC: &[u8; 4] = b"DDS ";
+const HEADER_SIZE: usize = 128;
+const DX10_HEADER_SIZE: usize = 20;
+
+const DDSD_MIPMAPCOUNT: u32 = 0x20000;
+const DDPF_FOURCC: u32 = 0x4;
+const DDSCAPS2_CUBEMAP: u32 = 0x200;
+const DDSCAPS2_VOLUME: u32 = 0x200000;
+const D3D10_RESOURCE_MISC_TEXTURECUBE: u32 = 0x4;
+const D3D10_RESOURCE_DIMENSION_TEXTURE2D: u32 = 3;
+
+fn read_u32(data: &[u8], offset: usize) -> u32
+{
+    u32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]])
+}
+
+// (format, srgb) of a legacy FourCC code
+fn fourcc_format(fourcc: &[u8]) -> Option<(BlockFormat, bool)>
+{
+    let format = match fourcc {
+        // DXT1 may carry 1 bit alpha, decoding it as RGBA is correct for opaque blocks too
+        b"DXT1" => BlockFormat::Bc1 { alpha: true },
+        b"DXT2" | b"DXT3" => BlockFormat::Bc2,
+        b"DXT4" | b"DXT5" => BlockFormat::Bc3,
+        b"ATI1" | b"BC4U" => BlockFormat::Bc4 { signed: false },
+        b"BC4S" => BlockFormat::Bc4 { signed: true },
+        b"ATI2" | b"BC5U" => BlockFormat::Bc5 { signed: false },
+        b"BC5S" => BlockFormat::Bc5 { signed: true },
+        _ => return None,
+    };
+    Some((format, false))
+}
+
+// (format, srgb) of a DXGI_FORMAT from the DX10 header. Typeless formats are read as UNORM
+fn dxgi_format(format: u32) -> Option<(BlockFormat, bool)>
+{
+    Some(match format {
+        70 | 71 => (BlockFormat::Bc1 { alpha: true }, false),
+        72 => (BlockFormat::Bc1 { alpha: true }, true),
+        73 | 74 => (BlockFormat::Bc2, false),
+        75 => (BlockFormat::Bc2, true),
+        76 | 77 => (BlockFormat::Bc3, false),
+        78 => (BlockFormat::Bc3, true),
+        79 | 80 => (BlockFormat::Bc4 { signed: false }, false),
+        81 => (BlockFormat::Bc4 { signed: true }, false),
+        82 | 83 => (BlockFormat::Bc5 { signed: false }, false),
+        84 => (BlockFormat::Bc5 { signed: true }, false),
+        94 | 95 => (BlockFormat::Bc6h { signed: false }, false),
+        96 => (BlockFormat::Bc6h { signed: true }, false),
+        97 | 98 => (BlockFormat::Bc7, false),
+        99 => (BlockFormat::Bc7, true),
+        _ => return None,
+    })
+}
+
+// Parses a block compressed (BC1-BC7) DDS file, with mip chains, texture arrays (DX10 header) and cube maps
+pub fn parse_dds(data: &[u8]) -> Result<CompressedImage, String>
+{
+    if data.len() < HEADER_SIZE || &data[0..4] != MAGIC {
+        return Err("Not a DDS file".to_string());
+    }
+
+    let flags = read_u32(data, 8);
+    let height = read_u32(data, 12);
+    let width = read_u32(data, 16);
+    let mip_count = if flags & DDSD_MIPMAPCOUNT != 0 { read_u32(data, 28).max(1) } else { 1 };
+    let pixel_flags = read_u32(data, 80);
+    let fourcc = &data[84..88];
+    let caps2 = read_u32(data, 112);
+
+    if width == 0 || height == 0 {
+        return Err("DDS has an empty size".to_string());
+    }
+    // the chain ends at 1x1, more levels would shift the size by 32 bits or more
+    let max_levels = 32 - width.max(height).leading_zeros();
+    if mip_count > max_levels {
+        return Err(format!("DDS has {} mip levels, a {}x{} image has at most {}", mip_count, width, height, max_levels));
+    }
+    if caps2 & DDSCAPS2_VOLUME != 0 {
+        return Err("Volume DDS textures are not supported".to_string());
+    }
+    if pixel_flags & DDPF_FOURCC == 0 {
+        return Err("Uncompressed DDS files are not supported".to_string());
+    }
+
+    let (format, srgb, layers, cubemap, data_offset) = if fourcc == b"DX10" {
+        if data.len() < HEADER_SIZE + DX10_HEADER_SIZE {
+            return Err("DDS DX10 header truncated".to_string());
+        }
+        let dxgi = read_u32(data, HEADER_SIZE);
+        let dimension = read_u32(data, HEADER_SIZE + 4);
+        let misc_flags = read_u32(data, HEADER_SIZE + 8);
+        let array_size = read_u32(data, HEADER_SIZE + 12).max(1);
+
+        if dimension != D3D10_RESOURCE_DIMENSION_TEXTURE2D {
+            return Err(format!("Unsupported DDS resource dimension {}", dimension));
+        }
+        let (format, srgb) = dxgi_format(dxgi).ok_or(format!("Unsupported DXGI format {}", dxgi))?;
+        (format, srgb, array_size, misc_flags & D3D10_RESOURCE_MISC_TEXTURECUBE != 0, HEADER_SIZE + DX10_HEADER_SIZE)
+    } else {
+        let (format, srgb) = fourcc_format(fourcc)
+            .ok_or(format!("Unsupported DDS FourCC {}", String::from_utf8_lossy(fourcc)))?;
+        (format, srgb, 1, caps2 & DDSCAPS2_CUBEMAP != 0, HEADER_SIZE)
+    };
+
+    // partial cube maps (only some faces present) are not handled, all six are expected
+    let faces = if cubemap { 6 } else { 1 };
+    let image_count = layers.checked_mul(faces).ok_or(format!("DDS array size {} is too large", layers))?;
+
+    let mut levels: Vec<MipLevel> = (0..mip_count).map(|level| MipLevel {
+        width: (width >> level).max(1),
+        height: (height >> level).max(1),
+        images: Vec::new(),
+    }).collect();
+
+    // DDS stores the full mip chain of each layer and face one after another
+    let mut offset = data_offset;
+    for _ in 0..image_count {
+        for level in levels.iter_mut() {
+            let size = format.image_size(level.width, level.height);
+            let image = offset.checked_add(size)
+                .and_then(|end| data.get(offset..end))
+                .ok_or(format!("DDS data truncated at level {}x{}", level.width, level.height))?;
+            level.images.push(image.to_vec());
+            offset += size;
+        }
+    }
+
+    Ok(CompressedImage { format, srgb, width, height, layers, faces, levels })
+}
+
+pub fn load_dds(path: &Path) -> Result<CompressedImage, String>
+{
+    let data = std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    parse_dds(&data).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use super::super::test_support::{ assert_solid, with_u32 };
+
+    const BC1_MIPS: &[u8] = include_bytes!("../tests/fixtures/bc1_mips.dds");
+    const BC3_ARRAY: &[u8] = include_bytes!("../tests/fixtures/bc3_array.dds");
+    const BC7_CUBE: &[u8] = include_bytes!("../tests/fixtures/bc7_cube.dds");
+
+    #[test]
+    fn bc1_mip_chain()
+    {
+        let image = parse_dds(BC1_MIPS).unwrap();
+        assert_eq!(image.format, BlockFormat::Bc1 { alpha: true });
+        assert_eq!((image.width, image.height, image.layers, image.faces), (8, 8, 1, 1));
+
+        let sizes: Vec<(u32, u32)> = image.levels.iter().map(|level| (level.width, level.height)).collect();
+        assert_eq!(sizes, [(8, 8), (4, 4), (2, 2), (1, 1)]);
+
+        let colors = [[255, 0, 0, 255], [0, 255, 0, 255], [0, 0, 255, 255], [255, 255, 255, 255]];
+        for (level, color) in colors.iter().enumerate() {
+            assert_eq!(image.levels[level].images.len(), 1);
+            assert_solid(&image, level, 0, *color);
+        }
+    }
+
+    #[test]
+    fn bc3_array()
+    {
+        let image = parse_dds(BC3_ARRAY).unwrap();
+        assert_eq!((image.format, image.srgb), (BlockFormat::Bc3, false));
+        assert!(image.is_array() && !image.is_cubemap());
+        assert_eq!((image.layers, image.levels.len(), image.levels[0].images.len()), (3, 1, 3));
+
+        assert_solid(&image, 0, 0, [255, 0, 0, 0]);
+        assert_solid(&image, 0, 1, [0, 255, 0, 128]);
+        assert_solid(&image, 0, 2, [0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn bc7_cube_map()
+    {
+        let image = parse_dds(BC7_CUBE).unwrap();
+        assert_eq!(image.format, BlockFormat::Bc7);
+        assert!(image.is_cubemap() && !image.is_array());
+        assert_eq!(image.levels.len(), 3);
+
+        // faces are stored with their whole mip chain, the parser regroups them by level
+        let faces = [[255, 1, 1], [1, 255, 1], [1, 1, 255], [255, 255, 1], [1, 255, 255], [255, 1, 255]];
+        for (level, alpha) in [255, 191, 127].iter().enumerate() {
+            assert_eq!(image.levels[level].images.len(), 6);
+            for (face, [r, g, b]) in faces.iter().enumerate() {
+                assert_solid(&image, level, face, [*r, *g, *b, *alpha]);
+            }
+        }
+    }
+
+    #[test]
+    fn truncated_files_fail()
+    {
+        for data in [BC1_MIPS, BC3_ARRAY, BC7_CUBE].iter() {
+            for length in 0..data.len() {
+                assert!(parse_dds(&data[..length]).is_err(), "{} of {} bytes parsed", length, data.len());
+            }
+        }
+    }
+
+    #[test]
+    fn oversized_headers_fail()
+    {
+        // an 8x8 image has 4 levels at most
+        assert!(parse_dds(&with_u32(BC1_MIPS, 28, 5)).is_err());
+        assert!(parse_dds(&with_u32(BC1_MIPS, 28, u32::MAX)).is_err());
+
+        // sizes no file can hold, with and without a full mip chain
+        let huge = with_u32(&with_u32(BC1_MIPS, 12, u32::MAX), 16, u32::MAX);
+        assert!(parse_dds(&huge).is_err());
+        assert!(parse_dds(&with_u32(&huge, 28, 32)).is_err());
+
+        // array sizes whose image count overflows with the six cube faces, or that just don't fit the data
+        assert!(parse_dds(&with_u32(BC7_CUBE, HEADER_SIZE + 12, u32::MAX)).is_err());
+        assert!(parse_dds(&with_u32(BC3_ARRAY, HEADER_SIZE + 12, u32::MAX)).is_err());
+    }
+}
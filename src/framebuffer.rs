@@ -0,0 +1,414 @@
+use gl::types::*;
+
+// Where an attachment's pixels live. Textures can be sampled afterwards (post processing, shadow maps),
+// renderbuffers can only be drawn to, read back and blitted but may be faster
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AttachmentStorage {
+    Texture,
+    Renderbuffer,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Attachment
+{
+    // sized format, e.g. gl::RGBA8, gl::RGBA16F, gl::DEPTH24_STENCIL8
+    pub internal_format: GLenum,
+    pub storage: AttachmentStorage,
+}
+
+impl Attachment
+{
+    pub fn texture(internal_format: GLenum) -> Attachment
+    {
+        Attachment { internal_format, storage: AttachmentStorage::Texture }
+    }
+
+    pub fn renderbuffer(internal_format: GLenum) -> Attachment
+    {
+        Attachment { internal_format, storage: AttachmentStorage::Renderbuffer }
+    }
+}
+
+// Layout of a framebuffer: color attachments in draw buffer order (fragment output locations 0, 1, ...) and an
+// optional depth, depth/stencil or stencil attachment
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct FramebufferDesc
+{
+    pub color: Vec<Attachment>,
+    pub depth: Option<Attachment>,
+    // MSAA samples per pixel, 0 or 1 for none. Multisampled framebuffers can't be read or sampled like normal
+    // ones, they are resolved by blitting into a single sampled framebuffer of the same size
+    pub samples: u32,
+}
+
+// (format, type) accepted by glTexImage2D for an internal format. No data is uploaded, they only have to match
+fn texture_upload_format(internal_format: GLenum) -> (GLenum, GLenum)
+{
+    match internal_format {
+        gl::R8 => (gl::RED, gl::UNSIGNED_BYTE),
+        gl::RG8 => (gl::RG, gl::UNSIGNED_BYTE),
+        gl::RGB8 | gl::SRGB8 => (gl::RGB, gl::UNSIGNED_BYTE),
+        gl::R16F | gl::R32F => (gl::RED, gl::FLOAT),
+        gl::RG16F | gl::RG32F => (gl::RG, gl::FLOAT),
+        gl::RGB16F | gl::RGB32F | gl::R11F_G11F_B10F => (gl::RGB, gl::FLOAT),
+        gl::RGBA16F | gl::RGBA32F => (gl::RGBA, gl::FLOAT),
+        gl::RGB10_A2 => (gl::RGBA, gl::UNSIGNED_INT_2_10_10_10_REV),
+        gl::R32UI => (gl::RED_INTEGER, gl::UNSIGNED_INT),
+        gl::DEPTH_COMPONENT16 | gl::DEPTH_COMPONENT24 | gl::DEPTH_COMPONENT32 | gl::DEPTH_COMPONENT32F => {
+            (gl::DEPTH_COMPONENT, gl::FLOAT)
+        },
+        gl::DEPTH24_STENCIL8 => (gl::DEPTH_STENCIL, gl::UNSIGNED_INT_24_8),
+        gl::DEPTH32F_STENCIL8 => (gl::DEPTH_STENCIL, gl::FLOAT_32_UNSIGNED_INT_24_8_REV),
+        gl::STENCIL_INDEX8 => (gl::STENCIL_INDEX, gl::UNSIGNED_BYTE),
+        _ => (gl::RGBA, gl::UNSIGNED_BYTE),
+    }
+}
+
+// attachment point of a depth and/or stencil format
+fn depth_attachment_point(internal_format: GLenum) -> GLenum
+{
+    match internal_format {
+        gl::DEPTH24_STENCIL8 | gl::DEPTH32F_STENCIL8 => gl::DEPTH_STENCIL_ATTACHMENT,
+        gl::STENCIL_INDEX8 => gl::STENCIL_ATTACHMENT,
+        _ => gl::DEPTH_ATTACHMENT,
+    }
+}
+
+fn is_depth_format(internal_format: GLenum) -> bool
+{
+    matches!(texture_upload_format(internal_format).0, gl::DEPTH_COMPONENT | gl::DEPTH_STENCIL | gl::STENCIL_INDEX)
+}
+
+// what glCheckFramebufferStatus complains about, in words
+fn status_message(status: GLenum) -> String
+{
+    match status {
+        gl::FRAMEBUFFER_UNDEFINED => "the default framebuffer doesn't exist".to_string(),
+        gl::FRAMEBUFFER_INCOMPLETE_ATTACHMENT => "an attachment is incomplete (zero size or a format that can't be rendered to)".to_string(),
+        gl::FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT => "there are no attachments".to_string(),
+        gl::FRAMEBUFFER_INCOMPLETE_DRAW_BUFFER => "a draw buffer has no attachment".to_string(),
+        gl::FRAMEBUFFER_INCOMPLETE_READ_BUFFER => "the read buffer has no attachment".to_string(),
+        gl::FRAMEBUFFER_UNSUPPORTED => "the driver doesn't support this combination of formats".to_string(),
+        gl::FRAMEBUFFER_INCOMPLETE_MULTISAMPLE => "the attachments have different sample counts".to_string(),
+        gl::FRAMEBUFFER_INCOMPLETE_LAYER_TARGETS => "layered and non layered attachments are mixed".to_string(),
+        _ => format!("unknown status 0x{:X}", status),
+    }
+}
+
+// GL object backing one attachment
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum AttachmentObject {
+    Texture(GLuint),
+    Renderbuffer(GLuint),
+}
+
+impl AttachmentObject
+{
+    // creates the storage and attaches it to the bound framebuffer
+    fn create(attachment: &Attachment, attachment_point: GLenum, width: u32, height: u32, samples: u32) -> AttachmentObject
+    {
+        let (width, height) = (width as GLsizei, height as GLsizei);
+        let mut id: GLuint = 0;
+
+        unsafe {
+            match attachment.storage {
+                // multisample textures have no filtering or mips, every sample is fetched separately with texelFetch
+                AttachmentStorage::Texture if samples > 1 => {
+                    gl::GenTextures(1, &mut id);
+                    gl::BindTexture(gl::TEXTURE_2D_MULTISAMPLE, id);
+                    gl::TexImage2DMultisample(gl::TEXTURE_2D_MULTISAMPLE, samples as GLsizei, attachment.internal_format, width, height, gl::TRUE);
+                    gl::FramebufferTexture2D(gl::FRAMEBUFFER, attachment_point, gl::TEXTURE_2D_MULTISAMPLE, id, 0);
+                    AttachmentObject::Texture(id)
+                },
+                AttachmentStorage::Texture => {
+                    let (format, type_) = texture_upload_format(attachment.internal_format);
+                    // depth is usually compared or read exactly, color filtered when sampled by a later pass
+                    let filter = if is_depth_format(attachment.internal_format) { gl::NEAREST } else { gl::LINEAR };
+
+                    gl::GenTextures(1, &mut id);
+                    gl::BindTexture(gl::TEXTURE_2D, id);
+                    gl::TexImage2D(gl::TEXTURE_2D, 0, attachment.internal_format as GLint, width, height, 0, format, type_, std::ptr::null());
+                    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, filter as GLint);
+                    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, filter as GLint);
+                    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as GLint);
+                    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as GLint);
+                    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAX_LEVEL, 0);
+                    gl::FramebufferTexture2D(gl::FRAMEBUFFER, attachment_point, gl::TEXTURE_2D, id, 0);
+                    AttachmentObject::Texture(id)
+                },
+                AttachmentStorage::Renderbuffer => {
+                    gl::GenRenderbuffers(1, &mut id);
+                    gl::BindRenderbuffer(gl::RENDERBUFFER, id);
+                    if samples > 1 {
+                        gl::RenderbufferStorageMultisample(gl::RENDERBUFFER, samples as GLsizei, attachment.internal_format, width, height);
+                    } else {
+                        gl::RenderbufferStorage(gl::RENDERBUFFER, attachment.internal_format, width, height);
+                    }
+                    gl::FramebufferRenderbuffer(gl::FRAMEBUFFER, attachment_point, gl::RENDERBUFFER, id);
+                    AttachmentObject::Renderbuffer(id)
+                },
+            }
+        }
+    }
+
+    fn delete(self)
+    {
+        unsafe {
+            match self {
+                AttachmentObject::Texture(id) => gl::DeleteTextures(1, &id),
+                AttachmentObject::Renderbuffer(id) => gl::DeleteRenderbuffers(1, &id),
+            }
+        }
+    }
+
+    fn texture(self) -> Option<GLuint>
+    {
+        match self {
+            AttachmentObject::Texture(id) => Some(id),
+            AttachmentObject::Renderbuffer(_) => None,
+        }
+    }
+}
+
+// Framebuffer object owning its attachments. All attachments share the framebuffer size
+pub struct Framebuffer
+{
+    pub id: GLuint,
+    pub width: u32,
+    pub height: u32,
+    pub desc: FramebufferDesc,
+    color: Vec<AttachmentObject>,
+    depth: Option<AttachmentObject>,
+}
+
+impl Framebuffer
+{
+    pub fn new(width: u32, height: u32, desc: FramebufferDesc) -> Result<Framebuffer, String>
+    {
+        let mut id: GLuint = 0;
+        unsafe { gl::GenFramebuffers(1, &mut id); }
+
+        // dropped on error, which frees what was created
+        let mut framebuffer = Framebuffer { id, width, height, desc, color: Vec::new(), depth: None };
+        framebuffer.create_attachments()?;
+        Ok(framebuffer)
+    }
+
+    // single color attachment plus depth/stencil, the usual target of a scene pass
+    pub fn with_color_and_depth(width: u32, height: u32, color_format: GLenum, storage: AttachmentStorage) -> Result<Framebuffer, String>
+    {
+        let desc = FramebufferDesc {
+            color: vec![Attachment { internal_format: color_format, storage }],
+            depth: Some(Attachment::renderbuffer(gl::DEPTH24_STENCIL8)),
+            samples: 0,
+        };
+        Framebuffer::new(width, height, desc)
+    }
+
+    // multisampled color and depth/stencil renderbuffers, drawn to and then resolved with `blit_to`
+    pub fn multisampled(width: u32, height: u32, color_format: GLenum, samples: u32) -> Result<Framebuffer, String>
+    {
+        let desc = FramebufferDesc {
+            color: vec![Attachment::renderbuffer(color_format)],
+            depth: Some(Attachment::renderbuffer(gl::DEPTH24_STENCIL8)),
+            samples,
+        };
+        Framebuffer::new(width, height, desc)
+    }
+
+    // GL_MAX_SAMPLES, the most MSAA samples any format supports
+    pub fn max_samples() -> u32
+    {
+        let mut max = 0;
+        unsafe { gl::GetIntegerv(gl::MAX_SAMPLES, &mut max); }
+        max.max(0) as u32
+    }
+
+    fn create_attachments(&mut self) -> Result<(), String>
+    {
+        if self.width == 0 || self.height == 0 {
+            return Err(format!("Framebuffer size {}x{} is empty", self.width, self.height));
+        }
+
+        let max_color = {
+            let mut max = 0;
+            unsafe { gl::GetIntegerv(gl::MAX_COLOR_ATTACHMENTS, &mut max); }
+            max.max(1) as usize
+        };
+        if self.desc.color.len() > max_color {
+            return Err(format!("Framebuffer has {} color attachments, the driver supports {}", self.desc.color.len(), max_color));
+        }
+        if self.desc.samples > 1 && self.desc.samples > Framebuffer::max_samples() {
+            return Err(format!("Framebuffer has {} samples, the driver supports {}", self.desc.samples, Framebuffer::max_samples()));
+        }
+
+        unsafe { gl::BindFramebuffer(gl::FRAMEBUFFER, self.id); }
+
+        self.color = self.desc.color.iter().enumerate()
+            .map(|(i, attachment)| AttachmentObject::create(attachment, gl::COLOR_ATTACHMENT0 + i as GLenum, self.width, self.height, self.desc.samples))
+            .collect();
+        self.depth = self.desc.depth.as_ref()
+            .map(|attachment| AttachmentObject::create(attachment, depth_attachment_point(attachment.internal_format), self.width, self.height, self.desc.samples));
+
+        let status = unsafe {
+            // every color attachment receives the fragment output with the same location, depth only targets
+            // (shadow maps) have no color buffer at all
+            if self.color.is_empty() {
+                gl::DrawBuffer(gl::NONE);
+                gl::ReadBuffer(gl::NONE);
+            } else {
+                let buffers: Vec<GLenum> = (0..self.color.len()).map(|i| gl::COLOR_ATTACHMENT0 + i as GLenum).collect();
+                gl::DrawBuffers(buffers.len() as GLsizei, buffers.as_ptr());
+                gl::ReadBuffer(gl::COLOR_ATTACHMENT0);
+            }
+
+            let status = gl::CheckFramebufferStatus(gl::FRAMEBUFFER);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+            status
+        };
+
+        if status != gl::FRAMEBUFFER_COMPLETE {
+            return Err(format!("Framebuffer {}x{} is incomplete: {}", self.width, self.height, status_message(status)));
+        }
+        Ok(())
+    }
+
+    fn delete_attachments(&mut self)
+    {
+        for attachment in self.color.drain(..) {
+            attachment.delete();
+        }
+        if let Some(attachment) = self.depth.take() {
+            attachment.delete();
+        }
+    }
+
+    // recreates the attachments at the new size, their contents are lost. Does nothing when the size didn't change
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), String>
+    {
+        if (width, height) == (self.width, self.height) {
+            return Ok(());
+        }
+
+        self.delete_attachments();
+        self.width = width;
+        self.height = height;
+        self.create_attachments()
+    }
+
+    // binds it for drawing and reading and sets the viewport to cover it
+    pub fn bind(&self)
+    {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.id);
+            gl::Viewport(0, 0, self.width as GLsizei, self.height as GLsizei);
+        }
+    }
+
+    // back to the window (or nothing in headless mode), with a viewport of the given size
+    pub fn bind_default(width: u32, height: u32)
+    {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+            gl::Viewport(0, 0, width as GLsizei, height as GLsizei);
+        }
+    }
+
+    // texture of color attachment `index`, None for renderbuffers
+    pub fn color_texture(&self, index: usize) -> Option<GLuint>
+    {
+        self.color.get(index).and_then(|attachment| attachment.texture())
+    }
+
+    pub fn depth_texture(&self) -> Option<GLuint>
+    {
+        self.depth.and_then(|attachment| attachment.texture())
+    }
+
+    // binds the texture of color attachment `index` on texture unit `unit`
+    pub fn bind_color_texture(&self, index: usize, unit: u32)
+    {
+        if let Some(texture) = self.color_texture(index) {
+            unsafe {
+                gl::ActiveTexture(gl::TEXTURE0 + unit);
+                gl::BindTexture(self.texture_target(), texture);
+            }
+        }
+    }
+
+    // gl::TEXTURE_2D_MULTISAMPLE for multisampled texture attachments
+    pub fn texture_target(&self) -> GLenum
+    {
+        if self.desc.samples > 1 { gl::TEXTURE_2D_MULTISAMPLE } else { gl::TEXTURE_2D }
+    }
+
+    pub fn bind_depth_texture(&self, unit: u32)
+    {
+        if let Some(texture) = self.depth_texture() {
+            unsafe {
+                gl::ActiveTexture(gl::TEXTURE0 + unit);
+                gl::BindTexture(self.texture_target(), texture);
+            }
+        }
+    }
+
+    // color attachment `index` as 8 bit RGBA, top row first. Float attachments are clamped to [0, 1]. Multisampled
+    // framebuffers have to be resolved first
+    pub fn read_pixels(&self, index: usize) -> image::RgbaImage
+    {
+        let mut pixels = vec![0u8; self.width as usize * self.height as usize * 4];
+        unsafe {
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.id);
+            gl::ReadBuffer(gl::COLOR_ATTACHMENT0 + index as GLenum);
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 4);
+            gl::ReadPixels(0, 0, self.width as GLsizei, self.height as GLsizei, gl::RGBA, gl::UNSIGNED_BYTE,
+                pixels.as_mut_ptr() as *mut std::ffi::c_void);
+            gl::ReadBuffer(gl::COLOR_ATTACHMENT0);
+        }
+        let image = image::RgbaImage::from_raw(self.width, self.height, pixels).unwrap();
+        // GL returns the bottom row first
+        image::imageops::flip_vertical(&image)
+    }
+
+    fn blit(&self, target: GLuint, target_width: u32, target_height: u32, color_index: usize, mask: GLbitfield, filter: GLenum)
+    {
+        unsafe {
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.id);
+            gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, target);
+            if !self.color.is_empty() {
+                gl::ReadBuffer(gl::COLOR_ATTACHMENT0 + color_index as GLenum);
+            }
+            gl::BlitFramebuffer(
+                0, 0, self.width as GLint, self.height as GLint,
+                0, 0, target_width as GLint, target_height as GLint,
+                mask, filter
+            );
+            if !self.color.is_empty() {
+                gl::ReadBuffer(gl::COLOR_ATTACHMENT0);
+            }
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+    }
+
+    // copies color attachment `color_index` (and depth/stencil when they are in `mask`) into every color attachment
+    // of `target`, scaled to its size. Depth and stencil need gl::NEAREST and equal sizes
+    pub fn blit_to(&self, target: &Framebuffer, color_index: usize, mask: GLbitfield, filter: GLenum)
+    {
+        self.blit(target.id, target.width, target.height, color_index, mask, filter);
+    }
+
+    // same into the default framebuffer of the given size, e.g. to present an offscreen render
+    pub fn blit_to_default(&self, width: u32, height: u32, color_index: usize, mask: GLbitfield, filter: GLenum)
+    {
+        self.blit(0, width, height, color_index, mask, filter);
+    }
+}
+
+impl Drop for Framebuffer
+{
+    fn drop(&mut self)
+    {
+        self.delete_attachments();
+        unsafe { gl::DeleteFramebuffers(1, &self.id); }
+    }
+}
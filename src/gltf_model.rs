@@ -0,0 +1,791 @@
+use gl::types::*;
+use image::DynamicImage;
+use nalgebra_glm::{ Mat4, Vec3, Vec4 };
+
+use std::collections::HashSet;
+use std::path::Path;
+
+use super::bounds::{ Aabb };
+use super::camera::Camera;
+use super::mesh::{ Mesh, Vertex };
+use super::mesh_optimizer;
+use super::mesh_simplify::MeshLod;
+use super::shader::Shader;
+use super::texture::{ PixelFormat, Texture2D, TextureFilter, TextureOptions, TextureWrap };
+
+// A glTF document with its buffers and decoded images, before anything is uploaded
+struct GltfData
+{
+    document: gltf::Document,
+    buffers: Vec<Vec<u8>>,
+    images: Vec<DynamicImage>,
+}
+
+impl GltfData
+{
+    // .gltf or .glb, external buffers and images are looked up next to the file
+    fn open(path: &Path) -> Result<GltfData, String>
+    {
+        let data = std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        GltfData::from_slice(&data, path.parent())
+    }
+
+    // relative URIs are resolved against `base_dir`, without one only embedded data can be read
+    fn from_slice(data: &[u8], base_dir: Option<&Path>) -> Result<GltfData, String>
+    {
+        let gltf::Gltf { document, mut blob } = gltf::Gltf::from_slice(data).map_err(|e| e.to_string())?;
+
+        let mut buffers = Vec::new();
+        for buffer in document.buffers() {
+            let mut bytes = match buffer.source() {
+                gltf::buffer::Source::Bin => blob.take().ok_or("Buffer refers to a missing GLB binary chunk")?,
+                gltf::buffer::Source::Uri(uri) => read_uri(uri, base_dir)?,
+            };
+            if bytes.len() < buffer.length() {
+                return Err(format!("Buffer {} holds {} bytes, expected {}", buffer.index(), bytes.len(), buffer.length()));
+            }
+            // GLB chunks are padded to 4 bytes
+            bytes.truncate(buffer.length());
+            buffers.push(bytes);
+        }
+
+        let mut images = Vec::new();
+        for image in document.images() {
+            let external;
+            let bytes = match image.source() {
+                gltf::image::Source::View { view, .. } => {
+                    let (start, end) = (view.offset(), view.offset() + view.length());
+                    buffers[view.buffer().index()].get(start..end)
+                        .ok_or(format!("Image {} is outside of its buffer", image.index()))?
+                },
+                gltf::image::Source::Uri { uri, .. } => {
+                    external = read_uri(uri, base_dir)?;
+                    &external[..]
+                },
+            };
+            images.push(image::load_from_memory(bytes).map_err(|e| format!("Failed to decode image {}: {}", image.index(), e))?);
+        }
+
+        Ok(GltfData { document, buffers, images })
+    }
+}
+
+// contents of a base64 data URI, or of a file relative to `base_dir`
+fn read_uri(uri: &str, base_dir: Option<&Path>) -> Result<Vec<u8>, String>
+{
+    if let Some(data) = uri.strip_prefix("data:") {
+        let (_, encoded) = data.split_once(";base64,").ok_or("Only base64 data URIs are supported")?;
+        return base64::decode(encoded).map_err(|e| format!("Failed to decode a data URI: {}", e));
+    }
+
+    let base_dir = base_dir.ok_or(format!("No directory to look up {} in", uri))?;
+    let path = base_dir.join(urlencoding::decode(uri).map_err(|e| format!("Invalid URI {}: {}", uri, e))?.as_ref());
+    std::fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AlphaMode {
+    Opaque,
+    Mask,
+    Blend,
+}
+
+// Reference from a material to one of the model textures
+#[derive(Clone, Copy, Debug)]
+pub struct TextureInfo
+{
+    pub texture: usize,
+    pub tex_coord: u32,
+}
+
+// PBR metallic-roughness material as described by the glTF 2.0 core spec
+#[derive(Clone, Debug)]
+pub struct PbrMaterial
+{
+    pub name: Option<String>,
+    pub base_color_factor: Vec4,
+    pub base_color_texture: Option<TextureInfo>,
+    pub metallic_factor: f32,
+    pub roughness_factor: f32,
+    // metalness is read from the blue channel, roughness from the green channel
+    pub metallic_roughness_texture: Option<TextureInfo>,
+    pub normal_texture: Option<TextureInfo>,
+    pub normal_scale: f32,
+    pub occlusion_texture: Option<TextureInfo>,
+    pub occlusion_strength: f32,
+    pub emissive_texture: Option<TextureInfo>,
+    pub emissive_factor: Vec3,
+    pub alpha_mode: AlphaMode,
+    pub alpha_cutoff: f32,
+    pub double_sided: bool,
+}
+
+impl PbrMaterial
+{
+    // material used by primitives that don't reference any material
+    pub fn default_material() -> PbrMaterial
+    {
+        PbrMaterial {
+            name: None,
+            base_color_factor: nalgebra_glm::vec4(1.0f32, 1.0, 1.0, 1.0),
+            base_color_texture: None,
+            metallic_factor: 1.0,
+            roughness_factor: 1.0,
+            metallic_roughness_texture: None,
+            normal_texture: None,
+            normal_scale: 1.0,
+            occlusion_texture: None,
+            occlusion_strength: 1.0,
+            emissive_texture: None,
+            emissive_factor: nalgebra_glm::vec3(0.0f32, 0.0, 0.0),
+            alpha_mode: AlphaMode::Opaque,
+            alpha_cutoff: 0.5,
+            double_sided: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum CameraProjection {
+    // a missing aspect ratio means "use the viewport one", a missing zfar means an infinite projection
+    Perspective { yfov: f32, aspect_ratio: Option<f32>, znear: f32, zfar: Option<f32> },
+    Orthographic { xmag: f32, ymag: f32, znear: f32, zfar: f32 },
+}
+
+#[derive(Clone, Debug)]
+pub struct GltfCamera
+{
+    pub name: Option<String>,
+    pub projection: CameraProjection,
+}
+
+impl GltfCamera
+{
+    pub fn projection_matrix(&self, viewport_aspect: f32) -> Mat4
+    {
+        match self.projection {
+            CameraProjection::Perspective { yfov, aspect_ratio, znear, zfar } => {
+                let aspect = aspect_ratio.unwrap_or(viewport_aspect);
+                match zfar {
+                    Some(zfar) => nalgebra_glm::perspective(aspect, yfov, znear, zfar),
+                    None => nalgebra_glm::infinite_perspective_rh_no(aspect, yfov, znear),
+                }
+            },
+            CameraProjection::Orthographic { xmag, ymag, znear, zfar } => {
+                nalgebra_glm::ortho(-xmag, xmag, -ymag, ymag, znear, zfar)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Node
+{
+    pub name: Option<String>,
+    // transform relative to the parent node
+    pub local_transform: Mat4,
+    pub children: Vec<usize>,
+    pub mesh: Option<usize>,
+    pub camera: Option<usize>,
+}
+
+// Levels of detail of one primitive, appended after the original triangles in its index buffer
+struct PrimitiveLod
+{
+    lod: MeshLod,
+    // (first index, index count) of each level in the index buffer
+    ranges: Vec<(usize, usize)>,
+}
+
+// A glTF 2.0 scene (.gltf with embedded or external buffers, or .glb) loaded into GL meshes and textures
+pub struct GltfModel
+{
+    // one entry per glTF mesh, holding one Mesh per primitive
+    pub meshes: Vec<Vec<Mesh>>,
+    pub materials: Vec<PbrMaterial>,
+    // indexed like the glTF textures
+    pub textures: Vec<Texture2D>,
+    pub nodes: Vec<Node>,
+    // root nodes of the default scene
+    pub root_nodes: Vec<usize>,
+    pub cameras: Vec<GltfCamera>,
+
+    // indexed like `meshes`, empty until generate_lods. None for point and line primitives
+    lods: Vec<Vec<Option<PrimitiveLod>>>,
+
+    default_material: PbrMaterial,
+    // 1x1 white texture bound when a material has no base color texture
+    white_texture: Texture2D,
+}
+
+impl GltfModel
+{
+    pub fn load(path: &Path) -> Result<GltfModel, String>
+    {
+        println!("Loading glTF model in path: {}", path.display());
+
+        let GltfData { document, buffers, images } = GltfData::open(path)
+            .map_err(|e| format!("Failed to import glTF {}: {}", path.display(), e))?;
+
+        let materials: Vec<PbrMaterial> = document.materials().map(|m| read_material(&m)).collect();
+
+        // color textures hold sRGB values, every other map is linear data
+        let mut srgb_textures = HashSet::new();
+        for material in &materials {
+            for info in [material.base_color_texture, material.emissive_texture].iter().flatten() {
+                srgb_textures.insert(info.texture);
+            }
+        }
+
+        let mut textures = Vec::new();
+        for texture in document.textures() {
+            let image = &images[texture.source().index()];
+            textures.push(upload_texture(image, &texture.sampler(), srgb_textures.contains(&texture.index())));
+        }
+
+        let mut meshes = Vec::new();
+        for mesh in document.meshes() {
+            let mut primitives = Vec::new();
+            for primitive in mesh.primitives() {
+                let mut built = read_primitive(&primitive, &buffers, &materials)?;
+                built.setup_mesh();
+                primitives.push(built);
+            }
+            meshes.push(primitives);
+        }
+
+        let nodes = read_nodes(&document);
+        let root_nodes = scene_roots(&document);
+
+        let cameras = document.cameras().map(|camera| {
+            let projection = match camera.projection() {
+                gltf::camera::Projection::Perspective(p) => CameraProjection::Perspective {
+                    yfov: p.yfov(),
+                    aspect_ratio: p.aspect_ratio(),
+                    znear: p.znear(),
+                    zfar: p.zfar(),
+                },
+                gltf::camera::Projection::Orthographic(o) => CameraProjection::Orthographic {
+                    xmag: o.xmag(),
+                    ymag: o.ymag(),
+                    znear: o.znear(),
+                    zfar: o.zfar(),
+                },
+            };
+            GltfCamera { name: camera.name().map(|s| s.to_string()), projection }
+        }).collect();
+
+        let white_format = PixelFormat { internal_format: gl::RGBA8, format: gl::RGBA, type_: gl::UNSIGNED_BYTE, pixel_size: 4 };
+        let white_texture = Texture2D::from_pixels(1, 1, white_format, &[255u8; 4], TextureOptions::default());
+
+        Ok(GltfModel {
+            meshes,
+            materials,
+            textures,
+            nodes,
+            root_nodes,
+            cameras,
+            lods: Vec::new(),
+            default_material: PbrMaterial::default_material(),
+            white_texture,
+        })
+    }
+
+    fn visit_nodes<F: FnMut(usize, &Mat4)>(&self, model: &Mat4, f: F)
+    {
+        visit_nodes(&self.nodes, &self.root_nodes, model, f);
+    }
+
+    // world transform of every node, indexed like `nodes`. Nodes outside the default scene keep the identity
+    pub fn node_world_transforms(&self, model: &Mat4) -> Vec<Mat4>
+    {
+        world_transforms(&self.nodes, &self.root_nodes, model)
+    }
+
+    // world space box around every mesh of the scene placed with `model`
+    pub fn bounds(&self, model: &Mat4) -> Aabb
+    {
+        let mut aabb = Aabb::empty();
+
+        self.visit_nodes(model, |index, world| {
+            if let Some(mesh_index) = self.nodes[index].mesh {
+                for mesh in &self.meshes[mesh_index] {
+                    aabb = aabb.merge(&mesh.aabb.transform(world));
+                }
+            }
+        });
+
+        aabb
+    }
+
+    // view matrices of every camera node in the scene, as (camera index, view matrix)
+    pub fn camera_views(&self) -> Vec<(usize, Mat4)>
+    {
+        let world = self.node_world_transforms(&Mat4::identity());
+        self.nodes.iter().enumerate()
+            .filter_map(|(i, node)| node.camera.map(|c| (c, nalgebra_glm::inverse(&world[i]))))
+            .collect()
+    }
+
+    // runs the mesh optimizer on every primitive and re-uploads them, returns one report per primitive
+    pub fn optimize_meshes(&mut self) -> Vec<mesh_optimizer::OptimizationReport>
+    {
+        let mut reports = Vec::new();
+        for mesh in self.meshes.iter_mut().flatten() {
+            reports.push(mesh_optimizer::optimize_mesh(mesh, 1e-6));
+            mesh.setup_mesh();
+        }
+        reports
+    }
+
+    // Builds `level_count` levels of detail for every triangle primitive (see MeshLod::generate) and re-uploads the
+    // index buffers. Call it once, after optimize_meshes. Returns the triangle count of each level per primitive
+    pub fn generate_lods(&mut self, level_count: usize, ratio: f32, max_error: f32) -> Vec<Vec<usize>>
+    {
+        let mut triangle_counts = Vec::new();
+        self.lods = self.meshes.iter_mut().map(|primitives| {
+            primitives.iter_mut().map(|mesh| {
+                mesh.convert_to_triangle_list();
+                if mesh.mode != gl::TRIANGLES {
+                    return None;
+                }
+
+                let lod = MeshLod::generate(mesh, level_count, ratio, max_error);
+                triangle_counts.push(lod.levels.iter().map(|level| level.indices.len() / 3).collect());
+                let (indices, ranges) = lod.merged_indices();
+                mesh.indices = indices;
+                mesh.setup_mesh();
+                Some(PrimitiveLod { lod, ranges })
+            }).collect()
+        }).collect();
+        triangle_counts
+    }
+
+    pub fn material(&self, mesh: &Mesh) -> &PbrMaterial
+    {
+        mesh.material.map(|m| &self.materials[m]).unwrap_or(&self.default_material)
+    }
+
+    // Draws every node of the scene with gltf.vs/gltf.fs, which must be in use with its lights set. The material
+    // textures go to units 0 to 4 (base color, metallic-roughness, normal, occlusion, emissive), missing ones are
+    // replaced by a white texture
+    pub fn draw(&self, shader: &Shader, model: &Mat4)
+    {
+        self.draw_nodes(shader, model, None);
+    }
+
+    // Same as draw, with the level of detail of each primitive picked from its size on a screen `screen_height`
+    // pixels high, see MeshLod::select_level_for_camera. Draws the full meshes without generate_lods
+    pub fn draw_lod(&self, shader: &Shader, model: &Mat4, camera: &Camera, screen_height: f32)
+    {
+        self.draw_nodes(shader, model, Some((camera, screen_height)));
+    }
+
+    // (first index, index count) to draw of a primitive, None to draw all of its indices
+    fn lod_range(&self, mesh_index: usize, primitive: usize, world: &Mat4, view: Option<(&Camera, f32)>) -> Option<(usize, usize)>
+    {
+        let primitive_lod = self.lods.get(mesh_index)?.get(primitive)?.as_ref()?;
+        let level = match view {
+            Some((camera, screen_height)) => {
+                let (_, sphere) = self.meshes[mesh_index][primitive].world_bounds(world);
+                primitive_lod.lod.select_level_for_camera(camera, &sphere.center, sphere.radius, screen_height)
+            },
+            None => 0,
+        };
+        Some(primitive_lod.ranges[level])
+    }
+
+    // binds the textures and sets the uniforms gltf.fs reads for `material`
+    fn bind_material(&self, shader: &Shader, material: &PbrMaterial)
+    {
+        let textures = [
+            material.base_color_texture,
+            material.metallic_roughness_texture,
+            material.normal_texture,
+            material.occlusion_texture,
+            material.emissive_texture,
+        ];
+        for (unit, info) in textures.iter().enumerate() {
+            info.map(|t| &self.textures[t.texture]).unwrap_or(&self.white_texture).bind(unit as u32);
+        }
+
+        shader.set_vec4("baseColorFactor", &material.base_color_factor);
+        shader.set_float("metallicFactor", material.metallic_factor);
+        shader.set_float("roughnessFactor", material.roughness_factor);
+        shader.set_bool("hasNormalTexture", material.normal_texture.is_some());
+        shader.set_float("normalScale", material.normal_scale);
+        // without an occlusion texture the white one would be mixed in for nothing
+        let occlusion_strength = if material.occlusion_texture.is_some() { material.occlusion_strength } else { 0.0 };
+        shader.set_float("occlusionStrength", occlusion_strength);
+        shader.set_vec3("emissiveFactor", &material.emissive_factor);
+        let cutoff = if material.alpha_mode == AlphaMode::Mask { material.alpha_cutoff } else { 0.0 };
+        shader.set_float("alphaCutoff", cutoff);
+        shader.set_bool("alphaBlend", material.alpha_mode == AlphaMode::Blend);
+    }
+
+    fn draw_nodes(&self, shader: &Shader, model: &Mat4, view: Option<(&Camera, f32)>)
+    {
+        let samplers = ["baseColorTexture", "metallicRoughnessTexture", "normalTexture", "occlusionTexture", "emissiveTexture"];
+        for (unit, name) in samplers.iter().enumerate() {
+            shader.set_int(name, unit as i32);
+        }
+
+        self.visit_nodes(model, |index, world| {
+            let mesh_index = match self.nodes[index].mesh {
+                Some(mesh_index) => mesh_index,
+                None => return,
+            };
+
+            shader.set_mat4("model", world);
+
+            for (primitive, mesh) in self.meshes[mesh_index].iter().enumerate() {
+                let material = self.material(mesh);
+                self.bind_material(shader, material);
+
+                unsafe {
+                    if material.alpha_mode == AlphaMode::Blend {
+                        gl::Enable(gl::BLEND);
+                        gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+                    }
+                }
+
+                match self.lod_range(mesh_index, primitive, world, view) {
+                    Some((first_index, count)) => mesh.draw_range(shader, first_index, count),
+                    None => mesh.draw(shader),
+                }
+
+                if material.alpha_mode == AlphaMode::Blend {
+                    unsafe { gl::Disable(gl::BLEND); }
+                }
+            }
+        });
+    }
+}
+
+// calls `f` with the index and world transform of every node reachable from `roots`
+fn visit_nodes<F: FnMut(usize, &Mat4)>(nodes: &[Node], roots: &[usize], model: &Mat4, mut f: F)
+{
+    let mut stack: Vec<(usize, Mat4)> = roots.iter().map(|&n| (n, *model)).collect();
+
+    while let Some((index, parent)) = stack.pop() {
+        let node = &nodes[index];
+        let world = parent * node.local_transform;
+        for &child in &node.children {
+            stack.push((child, world));
+        }
+        f(index, &world);
+    }
+}
+
+fn world_transforms(nodes: &[Node], roots: &[usize], model: &Mat4) -> Vec<Mat4>
+{
+    let mut world = vec![Mat4::identity(); nodes.len()];
+    visit_nodes(nodes, roots, model, |index, transform| world[index] = *transform);
+    world
+}
+
+fn read_nodes(document: &gltf::Document) -> Vec<Node>
+{
+    document.nodes().map(|node| {
+        Node {
+            name: node.name().map(|s| s.to_string()),
+            local_transform: Mat4::from(node.transform().matrix()),
+            children: node.children().map(|child| child.index()).collect(),
+            mesh: node.mesh().map(|m| m.index()),
+            camera: node.camera().map(|c| c.index()),
+        }
+    }).collect()
+}
+
+// root nodes of the default scene, or of the first one without a default
+fn scene_roots(document: &gltf::Document) -> Vec<usize>
+{
+    match document.default_scene().or_else(|| document.scenes().next()) {
+        Some(scene) => scene.nodes().map(|node| node.index()).collect(),
+        None => Vec::new(),
+    }
+}
+
+fn texture_info(info: Option<gltf::texture::Info>) -> Option<TextureInfo>
+{
+    info.map(|i| TextureInfo { texture: i.texture().index(), tex_coord: i.tex_coord() })
+}
+
+fn read_material(material: &gltf::Material) -> PbrMaterial
+{
+    let pbr = material.pbr_metallic_roughness();
+    let normal = material.normal_texture();
+    let occlusion = material.occlusion_texture();
+
+    PbrMaterial {
+        name: material.name().map(|s| s.to_string()),
+        base_color_factor: Vec4::from(pbr.base_color_factor()),
+        base_color_texture: texture_info(pbr.base_color_texture()),
+        metallic_factor: pbr.metallic_factor(),
+        roughness_factor: pbr.roughness_factor(),
+        metallic_roughness_texture: texture_info(pbr.metallic_roughness_texture()),
+        normal_texture: normal.as_ref().map(|n| TextureInfo { texture: n.texture().index(), tex_coord: n.tex_coord() }),
+        normal_scale: normal.as_ref().map(|n| n.scale()).unwrap_or(1.0),
+        occlusion_texture: occlusion.as_ref().map(|o| TextureInfo { texture: o.texture().index(), tex_coord: o.tex_coord() }),
+        occlusion_strength: occlusion.as_ref().map(|o| o.strength()).unwrap_or(1.0),
+        emissive_texture: texture_info(material.emissive_texture()),
+        emissive_factor: Vec3::from(material.emissive_factor()),
+        alpha_mode: match material.alpha_mode() {
+            gltf::material::AlphaMode::Opaque => AlphaMode::Opaque,
+            gltf::material::AlphaMode::Mask => AlphaMode::Mask,
+            gltf::material::AlphaMode::Blend => AlphaMode::Blend,
+        },
+        alpha_cutoff: material.alpha_cutoff().unwrap_or(0.5),
+        double_sided: material.double_sided(),
+    }
+}
+
+fn primitive_mode(mode: gltf::mesh::Mode) -> GLenum
+{
+    use gltf::mesh::Mode;
+
+    match mode {
+        Mode::Points => gl::POINTS,
+        Mode::Lines => gl::LINES,
+        Mode::LineLoop => gl::LINE_LOOP,
+        Mode::LineStrip => gl::LINE_STRIP,
+        Mode::Triangles => gl::TRIANGLES,
+        Mode::TriangleStrip => gl::TRIANGLE_STRIP,
+        Mode::TriangleFan => gl::TRIANGLE_FAN,
+    }
+}
+
+// The texture coordinate set a material samples. Vertices hold a single set, the base color one wins when the
+// material's textures disagree
+fn tex_coord_set(material: &PbrMaterial) -> u32
+{
+    let textures = [
+        material.base_color_texture,
+        material.metallic_roughness_texture,
+        material.normal_texture,
+        material.occlusion_texture,
+        material.emissive_texture,
+    ];
+    let set = textures.iter().flatten().next().map_or(0, |info| info.tex_coord);
+    if textures.iter().flatten().any(|info| info.tex_coord != set) {
+        println!("glTF material {} uses several texture coordinate sets, only TEXCOORD_{} is loaded",
+            material.name.as_deref().unwrap_or("(unnamed)"), set);
+    }
+    set
+}
+
+// reads one primitive into a CPU mesh. The accessor readers resolve sparse accessors, normalized integers are converted to floats
+fn read_primitive(primitive: &gltf::Primitive, buffers: &[Vec<u8>], materials: &[PbrMaterial]) -> Result<Mesh, String>
+{
+    let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(|data| &data[..]));
+
+    let positions = reader.read_positions().ok_or("glTF primitive without POSITION attribute")?;
+    let mut vertices: Vec<Vertex> = positions
+        .map(|p| Vertex::new(nalgebra_glm::vec3(p[0], p[1], p[2]), nalgebra_glm::vec2(0.0f32, 0.0)))
+        .collect();
+
+    let set = primitive.material().index().map_or(0, |index| tex_coord_set(&materials[index]));
+    if let Some(tex_coords) = reader.read_tex_coords(set) {
+        for (vertex, uv) in vertices.iter_mut().zip(tex_coords.into_f32()) {
+            vertex.tex_coords = nalgebra_glm::vec2(uv[0], uv[1]);
+        }
+    }
+    if let Some(normals) = reader.read_normals() {
+        for (vertex, n) in vertices.iter_mut().zip(normals) {
+            vertex.normal = nalgebra_glm::vec3(n[0], n[1], n[2]);
+        }
+    }
+    if let Some(tangents) = reader.read_tangents() {
+        for (vertex, t) in vertices.iter_mut().zip(tangents) {
+            vertex.tangent = nalgebra_glm::vec4(t[0], t[1], t[2], t[3]);
+        }
+    }
+    if let Some(colors) = reader.read_colors(0) {
+        for (vertex, c) in vertices.iter_mut().zip(colors.into_rgba_f32()) {
+            vertex.color = nalgebra_glm::vec4(c[0], c[1], c[2], c[3]);
+        }
+    }
+
+    let mode = primitive_mode(primitive.mode());
+    let mut mesh = match reader.read_indices() {
+        Some(indices) => Mesh::new(vertices, indices.into_u32().collect(), mode),
+        None => Mesh::from_vertices(vertices, mode),
+    };
+    mesh.material = primitive.material().index();
+
+    Ok(mesh)
+}
+
+fn texture_wrap(mode: gltf::texture::WrappingMode) -> TextureWrap
+{
+    use gltf::texture::WrappingMode;
+
+    match mode {
+        WrappingMode::ClampToEdge => TextureWrap::ClampToEdge,
+        WrappingMode::MirroredRepeat => TextureWrap::MirroredRepeat,
+        WrappingMode::Repeat => TextureWrap::Repeat,
+    }
+}
+
+// glTF images start at the top-left corner, the same corner the texture coordinates start from, so the rows are
+// uploaded as they are
+fn upload_texture(image: &DynamicImage, sampler: &gltf::texture::Sampler, srgb: bool) -> Texture2D
+{
+    use gltf::texture::{ MagFilter, MinFilter };
+
+    let (min_filter, mipmap_filter) = match sampler.min_filter() {
+        Some(MinFilter::Nearest) => (TextureFilter::Nearest, None),
+        Some(MinFilter::Linear) => (TextureFilter::Linear, None),
+        Some(MinFilter::NearestMipmapNearest) => (TextureFilter::Nearest, Some(TextureFilter::Nearest)),
+        Some(MinFilter::LinearMipmapNearest) => (TextureFilter::Linear, Some(TextureFilter::Nearest)),
+        Some(MinFilter::NearestMipmapLinear) => (TextureFilter::Nearest, Some(TextureFilter::Linear)),
+        Some(MinFilter::LinearMipmapLinear) | None => (TextureFilter::Linear, Some(TextureFilter::Linear)),
+    };
+    let mag_filter = match sampler.mag_filter() {
+        Some(MagFilter::Nearest) => TextureFilter::Nearest,
+        Some(MagFilter::Linear) | None => TextureFilter::Linear,
+    };
+
+    let options = TextureOptions {
+        wrap_s: texture_wrap(sampler.wrap_s()),
+        wrap_t: texture_wrap(sampler.wrap_t()),
+        min_filter,
+        mag_filter,
+        mipmap_filter,
+        flip_vertically: false,
+        flip_horizontally: false,
+        srgb,
+    };
+
+    Texture2D::from_image(image, options)
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    const SPARSE: &[u8] = include_bytes!("../tests/fixtures/sparse.gltf");
+    const MODES: &[u8] = include_bytes!("../tests/fixtures/modes.gltf");
+    const NODES: &[u8] = include_bytes!("../tests/fixtures/nodes.gltf");
+
+    fn read_primitives(data: &GltfData) -> Vec<Mesh>
+    {
+        let materials: Vec<PbrMaterial> = data.document.materials().map(|m| read_material(&m)).collect();
+        data.document.meshes().next().unwrap().primitives()
+            .map(|primitive| read_primitive(&primitive, &data.buffers, &materials).unwrap())
+            .collect()
+    }
+
+    fn position(mesh: &Mesh, index: usize) -> [f32; 3]
+    {
+        let p = mesh.vertices[index].position;
+        [p.x, p.y, p.z]
+    }
+
+    #[test]
+    fn sparse_accessors_override_their_base()
+    {
+        let data = GltfData::from_slice(SPARSE, None).unwrap();
+        let meshes = read_primitives(&data);
+        let mesh = &meshes[0];
+
+        assert_eq!(mesh.indices, vec![0, 1, 2, 0, 2, 3]);
+        assert_eq!(position(mesh, 0), [0.0, 0.0, 0.0]);
+        assert_eq!(position(mesh, 1), [5.0, 0.0, 0.0]);
+        assert_eq!(position(mesh, 2), [1.0, 1.0, 0.0]);
+        assert_eq!(position(mesh, 3), [0.0, 7.0, 0.0]);
+
+        // the normals have no buffer view, everything but the sparse value is zero
+        let normals: Vec<[f32; 3]> = mesh.vertices.iter().map(|v| [v.normal.x, v.normal.y, v.normal.z]).collect();
+        assert_eq!(normals, vec![[0.0; 3], [0.0; 3], [0.5, 0.25, 1.0], [0.0; 3]]);
+
+        assert_eq!(mesh.aabb.max, nalgebra_glm::vec3(5.0, 7.0, 0.0));
+    }
+
+    #[test]
+    fn primitive_modes_and_indices_are_kept()
+    {
+        let data = GltfData::from_slice(MODES, None).unwrap();
+        let meshes = read_primitives(&data);
+
+        let modes: Vec<GLenum> = meshes.iter().map(|mesh| mesh.mode).collect();
+        assert_eq!(modes, vec![gl::POINTS, gl::LINES, gl::TRIANGLE_STRIP, gl::TRIANGLE_FAN]);
+
+        // without indices every vertex is drawn once, u8 indices are widened
+        assert_eq!(meshes[0].indices, vec![0, 1, 2, 3, 4]);
+        assert_eq!(meshes[1].indices, vec![0, 1, 1, 2, 2, 3, 3, 0]);
+        assert!(meshes[1].triangles().is_empty());
+        assert_eq!(meshes[2].triangles().len(), 3);
+        assert_eq!(meshes[3].triangles(), vec![[0, 1, 2], [0, 2, 3], [0, 3, 4]]);
+        assert_eq!(meshes[2].material, Some(0));
+        assert_eq!(meshes[3].material, None);
+    }
+
+    #[test]
+    fn materials_select_their_texture_coordinate_set()
+    {
+        let data = GltfData::from_slice(MODES, None).unwrap();
+        let meshes = read_primitives(&data);
+
+        // the material samples TEXCOORD_1, the primitive without material falls back to TEXCOORD_0
+        let uv = |mesh: &Mesh, index: usize| [mesh.vertices[index].tex_coords.x, mesh.vertices[index].tex_coords.y];
+        assert_eq!(uv(&meshes[2], 2), [1.0, 1.0]);
+        assert_eq!(uv(&meshes[2], 4), [0.5, 0.5]);
+        assert_eq!(uv(&meshes[3], 2), [0.0, 0.0]);
+    }
+
+    #[test]
+    fn materials_and_embedded_images_are_read()
+    {
+        let data = GltfData::from_slice(MODES, None).unwrap();
+        let material = read_material(&data.document.materials().next().unwrap());
+
+        assert_eq!(material.name.as_deref(), Some("cutout"));
+        assert_eq!(material.base_color_factor, nalgebra_glm::vec4(1.0, 0.5, 0.25, 1.0));
+        assert_eq!(material.base_color_texture.map(|info| (info.texture, info.tex_coord)), Some((0, 1)));
+        assert!(material.metallic_roughness_texture.is_none());
+        assert_eq!(material.metallic_factor, 0.0);
+        assert_eq!(material.roughness_factor, 0.5);
+        assert_eq!(material.emissive_factor, nalgebra_glm::vec3(0.0, 1.0, 0.0));
+        assert_eq!(material.alpha_mode, AlphaMode::Mask);
+        assert_eq!(material.alpha_cutoff, 0.25);
+        assert!(material.double_sided);
+
+        let image = data.images[0].to_rgba8();
+        assert_eq!(image.dimensions(), (2, 1));
+        assert_eq!(image.get_pixel(0, 0).0, [255, 0, 0, 255]);
+        assert_eq!(image.get_pixel(1, 0).0, [0, 0, 255, 0]);
+    }
+
+    #[test]
+    fn node_transforms_follow_the_default_scene()
+    {
+        let data = GltfData::from_slice(NODES, None).unwrap();
+        let nodes = read_nodes(&data.document);
+        let roots = scene_roots(&data.document);
+        assert_eq!(roots, vec![0]);
+        assert_eq!(nodes[0].children, vec![1]);
+        assert_eq!(nodes[2].name.as_deref(), Some("leaf"));
+
+        let world = world_transforms(&nodes, &roots, &Mat4::identity());
+        let origin = |m: &Mat4| m * nalgebra_glm::vec4(0.0, 0.0, 0.0, 1.0);
+        assert!(nalgebra_glm::distance(&origin(&world[0]).xyz(), &nalgebra_glm::vec3(1.0, 2.0, 3.0)) < 1e-5);
+        // translated along z, scaled by 2 and turned onto +x
+        assert!(nalgebra_glm::distance(&origin(&world[2]).xyz(), &nalgebra_glm::vec3(11.0, 2.0, 3.0)) < 1e-5);
+        // node 3 is only part of the other scene
+        assert_eq!(world[3], Mat4::identity());
+
+        // the model matrix is applied on top
+        let moved = world_transforms(&nodes, &roots, &nalgebra_glm::translation(&nalgebra_glm::vec3(0.0, -2.0, 0.0)));
+        assert!(nalgebra_glm::distance(&origin(&moved[2]).xyz(), &nalgebra_glm::vec3(11.0, 0.0, 3.0)) < 1e-5);
+    }
+
+    #[test]
+    fn external_files_need_a_directory()
+    {
+        assert_eq!(read_uri("data:application/octet-stream;base64,AAEC", None), Ok(vec![0, 1, 2]));
+        assert!(read_uri("buffer.bin", None).is_err());
+        assert!(read_uri("missing%20file.bin", Some(Path::new("/nonexistent"))).unwrap_err().contains("missing file.bin"));
+    }
+}
@@ -0,0 +1,223 @@
+use image::RgbaImage;
+
+use std::path::Path;
+
+use super::assets::AssetManager;
+use super::async_loader::AsyncLoader;
+use super::camera::Camera;
+use super::framebuffer::{ AttachmentStorage, Framebuffer };
+use super::headless::HeadlessContext;
+use super::scene::{ Scene, SceneOptions };
+
+// A frame compared against a reference image. The scene is the default one (the ten textured cubes), only the
+// camera and the time change
+pub struct GoldenScene
+{
+    pub name: &'static str,
+    pub width: u32,
+    pub height: u32,
+    // seconds, drives the cube rotation
+    pub time: f32,
+    pub camera_position: [f32; 3],
+}
+
+pub const GOLDEN_SCENES: [GoldenScene; 3] = [
+    GoldenScene { name: "cubes", width: 320, height: 240, time: 1.0, camera_position: [0.0, 0.0, 3.0] },
+    GoldenScene { name: "cubes_start", width: 320, height: 240, time: 0.0, camera_position: [0.0, 0.0, 3.0] },
+    GoldenScene { name: "cubes_far", width: 320, height: 240, time: 2.5, camera_position: [0.0, 0.0, 8.0] },
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GoldenOptions
+{
+    // overwrite the references with the rendered frames instead of comparing
+    pub bless: bool,
+    // largest per channel difference still counted as equal
+    pub tolerance: u8,
+    // fraction of pixels allowed over the tolerance, rasterization rules differ slightly between drivers
+    pub max_mismatch: f32,
+}
+
+impl Default for GoldenOptions
+{
+    fn default() -> GoldenOptions
+    {
+        GoldenOptions { bless: false, tolerance: 2, max_mismatch: 0.001 }
+    }
+}
+
+pub struct ImageDiff
+{
+    // pixels with a channel differing by more than the tolerance
+    pub mismatched: usize,
+    pub max_difference: u8,
+    // the expected image dimmed to gray, with mismatched pixels in red
+    pub image: RgbaImage,
+}
+
+pub fn compare_images(actual: &RgbaImage, expected: &RgbaImage, tolerance: u8) -> Result<ImageDiff, String>
+{
+    if actual.dimensions() != expected.dimensions() {
+        return Err(format!("Image is {}x{}, the reference is {}x{}",
+            actual.width(), actual.height(), expected.width(), expected.height()));
+    }
+
+    let mut mismatched = 0;
+    let mut max_difference = 0;
+    let image = RgbaImage::from_fn(expected.width(), expected.height(), |x, y| {
+        let (a, e) = (actual.get_pixel(x, y), expected.get_pixel(x, y));
+        let difference = (0..4).map(|c| (a[c] as i32 - e[c] as i32).unsigned_abs() as u8).max().unwrap_or(0);
+        max_difference = max_difference.max(difference);
+
+        if difference > tolerance {
+            mismatched += 1;
+            image::Rgba([255, 0, 0, 255])
+        } else {
+            let gray = ((e[0] as u32 + e[1] as u32 + e[2] as u32) / 12) as u8;
+            image::Rgba([gray, gray, gray, 255])
+        }
+    });
+
+    Ok(ImageDiff { mismatched, max_difference, image })
+}
+
+// Renders the named scenes ("all" for every one) and compares them with `<golden_dir>/<name>.png`. On a mismatch
+// the frame and a diff image go to `<golden_dir>/out`. Returns whether every scene passed
+pub fn run(asset_dir: &Path, golden_dir: &Path, name: &str, options: &GoldenOptions) -> Result<bool, String>
+{
+    let scenes: Vec<&GoldenScene> = GOLDEN_SCENES.iter().filter(|scene| name == "all" || scene.name == name).collect();
+    if scenes.is_empty() {
+        let names: Vec<&str> = GOLDEN_SCENES.iter().map(|scene| scene.name).collect();
+        return Err(format!("Unknown golden scene {}, expected all or one of {}", name, names.join(", ")));
+    }
+
+    let context = HeadlessContext::create()?;
+    println!("Golden renderer: {}", context.renderer());
+
+    let mut assets = AssetManager::new();
+    let mut loader = AsyncLoader::new(0);
+    let scene = Scene::load(&mut assets, &mut loader, asset_dir, &SceneOptions::default())?;
+    loader.finish_all(&mut assets);
+
+    unsafe { gl::Enable(gl::DEPTH_TEST); }
+
+    let mut passed = true;
+    for golden in scenes {
+        let target = Framebuffer::with_color_and_depth(golden.width, golden.height, gl::RGBA8, AttachmentStorage::Renderbuffer)?;
+        target.bind();
+
+        let mut camera = Camera::new();
+        let [x, y, z] = golden.camera_position;
+        camera.position = nalgebra_glm::vec3(x, y, z);
+        scene.draw(&camera, golden.width as f32 / golden.height as f32, golden.time);
+        let frame = target.read_pixels(0);
+
+        let reference_path = golden_dir.join(format!("{}.png", golden.name));
+        if options.bless {
+            std::fs::create_dir_all(golden_dir).map_err(|e| format!("Failed to create {}: {}", golden_dir.display(), e))?;
+            frame.save(&reference_path).map_err(|e| format!("Failed to write {}: {}", reference_path.display(), e))?;
+            println!("golden {}: blessed {}", golden.name, reference_path.display());
+            continue;
+        }
+
+        let reference = match image::open(&reference_path) {
+            Ok(reference) => reference.to_rgba8(),
+            Err(e) => {
+                println!("golden {}: FAILED, no reference {} ({}), run with --bless to create it", golden.name, reference_path.display(), e);
+                passed = false;
+                continue;
+            },
+        };
+
+        let diff = match compare_images(&frame, &reference, options.tolerance) {
+            Ok(diff) => diff,
+            Err(e) => {
+                println!("golden {}: FAILED, {}", golden.name, e);
+                passed = false;
+                continue;
+            },
+        };
+
+        let allowed = (options.max_mismatch * (golden.width * golden.height) as f32) as usize;
+        if diff.mismatched <= allowed {
+            println!("golden {}: ok ({} pixels over the tolerance, max difference {})", golden.name, diff.mismatched, diff.max_difference);
+            continue;
+        }
+
+        passed = false;
+        let out_dir = golden_dir.join("out");
+        std::fs::create_dir_all(&out_dir).map_err(|e| format!("Failed to create {}: {}", out_dir.display(), e))?;
+        let actual_path = out_dir.join(format!("{}.png", golden.name));
+        let diff_path = out_dir.join(format!("{}.diff.png", golden.name));
+        frame.save(&actual_path).map_err(|e| format!("Failed to write {}: {}", actual_path.display(), e))?;
+        diff.image.save(&diff_path).map_err(|e| format!("Failed to write {}: {}", diff_path.display(), e))?;
+        println!("golden {}: FAILED, {} pixels differ by up to {} (allowed {}), see {}",
+            golden.name, diff.mismatched, diff.max_difference, allowed, diff_path.display());
+    }
+
+    Ok(passed)
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    // a horizontal gradient, so every pixel has its own value
+    fn gradient() -> RgbaImage
+    {
+        RgbaImage::from_fn(8, 4, |x, y| image::Rgba([x as u8 * 30, y as u8 * 60, 120, 255]))
+    }
+
+    #[test]
+    fn identical_images_match()
+    {
+        let diff = compare_images(&gradient(), &gradient(), 0).unwrap();
+        assert_eq!((diff.mismatched, diff.max_difference), (0, 0));
+        assert_eq!(diff.image.dimensions(), (8, 4));
+
+        // the diff shows the reference dimmed to gray, (210 + 180 + 120) / 12
+        assert_eq!(diff.image.get_pixel(7, 3), &image::Rgba([42, 42, 42, 255]));
+    }
+
+    #[test]
+    fn differences_within_the_tolerance_match()
+    {
+        let mut actual = gradient();
+        for pixel in actual.pixels_mut() {
+            pixel[2] += 2;
+        }
+        actual.get_pixel_mut(3, 1)[3] = 253;
+
+        let diff = compare_images(&actual, &gradient(), 2).unwrap();
+        assert_eq!((diff.mismatched, diff.max_difference), (0, 2));
+        assert!(diff.image.pixels().all(|pixel| pixel[0] == pixel[1] && pixel[1] == pixel[2]));
+    }
+
+    #[test]
+    fn mismatches_are_counted_and_marked_red()
+    {
+        let mut actual = gradient();
+        actual.put_pixel(0, 0, image::Rgba([3, 0, 120, 255]));
+        actual.put_pixel(5, 2, image::Rgba([150, 120, 120, 0]));
+        actual.put_pixel(7, 3, image::Rgba([210, 180, 123, 255]));
+
+        let diff = compare_images(&actual, &gradient(), 2).unwrap();
+        assert_eq!((diff.mismatched, diff.max_difference), (3, 255));
+
+        let red = image::Rgba([255, 0, 0, 255]);
+        let marked: Vec<(u32, u32)> = diff.image.enumerate_pixels()
+            .filter(|(_, _, pixel)| **pixel == red)
+            .map(|(x, y, _)| (x, y))
+            .collect();
+        assert_eq!(marked, [(0, 0), (5, 2), (7, 3)]);
+    }
+
+    #[test]
+    fn different_sizes_fail()
+    {
+        let smaller = RgbaImage::new(8, 3);
+        assert!(compare_images(&smaller, &gradient(), 255).is_err());
+        assert!(compare_images(&gradient(), &smaller, 255).is_err());
+    }
+}
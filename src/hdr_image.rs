@@ -0,0 +1,166 @@
+use gl::types::*;
+
+use std::fs::File;
+use std::io::{ BufReader, BufWriter };
+use std::path::Path;
+
+// Floating point image with 3 (RGB) or 4 (RGBA) channels, rows top to bottom
+#[derive(Clone, Debug, PartialEq)]
+pub struct HdrImage
+{
+    pub width: u32,
+    pub height: u32,
+    pub channels: usize,
+    pub pixels: Vec<f32>,
+}
+
+impl HdrImage
+{
+    pub fn new(width: u32, height: u32, channels: usize) -> HdrImage
+    {
+        HdrImage { width, height, channels, pixels: vec![0.0; width as usize * height as usize * channels] }
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> &[f32]
+    {
+        let offset = (y as usize * self.width as usize + x as usize) * self.channels;
+        &self.pixels[offset..offset + self.channels]
+    }
+
+    // (r, g, b, a) with alpha 1 for RGB images
+    fn rgba(&self, x: u32, y: u32) -> (f32, f32, f32, f32)
+    {
+        let p = self.pixel(x, y);
+        (p[0], p[1], p[2], if self.channels == 4 { p[3] } else { 1.0 })
+    }
+
+    pub fn flip_vertically(&mut self)
+    {
+        let row_size = self.width as usize * self.channels;
+        let height = self.height as usize;
+        for y in 0..height / 2 {
+            let (top, bottom) = self.pixels.split_at_mut((height - 1 - y) * row_size);
+            top[y * row_size..(y + 1) * row_size].swap_with_slice(&mut bottom[..row_size]);
+        }
+    }
+
+    pub fn flip_horizontally(&mut self)
+    {
+        let channels = self.channels;
+        let width = self.width as usize;
+        for row in self.pixels.chunks_exact_mut(width * channels) {
+            for x in 0..width / 2 {
+                let (left, right) = row.split_at_mut((width - 1 - x) * channels);
+                left[x * channels..(x + 1) * channels].swap_with_slice(&mut right[..channels]);
+            }
+        }
+    }
+}
+
+// Radiance RGBE (.hdr)
+pub fn load_hdr(path: &Path) -> Result<HdrImage, String>
+{
+    let file = File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let decoder = image::hdr::HdrDecoder::new(BufReader::new(file))
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+
+    let metadata = decoder.metadata();
+    let pixels = decoder.read_image_hdr().map_err(|e| format!("Failed to decode {}: {}", path.display(), e))?;
+
+    Ok(HdrImage {
+        width: metadata.width,
+        height: metadata.height,
+        channels: 3,
+        pixels: pixels.iter().flat_map(|p| p.0.iter().cloned()).collect(),
+    })
+}
+
+// OpenEXR (.exr), the first layer with RGB(A) channels. Missing alpha is read as 1
+pub fn load_exr(path: &Path) -> Result<HdrImage, String>
+{
+    let image = exr::prelude::read_first_rgba_layer_from_file(
+        path,
+        |resolution, _| HdrImage::new(resolution.width() as u32, resolution.height() as u32, 4),
+        |image: &mut HdrImage, position, (r, g, b, a): (f32, f32, f32, f32)| {
+            let offset = (position.y() * image.width as usize + position.x()) * 4;
+            image.pixels[offset..offset + 4].copy_from_slice(&[r, g, b, a]);
+        }
+    ).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+
+    Ok(image.layer_data.channel_data.pixels)
+}
+
+// .hdr or .exr by extension
+pub fn load_float_image(path: &Path) -> Result<HdrImage, String>
+{
+    match extension(path).as_deref() {
+        Some("hdr") => load_hdr(path),
+        Some("exr") => load_exr(path),
+        _ => Err(format!("Unsupported floating point image format: {}", path.display())),
+    }
+}
+
+pub fn is_float_image_path(path: &Path) -> bool
+{
+    matches!(extension(path).as_deref(), Some("hdr") | Some("exr"))
+}
+
+fn extension(path: &Path) -> Option<String>
+{
+    path.extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase())
+}
+
+// Radiance files only store RGB, alpha is dropped
+pub fn save_hdr(image: &HdrImage, path: &Path) -> Result<(), String>
+{
+    let file = File::create(path).map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+    let pixels: Vec<image::Rgb<f32>> = (0..image.height)
+        .flat_map(|y| (0..image.width).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            let (r, g, b, _) = image.rgba(x, y);
+            image::Rgb([r, g, b])
+        })
+        .collect();
+
+    image::hdr::HdrEncoder::new(BufWriter::new(file))
+        .encode(&pixels, image.width as usize, image.height as usize)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+// writes 32 bit float channels, RGBA when the image has alpha
+pub fn save_exr(image: &HdrImage, path: &Path) -> Result<(), String>
+{
+    let (width, height) = (image.width as usize, image.height as usize);
+    let result = if image.channels == 4 {
+        exr::prelude::write_rgba_file(path, width, height, |x, y| image.rgba(x as u32, y as u32))
+    } else {
+        exr::prelude::write_rgb_file(path, width, height, |x, y| {
+            let (r, g, b, _) = image.rgba(x as u32, y as u32);
+            (r, g, b)
+        })
+    };
+    result.map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+pub fn save_float_image(image: &HdrImage, path: &Path) -> Result<(), String>
+{
+    match extension(path).as_deref() {
+        Some("hdr") => save_hdr(image, path),
+        Some("exr") => save_exr(image, path),
+        _ => Err(format!("Unsupported floating point image format: {}", path.display())),
+    }
+}
+
+// reads a rectangle of the bound read framebuffer as RGBA floats, e.g. to dump a float render target to an .exr
+pub fn read_framebuffer(x: i32, y: i32, width: u32, height: u32) -> HdrImage
+{
+    let mut image = HdrImage::new(width, height, 4);
+    unsafe {
+        gl::PixelStorei(gl::PACK_ALIGNMENT, 4);
+        gl::ReadPixels(x, y, width as GLsizei, height as GLsizei, gl::RGBA, gl::FLOAT,
+            image.pixels.as_mut_ptr() as *mut std::ffi::c_void);
+    }
+    // GL returns the bottom row first
+    image.flip_vertically();
+    image
+}
@@ -0,0 +1,98 @@
+use khronos_egl as egl;
+
+// EGL_MESA_platform_surfaceless, a display without any window system (works with llvmpipe on GPU-less machines)
+const PLATFORM_SURFACELESS_MESA: egl::Enum = 0x31DD;
+
+// OpenGL core context without a window or default framebuffer, current on the creating thread. Everything has to be
+// rendered into framebuffer objects. GL objects must be dropped before the context
+pub struct HeadlessContext
+{
+    egl: egl::DynamicInstance<egl::EGL1_5>,
+    display: egl::Display,
+    context: egl::Context,
+}
+
+impl HeadlessContext
+{
+    // loads libEGL at runtime, so machines without it can still run the windowed mode
+    pub fn new(major_version: i32, minor_version: i32) -> Result<HeadlessContext, String>
+    {
+        let egl = unsafe { egl::DynamicInstance::<egl::EGL1_5>::load_required() }
+            .map_err(|e| format!("Failed to load libEGL: {}", e))?;
+
+        let display = unsafe { egl.get_platform_display(PLATFORM_SURFACELESS_MESA, egl::DEFAULT_DISPLAY, &[egl::ATTRIB_NONE]) }
+            .map_err(|e| format!("Failed to get the surfaceless EGL display: {}", e))?;
+        egl.initialize(display).map_err(|e| format!("Failed to initialize EGL: {}", e))?;
+
+        let extensions = egl.query_string(Some(display), egl::EXTENSIONS)
+            .map(|extensions| extensions.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        if !extensions.split(' ').any(|extension| extension == "EGL_KHR_surfaceless_context") {
+            let _ = egl.terminate(display);
+            return Err("EGL_KHR_surfaceless_context is not supported".to_string());
+        }
+
+        let context = HeadlessContext::create_context(&egl, display, major_version, minor_version).inspect_err(|_| {
+            let _ = egl.terminate(display);
+        })?;
+
+        gl::load_with(|name| egl.get_proc_address(name).map_or(std::ptr::null(), |f| f as *const std::ffi::c_void));
+
+        Ok(HeadlessContext { egl, display, context })
+    }
+
+    // 4.5 like the window, older software renderers still run everything on 3.3
+    pub fn create() -> Result<HeadlessContext, String>
+    {
+        HeadlessContext::new(4, 5).or_else(|_| HeadlessContext::new(3, 3))
+    }
+
+    fn create_context(egl: &egl::DynamicInstance<egl::EGL1_5>, display: egl::Display, major_version: i32, minor_version: i32) -> Result<egl::Context, String>
+    {
+        egl.bind_api(egl::OPENGL_API).map_err(|e| format!("Failed to bind the OpenGL API: {}", e))?;
+
+        // the surface type defaults to windows, which the surfaceless platform has none of
+        let config_attributes = [egl::SURFACE_TYPE, egl::PBUFFER_BIT, egl::RENDERABLE_TYPE, egl::OPENGL_BIT, egl::NONE];
+        let config = egl.choose_first_config(display, &config_attributes)
+            .map_err(|e| format!("Failed to choose an EGL config: {}", e))?
+            .ok_or("No EGL config supports OpenGL")?;
+
+        let attributes = [
+            egl::CONTEXT_MAJOR_VERSION, major_version,
+            egl::CONTEXT_MINOR_VERSION, minor_version,
+            egl::CONTEXT_OPENGL_PROFILE_MASK, egl::CONTEXT_OPENGL_CORE_PROFILE_BIT,
+            egl::NONE,
+        ];
+        let context = egl.create_context(display, config, None, &attributes)
+            .map_err(|e| format!("Failed to create an OpenGL {}.{} context: {}", major_version, minor_version, e))?;
+
+        egl.make_current(display, None, None, Some(context)).map_err(|e| {
+            let _ = egl.destroy_context(display, context);
+            format!("Failed to make the EGL context current: {}", e)
+        })?;
+
+        Ok(context)
+    }
+
+    // GL_RENDERER of the context, e.g. "llvmpipe (LLVM 15.0.7, 256 bits)"
+    pub fn renderer(&self) -> String
+    {
+        unsafe {
+            let renderer = gl::GetString(gl::RENDERER);
+            if renderer.is_null() {
+                return String::new();
+            }
+            std::ffi::CStr::from_ptr(renderer as *const std::os::raw::c_char).to_string_lossy().into_owned()
+        }
+    }
+}
+
+impl Drop for HeadlessContext
+{
+    fn drop(&mut self)
+    {
+        let _ = self.egl.make_current(self.display, None, None, None);
+        let _ = self.egl.destroy_context(self.display, self.context);
+        let _ = self.egl.terminate(self.display);
+    }
+}
@@ -0,0 +1,206 @@
+use std::path::Path;
+
+use super::bcn::BlockFormat;
+use super::compressed_texture::{ CompressedImage, MipLevel };
+
+const IDENTIFIER: [u8; 12] = [0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A];
+const HEADER_SIZE: usize = 80;
+const LEVEL_INDEX_SIZE: usize = 24;
+
+fn read_u32(data: &[u8], offset: usize) -> u32
+{
+    u32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]])
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64
+{
+    read_u32(data, offset) as u64 | (read_u32(data, offset + 4) as u64) << 32
+}
+
+// (format, srgb) of a VkFormat
+fn vk_format(format: u32) -> Option<(BlockFormat, bool)>
+{
+    Some(match format {
+        131 => (BlockFormat::Bc1 { alpha: false }, false),
+        132 => (BlockFormat::Bc1 { alpha: false }, true),
+        133 => (BlockFormat::Bc1 { alpha: true }, false),
+        134 => (BlockFormat::Bc1 { alpha: true }, true),
+        135 => (BlockFormat::Bc2, false),
+        136 => (BlockFormat::Bc2, true),
+        137 => (BlockFormat::Bc3, false),
+        138 => (BlockFormat::Bc3, true),
+        139 => (BlockFormat::Bc4 { signed: false }, false),
+        140 => (BlockFormat::Bc4 { signed: true }, false),
+        141 => (BlockFormat::Bc5 { signed: false }, false),
+        142 => (BlockFormat::Bc5 { signed: true }, false),
+        143 => (BlockFormat::Bc6h { signed: false }, false),
+        144 => (BlockFormat::Bc6h { signed: true }, false),
+        145 => (BlockFormat::Bc7, false),
+        146 => (BlockFormat::Bc7, true),
+        _ => return None,
+    })
+}
+
+// Parses a KTX2 file holding BC1-BC7 blocks, with mip chains, array layers and cube maps. Supercompressed files
+// (Basis Universal, zstd) are rejected
+pub fn parse_ktx2(data: &[u8]) -> Result<CompressedImage, String>
+{
+    if data.len() < HEADER_SIZE || data[0..12] != IDENTIFIER {
+        return Err("Not a KTX2 file".to_string());
+    }
+
+    let format_id = read_u32(data, 12);
+    let width = read_u32(data, 20);
+    let height = read_u32(data, 24);
+    let depth = read_u32(data, 28);
+    let layers = read_u32(data, 32).max(1);
+    let faces = read_u32(data, 36);
+    // 0 asks the loader to generate the mips, only the base level is stored
+    let level_count = read_u32(data, 40).max(1);
+    let supercompression = read_u32(data, 44);
+
+    if supercompression != 0 {
+        return Err(format!("Supercompressed KTX2 (scheme {}) is not supported", supercompression));
+    }
+    if depth > 1 {
+        return Err("3D KTX2 textures are not supported".to_string());
+    }
+    if faces != 1 && faces != 6 {
+        return Err(format!("Invalid KTX2 face count {}", faces));
+    }
+    if width == 0 || height == 0 {
+        return Err("KTX2 has an empty size".to_string());
+    }
+    // the chain ends at 1x1, more levels would shift the size by 32 bits or more
+    let max_levels = 32 - width.max(height).leading_zeros();
+    if level_count > max_levels {
+        return Err(format!("KTX2 has {} mip levels, a {}x{} image has at most {}", level_count, width, height, max_levels));
+    }
+    let image_count = layers.checked_mul(faces).ok_or(format!("KTX2 layer count {} is too large", layers))?;
+    let (format, srgb) = vk_format(format_id).ok_or(format!("Unsupported KTX2 VkFormat {}", format_id))?;
+
+    let mut levels = Vec::new();
+    for level in 0..level_count {
+        let index = HEADER_SIZE + level as usize * LEVEL_INDEX_SIZE;
+        if data.len() < index + LEVEL_INDEX_SIZE {
+            return Err("KTX2 level index truncated".to_string());
+        }
+        let byte_offset = read_u64(data, index) as usize;
+        let byte_length = read_u64(data, index + 8) as usize;
+
+        let level_width = (width >> level).max(1);
+        let level_height = (height >> level).max(1);
+        let image_size = format.image_size(level_width, level_height);
+
+        if image_size.checked_mul(image_count as usize).is_none_or(|size| byte_length < size) {
+            return Err(format!("KTX2 level {} is too small", level));
+        }
+        let level_data = byte_offset.checked_add(byte_length)
+            .and_then(|end| data.get(byte_offset..end))
+            .ok_or(format!("KTX2 level {} is out of bounds", level))?;
+
+        // every level stores the faces of each layer one after another
+        let images = level_data.chunks_exact(image_size)
+            .take(image_count as usize)
+            .map(|image| image.to_vec())
+            .collect();
+
+        levels.push(MipLevel { width: level_width, height: level_height, images });
+    }
+
+    Ok(CompressedImage { format, srgb, width, height, layers, faces, levels })
+}
+
+pub fn load_ktx2(path: &Path) -> Result<CompressedImage, String>
+{
+    let data = std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    parse_ktx2(&data).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use super::super::test_support::{ assert_solid, with_u32 };
+
+    const BC1_MIPS: &[u8] = include_bytes!("../tests/fixtures/bc1_mips.ktx2");
+    const BC3_ARRAY: &[u8] = include_bytes!("../tests/fixtures/bc3_array.ktx2");
+    const BC7_CUBE: &[u8] = include_bytes!("../tests/fixtures/bc7_cube.ktx2");
+
+    #[test]
+    fn bc1_mip_chain()
+    {
+        let image = parse_ktx2(BC1_MIPS).unwrap();
+        assert_eq!((image.format, image.srgb), (BlockFormat::Bc1 { alpha: false }, false));
+        assert_eq!((image.width, image.height, image.layers, image.faces), (8, 8, 1, 1));
+
+        let sizes: Vec<(u32, u32)> = image.levels.iter().map(|level| (level.width, level.height)).collect();
+        assert_eq!(sizes, [(8, 8), (4, 4), (2, 2), (1, 1)]);
+
+        let colors = [[255, 0, 0, 255], [0, 255, 0, 255], [0, 0, 255, 255], [255, 255, 255, 255]];
+        for (level, color) in colors.iter().enumerate() {
+            assert_eq!(image.levels[level].images.len(), 1);
+            assert_solid(&image, level, 0, *color);
+        }
+    }
+
+    #[test]
+    fn bc3_array()
+    {
+        let image = parse_ktx2(BC3_ARRAY).unwrap();
+        assert_eq!(image.format, BlockFormat::Bc3);
+        assert!(image.is_array() && !image.is_cubemap());
+        assert_eq!((image.layers, image.levels.len(), image.levels[0].images.len()), (3, 1, 3));
+
+        assert_solid(&image, 0, 0, [255, 0, 0, 0]);
+        assert_solid(&image, 0, 1, [0, 255, 0, 128]);
+        assert_solid(&image, 0, 2, [0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn bc7_cube_map()
+    {
+        let image = parse_ktx2(BC7_CUBE).unwrap();
+        assert_eq!(image.format, BlockFormat::Bc7);
+        assert!(image.is_cubemap() && !image.is_array());
+        assert_eq!(image.levels.len(), 3);
+
+        let faces = [[255, 1, 1], [1, 255, 1], [1, 1, 255], [255, 255, 1], [1, 255, 255], [255, 1, 255]];
+        for (level, alpha) in [255, 191, 127].iter().enumerate() {
+            assert_eq!(image.levels[level].images.len(), 6);
+            for (face, [r, g, b]) in faces.iter().enumerate() {
+                assert_solid(&image, level, face, [*r, *g, *b, *alpha]);
+            }
+        }
+    }
+
+    #[test]
+    fn truncated_files_fail()
+    {
+        for data in [BC1_MIPS, BC3_ARRAY, BC7_CUBE].iter() {
+            for length in 0..data.len() {
+                assert!(parse_ktx2(&data[..length]).is_err(), "{} of {} bytes parsed", length, data.len());
+            }
+        }
+    }
+
+    #[test]
+    fn oversized_headers_fail()
+    {
+        // an 8x8 image has 4 levels at most
+        assert!(parse_ktx2(&with_u32(BC1_MIPS, 40, 5)).is_err());
+        assert!(parse_ktx2(&with_u32(BC1_MIPS, 40, u32::MAX)).is_err());
+
+        // sizes no file can hold, with and without a full mip chain
+        let huge = with_u32(&with_u32(BC1_MIPS, 20, u32::MAX), 24, u32::MAX);
+        assert!(parse_ktx2(&huge).is_err());
+        assert!(parse_ktx2(&with_u32(&huge, 40, 32)).is_err());
+
+        // layer counts whose image count overflows with the six cube faces, or that just don't fit the data
+        assert!(parse_ktx2(&with_u32(BC7_CUBE, 32, u32::MAX)).is_err());
+        assert!(parse_ktx2(&with_u32(BC3_ARRAY, 32, u32::MAX)).is_err());
+
+        // a level pointing past the end of the file
+        assert!(parse_ktx2(&with_u32(BC3_ARRAY, HEADER_SIZE, u32::MAX)).is_err());
+    }
+}
@@ -0,0 +1,143 @@
+use nalgebra_glm::Vec3;
+
+use super::async_loader::AsyncHandle;
+use super::shader::Shader;
+use super::texture::Texture2D;
+
+// size of the `lights` array in lit.fs, further lights are ignored
+pub const MAX_LIGHTS: usize = 16;
+
+// Falloff of point and spot lights, the light is divided by constant + linear * d + quadratic * d²
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Attenuation
+{
+    pub constant: f32,
+    pub linear: f32,
+    pub quadratic: f32,
+}
+
+impl Default for Attenuation
+{
+    fn default() -> Attenuation
+    {
+        Attenuation::for_range(50.0)
+    }
+}
+
+impl Attenuation
+{
+    // terms that leave only a few percent of the light at `range` world units
+    pub fn for_range(range: f32) -> Attenuation
+    {
+        let range = range.max(0.001);
+        Attenuation { constant: 1.0, linear: 4.5 / range, quadratic: 75.0 / (range * range) }
+    }
+}
+
+// `color` is linear and may exceed 1 for bright lights. Directions don't need to be normalized
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Light {
+    // infinitely far away like the sun, `direction` points from the light into the scene
+    Directional { direction: Vec3, color: Vec3 },
+    Point { position: Vec3, color: Vec3, attenuation: Attenuation },
+    // full brightness inside `inner_angle`, fading out towards `outer_angle`, both in degrees from the axis
+    Spot { position: Vec3, direction: Vec3, color: Vec3, attenuation: Attenuation, inner_angle: f32, outer_angle: f32 },
+}
+
+impl Light
+{
+    // value of `lights[i].type` in lit.fs
+    fn to_uniform(self) -> i32
+    {
+        match self {
+            Light::Directional { .. } => 0,
+            Light::Point { .. } => 1,
+            Light::Spot { .. } => 2,
+        }
+    }
+
+    // sets `lights[index]` of lit.fs, the shader must be in use
+    fn upload(&self, shader: &Shader, index: usize)
+    {
+        let name = |field: &str| format!("lights[{}].{}", index, field);
+        let zero = nalgebra_glm::vec3(0.0, 0.0, 0.0);
+        let none = Attenuation { constant: 1.0, linear: 0.0, quadratic: 0.0 };
+
+        // unused fields are zeroed, lit.fs only reads what the light type needs
+        let (position, direction, color, attenuation, cutoffs) = match *self {
+            Light::Directional { direction, color } => (zero, direction, color, none, (0.0, 0.0)),
+            Light::Point { position, color, attenuation } => (position, zero, color, attenuation, (0.0, 0.0)),
+            Light::Spot { position, direction, color, attenuation, inner_angle, outer_angle } => {
+                // the shader compares cosines, an outer cone narrower than the inner one would divide by zero
+                let outer = outer_angle.max(inner_angle + 0.01);
+                (position, direction, color, attenuation, (inner_angle.to_radians().cos(), outer.to_radians().cos()))
+            },
+        };
+
+        shader.set_int(&name("type"), self.to_uniform());
+        shader.set_vec3(&name("position"), &position);
+        shader.set_vec3(&name("direction"), &direction);
+        shader.set_vec3(&name("color"), &color);
+        shader.set_float(&name("constant"), attenuation.constant);
+        shader.set_float(&name("linear"), attenuation.linear);
+        shader.set_float(&name("quadratic"), attenuation.quadratic);
+        shader.set_float(&name("innerCutoff"), cutoffs.0);
+        shader.set_float(&name("outerCutoff"), cutoffs.1);
+    }
+}
+
+// The lights of a frame plus the ambient term, which keeps faces turned away from every light from going black
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lighting
+{
+    pub ambient: Vec3,
+    pub lights: Vec<Light>,
+}
+
+impl Default for Lighting
+{
+    fn default() -> Lighting
+    {
+        Lighting { ambient: nalgebra_glm::vec3(0.05, 0.05, 0.05), lights: Vec::new() }
+    }
+}
+
+impl Lighting
+{
+    // Sets the light uniforms of lit.fs and `viewPos`, the shader must be in use. Lights may move every frame, so
+    // this is meant to be called once per frame before drawing. Lights past the first MAX_LIGHTS are dropped
+    // silently, a warning would repeat every frame
+    pub fn upload(&self, shader: &Shader, view_position: &Vec3)
+    {
+        let count = self.lights.len().min(MAX_LIGHTS);
+        for (i, light) in self.lights.iter().take(count).enumerate() {
+            light.upload(shader, i);
+        }
+        shader.set_int("lightCount", count as i32);
+        shader.set_vec3("ambient", &self.ambient);
+        shader.set_vec3("viewPos", view_position);
+    }
+}
+
+// Surface of lit.fs. The diffuse map gives the color, the red channel of the specular map how strongly a texel
+// reflects highlights, and `shininess` how tight they are (the Blinn-Phong exponent)
+pub struct Material
+{
+    pub diffuse: AsyncHandle<Texture2D>,
+    pub specular: AsyncHandle<Texture2D>,
+    pub shininess: f32,
+}
+
+impl Material
+{
+    // binds the maps to units `first_unit` and `first_unit + 1` and sets the `material` uniforms, the shader must be
+    // in use
+    pub fn bind(&self, shader: &Shader, first_unit: u32)
+    {
+        self.diffuse.bind(first_unit);
+        self.specular.bind(first_unit + 1);
+        shader.set_int("material.diffuse", first_unit as i32);
+        shader.set_int("material.specular", first_unit as i32 + 1);
+        shader.set_float("material.shininess", self.shininess);
+    }
+}
@@ -6,14 +6,43 @@ use sdl2::keyboard::Keycode;
 
 use gl::types::*;
 
-use std::mem;
-use std::ptr;
-
 mod shader;
 mod utils;
 mod sandbox;
 mod vertex_shapes;
 mod camera;
+mod bounds;
+mod mesh;
+mod gltf_model;
+mod mesh_normals;
+mod mesh_optimizer;
+mod mesh_simplify;
+mod stl;
+mod ply;
+mod texture;
+mod assets;
+mod cubemap;
+mod skybox;
+mod bcn;
+mod dds;
+mod ktx2;
+mod compressed_texture;
+#[cfg(test)] mod test_support;
+mod hdr_image;
+mod atlas;
+mod async_loader;
+mod sampler;
+mod mipmap;
+mod scene;
+mod headless;
+mod golden;
+mod framebuffer;
+mod screenshot;
+mod recorder;
+mod postprocess;
+mod tonemap;
+mod bloom;
+mod lighting;
 
 fn print_gl_version_and_profile(gl_attributes: &sdl2::video::gl_attr::GLAttr)
 {
@@ -81,6 +110,56 @@ fn scroll_callback(camera: &mut camera::Camera ,zoom: &mut f32, xoffset: i32, yo
     camera.process_mouse_scroll(yoffset as f32);
 }
 
+// Renders the scene with an EGL surfaceless context into a `--size <width>x<height>` framebuffer (800x600 by
+// default) at `--time <seconds>` (0 by default) and saves it to `--output <path>` (headless.png by default).
+// `--post` passes apply like in the window
+fn run_headless(current_dir_path: &std::path::Path)
+{
+    let (width, height) = utils::arg_value("--size")
+        .map(|size| utils::parse_size(&size).expect("--size expects <width>x<height>"))
+        .unwrap_or((800, 600));
+    let time = utils::arg_value("--time").and_then(|value| value.parse().ok()).unwrap_or(0.0f32);
+    let output = utils::arg_value("--output").unwrap_or_else(|| "headless.png".to_string());
+
+    let context = headless::HeadlessContext::create().expect("Failed to create a headless OpenGL context");
+    println!("Headless renderer: {}", context.renderer());
+
+    let target = framebuffer::Framebuffer::with_color_and_depth(width, height, gl::RGBA8, framebuffer::AttachmentStorage::Renderbuffer)
+        .expect("Failed to create the offscreen framebuffer");
+    target.bind();
+    unsafe { gl::Enable(gl::DEPTH_TEST); }
+
+    let mut assets = assets::AssetManager::new();
+    let mut loader = async_loader::AsyncLoader::new(0);
+    let scene = scene::Scene::load(&mut assets, &mut loader, &current_dir_path.join("assets"), &scene::SceneOptions::from_args())
+        .expect("Failed to load scene");
+    // there is only one frame, it can't show placeholders
+    loader.finish_all(&mut assets);
+
+    let post_options = postprocess::PostProcessOptions::from_args();
+    let mut post_chain = if !post_options.is_enabled() {
+        None
+    } else {
+        Some(postprocess::PostProcessChain::from_options(&mut assets, &current_dir_path.join("assets"), width, height, &post_options)
+            .expect("Failed to create the post processing chain"))
+    };
+
+    let mut camera = camera::Camera::new();
+    camera.position = nalgebra_glm::vec3(0.0f32, 0.0, 3.0);
+    match post_chain.as_mut() {
+        Some(chain) => {
+            chain.begin();
+            scene.draw(&camera, width as f32 / height as f32, time);
+            // a single frame, auto exposure adapts to it fully
+            chain.apply(Some(&target), 0.0);
+        },
+        None => scene.draw(&camera, width as f32 / height as f32, time),
+    }
+
+    target.read_pixels(0).save(&output).expect("Failed to save the headless frame");
+    println!("Saved {}", output);
+}
+
 fn main()
 {
     //.unwrap().to_str().unwrap();
@@ -90,6 +169,30 @@ fn main()
     let current_dir = current_dir_path.to_str().unwrap();
     println!("Current dir: {}", current_dir);
 
+    // `--headless` renders a frame into an offscreen framebuffer without SDL, e.g. on CI machines without a GPU
+    if utils::has_flag("--headless") {
+        run_headless(&current_dir_path);
+        return;
+    }
+
+    // `--golden <name|all>` compares headless frames with the references in golden/, `--bless` updates them
+    if let Some(name) = utils::arg_value("--golden") {
+        let options = golden::GoldenOptions {
+            bless: utils::has_flag("--bless"),
+            tolerance: utils::arg_value("--tolerance").and_then(|value| value.parse().ok()).unwrap_or(2),
+            max_mismatch: utils::arg_value("--max-mismatch").and_then(|value| value.parse().ok()).unwrap_or(0.001),
+        };
+        let golden_dir = utils::arg_value("--golden-dir").map(std::path::PathBuf::from).unwrap_or_else(|| current_dir_path.join("golden"));
+        match golden::run(&current_dir_path.join("assets"), &golden_dir, &name, &options) {
+            Ok(true) => return,
+            Ok(false) => std::process::exit(1),
+            Err(e) => {
+                println!("{}", e);
+                std::process::exit(1);
+            },
+        }
+    }
+
     let width: u32 = 800;
     let height: u32 = 600;
 
@@ -105,6 +208,15 @@ fn main()
     set_gl_version_and_profile(&gl_attributes);
     // print_gl_version_and_profile(&gl_attributes);
 
+    // `--msaa <samples>` multisamples the window, or the HDR scene framebuffer when post processing is on. The
+    // window isn't multisampled then, the scene is drawn into the chain's framebuffers and never into it
+    let post_options = postprocess::PostProcessOptions::from_args();
+    let msaa_samples = utils::arg_value("--msaa").and_then(|value| value.parse::<u8>().ok()).unwrap_or(0);
+    if msaa_samples > 1 && !post_options.is_enabled() {
+        gl_attributes.set_multisample_buffers(1);
+        gl_attributes.set_multisample_samples(msaa_samples);
+    }
+
     for i in sdl2::video::drivers() 
     {
         println!("Video driver: {}",i);
@@ -141,148 +253,28 @@ fn main()
     
     let mut event_pump = sdl_context.event_pump().unwrap();
 
-    let mut shader = shader::Shader::new();
-    shader.create_program(current_dir_path.join("assets\\3.3.shader.vs").to_str().unwrap() , current_dir_path.join("assets\\3.3.shader.fs").to_str().unwrap() );
-
-    /* let vertices = [
-        // positions         // colors
-         0.5 as f32, -0.5, 0.0,  1.0, 0.0, 0.0,  // bottom right
-        -0.5, -0.5, 0.0,         0.0, 1.0, 0.0,  // bottom left
-         0.0,  0.5, 0.0,         0.0, 0.0, 1.0   // top 
-    ]; */
-
-    /* let vertices = [
-        // positions              // colors         // texture coords
-         0.5 as f32,  0.5, 0.0,   1.0, 0.0, 0.0,    1.0, 1.0, // top right
-         0.5,        -0.5, 0.0,   0.0, 1.0, 0.0,    1.0, 0.0, // bottom right
-        -0.5,        -0.5, 0.0,   0.0, 0.0, 1.0,    0.0, 0.0, // bottom left
-        -0.5,         0.5, 0.0,   1.0, 1.0, 0.0,    0.0, 1.0  // top left 
-    ]; */
-
-    let vertices = vertex_shapes::get_cube();
-
-    let cube_positions = [
-        nalgebra_glm::vec3( 0.0f32,  0.0,  0.0),
-        nalgebra_glm::vec3( 2.0f32,  5.0, -15.0),
-        nalgebra_glm::vec3(-1.5f32, -2.2, -2.5),
-        nalgebra_glm::vec3(-3.8f32, -2.0, -12.3),
-        nalgebra_glm::vec3( 2.4f32, -0.4, -3.5),
-        nalgebra_glm::vec3(-1.7f32,  3.0, -7.5),
-        nalgebra_glm::vec3( 1.3f32, -2.0, -2.5),
-        nalgebra_glm::vec3( 1.5f32,  2.0, -2.5),
-        nalgebra_glm::vec3( 1.5f32,  0.2, -1.5),
-        nalgebra_glm::vec3(-1.3f32,  1.0, -1.5)
-    ];
-
-    let indices = [
-        0 as u32, 1, 3, // first triangle
-        1,        2, 3  // second triangle
-    ];
-
-    let mut vao: u32 = 0;
-    let mut vbo: u32 = 0;
-    /* let mut EBO: u32 = 0; */
-
-    unsafe {
-        gl::GenVertexArrays(1, &mut vao);
-        gl::GenBuffers(1, &mut vbo);
-        /* gl::GenBuffers(1, &mut EBO); */
-
-        // bind the Vertex Array Object first, then bind and set vertex buffer(s), and then configure vertex attributes(s).
-        gl::BindVertexArray(vao);
-    
-        gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
-        gl::BufferData(gl::ARRAY_BUFFER, mem::size_of_val(&vertices) as isize, vertices.as_ptr() as *const std::ffi::c_void, gl::STATIC_DRAW);
-
-        /* gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, EBO);
-        gl::BufferData(gl::ELEMENT_ARRAY_BUFFER, mem::size_of_val(&indices) as isize, indices.as_ptr() as *const std::ffi::c_void, gl::STATIC_DRAW); */
-    
-        // position attribute
-        gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, 5 * mem::size_of::<f32>() as i32, ptr::null());
-        gl::EnableVertexAttribArray(0);
-        // color attribute
-        // gl::VertexAttribPointer(1, 3, gl::FLOAT, gl::FALSE, 8 * mem::size_of::<f32>() as i32, (3 * mem::size_of::<f32>() as i32) as *const std::ffi::c_void);
-        // gl::EnableVertexAttribArray(1);
-
-        gl::VertexAttribPointer(1, 2, gl::FLOAT, gl::FALSE, 5 * mem::size_of::<f32>() as i32, (3 * mem::size_of::<f32>()) as *const std::ffi::c_void);
-        gl::EnableVertexAttribArray(1);
-    
-        // You can unbind the VAO afterwards so other VAO calls won't accidentally modify this VAO, but this rarely happens. Modifying other
-        // VAOs requires a call to glBindVertexArray anyways so we generally don't unbind VAOs (nor VBOs) when it's not directly necessary.
-        // gl::BindVertexArray(0);
-    }
-
-    // Load and create Texture
-    let mut texture1: u32 = 0;
-    let mut texture2: u32 = 0;
-    {
-        unsafe {
-            gl::GenTextures(1, &mut texture1);
-            gl::BindTexture(gl::TEXTURE_2D, texture1); 
-            // set the texture wrapping parameters
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::REPEAT as i32);	// set texture wrapping to GL_REPEAT (default wrapping method)
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::REPEAT as i32);
-            // set texture filtering parameters
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
-        }
-
-        // load image, create texture and generate mipmaps
-        let data = image::open( current_dir_path.join("assets\\container.jpg") ).expect("Failed to load image");
-
-        let data_raw = data.into_rgb();
-
-        unsafe {
-            gl::TexImage2D(
-                gl::TEXTURE_2D,
-                0,
-                gl::RGB as i32,
-                data_raw.width() as i32,
-                data_raw.height() as i32,
-                0,
-                gl::RGB,
-                gl::UNSIGNED_BYTE,
-                data_raw.into_raw().as_ptr() as *const std::ffi::c_void
-            );
-            gl::GenerateMipmap(gl::TEXTURE_2D);
-        }
-
-        // texture 2
-        // ---------
-        unsafe {
-            gl::GenTextures(1, &mut texture2);
-            gl::BindTexture(gl::TEXTURE_2D, texture2);
-            // set the texture wrapping parameters
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::REPEAT as i32);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::REPEAT as i32);
-            // set texture filtering parameters
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
-        }
-        // load image, create texture and generate mipmaps
-        let data_t2 = image::open( current_dir_path.join("assets\\awesomeface.png") ).expect("Failed to load image");
-        let data_raw_t2 = data_t2.rotate180().into_rgba();
-
-        // note that the awesomeface.png has transparency and thus an alpha channel, so make sure to tell OpenGL the data type is of GL_RGBA
-        unsafe {
-            gl::TexImage2D(
-                    gl::TEXTURE_2D,
-                    0,
-                    gl::RGBA as i32,
-                    data_raw_t2.width() as i32,
-                    data_raw_t2.height() as i32,
-                    0,
-                    gl::RGBA,
-                    gl::UNSIGNED_BYTE,
-                    data_raw_t2.into_raw().as_ptr() as *const std::ffi::c_void
-                );
-            gl::GenerateMipmap(gl::TEXTURE_2D);
-        }
-    }
-
-    shader.use_shader();
-    shader.set_int("texture1", 0);
-    shader.set_int("texture2", 1);
+    // textures, shaders and meshes are loaded once per path and shared through handles
+    let mut assets = assets::AssetManager::new();
+    // the cube textures are decoded in the background, the cubes show a placeholder until then
+    let mut loader = async_loader::AsyncLoader::new(0);
+
+    // optional glTF scene (`--gltf <path>`, `--optimize`), skybox (`--skybox <path>`) and `--anisotropy <n>`
+    let scene = scene::Scene::load(&mut assets, &mut loader, &current_dir_path.join("assets"), &scene::SceneOptions::from_args())
+        .expect("Failed to load scene");
+    println!("Assets: {}", assets.stats());
+
+    // `--post <pass>,<pass>,...` renders the scene into an HDR framebuffer and runs the passes over it in order,
+    // `--lut <strip.png>` grades colors in the color_grading pass. `--hdr` or any tone mapping option (`--tonemap`,
+    // `--exposure`, `--auto-exposure`) tone maps the HDR scene first, F7 cycles the tone mapper and F8 toggles
+    // auto exposure. `--bloom` (`--bloom-threshold`, `--bloom-intensity`, ...) adds a glow to highlights before that.
+    // F5 cycles the MSAA sample count (on and off for the window without post processing), F6 toggles `--fxaa`
+    let mut post_chain = if !post_options.is_enabled() {
+        None
+    } else {
+        let (drawable_width, drawable_height) = window.drawable_size();
+        Some(postprocess::PostProcessChain::from_options(&mut assets, &current_dir_path.join("assets"), drawable_width, drawable_height, &post_options)
+            .expect("Failed to create the post processing chain"))
+    };
 
     let mut camera = camera::Camera::new();
     camera.position = nalgebra_glm::vec3(0.0f32, 0.0, 3.0);
@@ -301,10 +293,35 @@ fn main()
 
     let mut last_frame = 0.0f32; // Time of last frame
     let mut first_mouse = true;
-    
+    // F9 writes the HDR scene of the next frame to framebuffer.exr as floats, for debugging lighting values. Without
+    // post processing there is no HDR target and the window's [0, 1] colors are written instead
+    let mut dump_framebuffer = false;
+    // F12 saves the next frame to `--screenshot-dir <dir>` (screenshots/ by default) as `--screenshot-format png|jpg`
+    let mut take_screenshot = false;
+    let screenshot_dir = utils::arg_value("--screenshot-dir").map(std::path::PathBuf::from).unwrap_or_else(|| current_dir_path.join("screenshots"));
+    let screenshot_format = utils::arg_value("--screenshot-format").unwrap_or_else(|| "png".to_string());
+
+    // `--record <dir>` saves every frame as numbered PNGs, `--record-pipe <command>` streams raw RGB24 frames to an
+    // encoder instead, e.g. --record-pipe "ffmpeg -y -f rawvideo -pix_fmt rgb24 -s 800x600 -r 60 -i - demo.mp4".
+    // Time advances by 1 / `--record-fps` (60) per frame, `--record-frames <n>` stops after n frames
+    let recording_output = utils::arg_value("--record").map(|dir| recorder::RecordingOutput::Frames(dir.into()))
+        .or_else(|| utils::arg_value("--record-pipe").map(recorder::RecordingOutput::Pipe));
+    let mut recorder = recording_output.map(|output| {
+        let fps = utils::arg_value("--record-fps").and_then(|value| value.parse().ok()).unwrap_or(60.0f32);
+        let (drawable_width, drawable_height) = window.drawable_size();
+        // a recording should not start with placeholder textures
+        loader.finish_all(&mut assets);
+        recorder::Recorder::new(drawable_width, drawable_height, fps, output).expect("Failed to start recording")
+    });
+    let record_frames: Option<u64> = utils::arg_value("--record-frames").and_then(|value| value.parse().ok());
+
     'running: loop {
         let dur = std::time::Duration::from_secs(1);
-        let current_frame = timer.elapsed().expect("Time elapsed failed").as_secs_f32();
+        // recordings use simulated time so they play back at full speed however slow capturing is
+        let current_frame = match &recorder {
+            Some(recorder) => recorder.time(),
+            None => timer.elapsed().expect("Time elapsed failed").as_secs_f32(),
+        };
         let delta_time = current_frame - last_frame;
         last_frame = current_frame;
 
@@ -353,52 +370,133 @@ fn main()
                 Event::MouseWheel { x, y, ..} => {
                     scroll_callback(&mut camera, &mut fov, x, y);
                 },
+                Event::KeyDown { keycode: Some(Keycode::F9), .. } => {
+                    dump_framebuffer = true;
+                },
+                Event::KeyDown { keycode: Some(Keycode::F5), .. } => {
+                    match post_chain.as_mut() {
+                        Some(chain) => {
+                            let max_samples = framebuffer::Framebuffer::max_samples();
+                            // next larger count the driver supports, then off
+                            let samples = [2, 4, 8].iter().copied()
+                                .find(|&samples| samples > chain.samples() && samples <= max_samples)
+                                .unwrap_or(0);
+                            match chain.set_samples(samples) {
+                                Ok(()) => println!("MSAA: {}", if samples > 1 { format!("{}x", samples) } else { "off".to_string() }),
+                                Err(e) => println!("{}", e),
+                            }
+                        },
+                        // the window's sample count is fixed when it is created, it can only be switched off
+                        None => unsafe {
+                            if gl::IsEnabled(gl::MULTISAMPLE) == gl::TRUE {
+                                gl::Disable(gl::MULTISAMPLE);
+                                println!("MSAA: off");
+                            } else {
+                                gl::Enable(gl::MULTISAMPLE);
+                                println!("MSAA: {}", if msaa_samples > 1 { format!("{}x", msaa_samples) } else { "on, but the window has no samples (--msaa <samples>)".to_string() });
+                            }
+                        },
+                    }
+                },
+                Event::KeyDown { keycode: Some(Keycode::F6), .. } => {
+                    // FXAA runs in the post processing chain, created on first use
+                    if post_chain.is_none() {
+                        let (drawable_width, drawable_height) = window.drawable_size();
+                        match postprocess::PostProcessChain::from_options(&mut assets, &current_dir_path.join("assets"), drawable_width, drawable_height, &post_options) {
+                            Ok(chain) => post_chain = Some(chain),
+                            Err(e) => println!("{}", e),
+                        }
+                    }
+                    if let Some(chain) = post_chain.as_mut() {
+                        let enabled = !chain.fxaa();
+                        match chain.set_fxaa(&mut assets, &current_dir_path.join("assets"), enabled) {
+                            Ok(()) => println!("FXAA: {}", if enabled { "on" } else { "off" }),
+                            Err(e) => println!("{}", e),
+                        }
+                    }
+                },
+                Event::KeyDown { keycode: Some(Keycode::F7), .. } => {
+                    if let Some(tone_mapping) = post_chain.as_mut().and_then(|chain| chain.tone_mapping.as_mut()) {
+                        tone_mapping.options.operator = tone_mapping.options.operator.next();
+                        println!("Tone mapper: {}", tone_mapping.options.operator.name());
+                    }
+                },
+                Event::KeyDown { keycode: Some(Keycode::F8), .. } => {
+                    if let Some(tone_mapping) = post_chain.as_mut().and_then(|chain| chain.tone_mapping.as_mut()) {
+                        tone_mapping.options.auto_exposure = !tone_mapping.options.auto_exposure;
+                        println!("Auto exposure: {}", if tone_mapping.options.auto_exposure { "on" } else { "off" });
+                    }
+                },
+                Event::KeyDown { keycode: Some(Keycode::F12), .. } => {
+                    take_screenshot = true;
+                },
                 _ => {}
             }
         }
 
         if gl_context.is_current()
         {
-            unsafe {
-                gl::ClearColor(0.2, 0.3, 0.3, 1.0);
-                gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
-
-                // bind textures on corresponding texture units
-                gl::ActiveTexture(gl::TEXTURE0);
-                gl::BindTexture(gl::TEXTURE_2D, texture1);
-                gl::ActiveTexture(gl::TEXTURE1);
-                gl::BindTexture(gl::TEXTURE_2D, texture2);
+            // upload the textures decoded since the last frame, without stalling it for more than a few ms
+            loader.update(&mut assets, std::time::Duration::from_millis(4));
+            // drop the cache entries of assets released last frame, e.g. the old shaders after rebuilding the chain
+            assets.collect_garbage();
+
+            match post_chain.as_mut() {
+                Some(chain) => {
+                    let (drawable_width, drawable_height) = window.drawable_size();
+                    chain.resize(drawable_width, drawable_height).expect("Failed to resize the post processing buffers");
+                    chain.begin();
+                    scene.draw(&camera, width as f32 / height as f32, current_frame);
+                    chain.apply(None, delta_time);
+                },
+                None => scene.draw(&camera, width as f32 / height as f32, current_frame),
             }
 
-            shader.use_shader();
-
-            let projection = nalgebra_glm::perspective(utils::degree_to_radian( camera.zoom ), width as f32 / height as f32, 0.1f32, 100.0f32);
-            shader.set_mat4("projection", &projection);
-
-            
-
-            let mut direction = nalgebra_glm::vec3(0.0f32, 0.0, 0.0);
-            direction.x = f32::cos(utils::degree_to_radian(yaw) * f32::cos(utils::degree_to_radian(pitch)) );
-            direction.y = f32::sin(utils::degree_to_radian(pitch));
-            direction.z = f32::sin(utils::degree_to_radian(yaw)) * f32::cos(utils::degree_to_radian(pitch));
-            // camera_front = nalgebra_glm::normalize(&direction);
-
-            let view = camera.get_view_matrix();
-            // let view = nalgebra_glm::look_at(&camera_pos, &(camera_pos + camera_front), &camera_up);
-            shader.set_mat4("view", &view);
+            if dump_framebuffer {
+                let image = match post_chain.as_ref() {
+                    Some(chain) => chain.read_scene(),
+                    None => {
+                        println!("No post processing chain, only LDR data is available for framebuffer.exr");
+                        let (drawable_width, drawable_height) = window.drawable_size();
+                        hdr_image::read_framebuffer(0, 0, drawable_width, drawable_height)
+                    },
+                };
+                match hdr_image::save_float_image(&image, std::path::Path::new("framebuffer.exr")) {
+                    Ok(()) => println!("Saved framebuffer.exr"),
+                    Err(e) => println!("{}", e),
+                }
+                dump_framebuffer = false;
+            }
 
-            unsafe { gl::BindVertexArray(vao); }
-            for ( i, cube ) in cube_positions.iter().enumerate() {
-                let mut model = nalgebra_glm::translation(&cube);
-                
-                let angle = 20.0f32 * (i + 1) as f32;
-                let seconds = timer.elapsed().expect("Time elapsed failed").as_secs_f32() * utils::degree_to_radian(angle);
-                model = nalgebra_glm::rotate(&model, seconds, &nalgebra_glm::vec3(1.0f32, 0.3, 0.5));
-                shader.set_mat4("model", &model);
+            if take_screenshot {
+                let (drawable_width, drawable_height) = window.drawable_size();
+                match screenshot::take_screenshot(&screenshot_dir, &screenshot_format, drawable_width, drawable_height) {
+                    Ok(path) => println!("Saved {}", path.display()),
+                    Err(e) => println!("{}", e),
+                }
+                take_screenshot = false;
+            }
 
-                unsafe {
-                    // gl::DrawElements(gl::TRIANGLES, 6, gl::UNSIGNED_INT, ptr::null());
-                    gl::DrawArrays(gl::TRIANGLES, 0, 36);
+            if let Some(active) = recorder.as_mut() {
+                // the pixel buffers and the encoder are sized for the first frame, resizing the window ends the
+                // recording
+                let (drawable_width, drawable_height) = window.drawable_size();
+                let captured = if (drawable_width, drawable_height) != (active.width, active.height) {
+                    Err(format!("the window was resized from {}x{} to {}x{}", active.width, active.height, drawable_width, drawable_height))
+                } else {
+                    active.capture()
+                };
+
+                if let Err(e) = captured {
+                    println!("Recording stopped: {}", e);
+                    // still write the frames read back so far and wait for the writer
+                    match active.finish() {
+                        Ok(()) => println!("Recorded {} frames", active.frames),
+                        Err(e) => println!("Recording failed: {}", e),
+                    }
+                    recorder = None;
+                } else if record_frames.is_some_and(|frames| active.frames >= frames) {
+                    break 'running;
                 }
             }
         }
@@ -408,9 +506,11 @@ fn main()
         // std::thread::sleep(dur);
     }
 
-    unsafe {
-        gl::DeleteVertexArrays(1, &vao);
-        gl::DeleteBuffers(1, &vbo);
-        /* gl::DeleteBuffers(1, &EBO); */
+    if let Some(mut recorder) = recorder {
+        match recorder.finish() {
+            Ok(()) => println!("Recorded {} frames", recorder.frames),
+            Err(e) => println!("Recording failed: {}", e),
+        }
     }
+
 }
\ No newline at end of file
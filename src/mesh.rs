@@ -0,0 +1,248 @@
+use gl::types::*;
+use nalgebra_glm::{ Vec2, Vec3, Vec4 };
+
+use std::mem;
+use std::ptr;
+
+use super::bounds::{ Aabb, BoundingSphere };
+use super::shader::Shader;
+
+// Vertex layout shared by every mesh. Attribute locations: 0 position, 1 texture coords, 2 normal, 3 tangent, 4 color
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Vertex
+{
+    pub position: Vec3,
+    pub tex_coords: Vec2,
+    pub normal: Vec3,
+    // xyz is the tangent direction, w the bitangent sign (handedness)
+    pub tangent: Vec4,
+    pub color: Vec4,
+}
+
+impl Vertex
+{
+    pub fn new(position: Vec3, tex_coords: Vec2) -> Vertex
+    {
+        Vertex {
+            position,
+            tex_coords,
+            normal: nalgebra_glm::vec3(0.0f32, 0.0, 0.0),
+            tangent: nalgebra_glm::vec4(0.0f32, 0.0, 0.0, 1.0),
+            color: nalgebra_glm::vec4(1.0f32, 1.0, 1.0, 1.0),
+        }
+    }
+}
+
+// An indexed mesh. The CPU side (vertices/indices) is always available, the GPU side is only created by setup_mesh
+pub struct Mesh
+{
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+    // primitive mode used for drawing (gl::TRIANGLES, gl::LINES, gl::POINTS, ...)
+    pub mode: GLenum,
+    // index of the material in the owning model, if any
+    pub material: Option<usize>,
+    // object space bounds of the vertices, computed when the mesh is built
+    pub aabb: Aabb,
+    pub bounding_sphere: BoundingSphere,
+
+    pub vao: u32,
+    vbo: u32,
+    ebo: u32,
+}
+
+impl Mesh
+{
+    pub fn new(vertices: Vec<Vertex>, indices: Vec<u32>, mode: GLenum) -> Mesh
+    {
+        let mut mesh = Mesh {
+            vertices,
+            indices,
+            mode,
+            material: None,
+            aabb: Aabb::empty(),
+            bounding_sphere: BoundingSphere::empty(),
+            vao: 0,
+            vbo: 0,
+            ebo: 0,
+        };
+
+        mesh.recompute_bounds();
+
+        mesh
+    }
+
+    // must be called after the vertex positions are modified
+    pub fn recompute_bounds(&mut self)
+    {
+        let positions: Vec<nalgebra_glm::Vec3> = self.vertices.iter().map(|v| v.position).collect();
+        self.aabb = Aabb::from_points(&positions);
+        self.bounding_sphere = BoundingSphere::from_points(&positions);
+    }
+
+    // bounds of the mesh once placed in the world by `model`
+    pub fn world_bounds(&self, model: &nalgebra_glm::Mat4) -> (Aabb, BoundingSphere)
+    {
+        (self.aabb.transform(model), self.bounding_sphere.transform(model))
+    }
+
+    // builds a mesh without index buffer, every vertex is used once in order
+    pub fn from_vertices(vertices: Vec<Vertex>, mode: GLenum) -> Mesh
+    {
+        let indices = (0..vertices.len() as u32).collect();
+        Mesh::new(vertices, indices, mode)
+    }
+
+    // triangles of the mesh as vertex index triples, with strips and fans expanded. Empty for point and line meshes
+    pub fn triangles(&self) -> Vec<[usize; 3]>
+    {
+        let idx = |i: usize| self.indices[i] as usize;
+
+        match self.mode {
+            gl::TRIANGLES => {
+                self.indices.chunks_exact(3).map(|t| [t[0] as usize, t[1] as usize, t[2] as usize]).collect()
+            },
+            gl::TRIANGLE_STRIP => {
+                // every other triangle of a strip has its winding flipped
+                (2..self.indices.len()).map(|i| {
+                    if i % 2 == 0 { [idx(i - 2), idx(i - 1), idx(i)] } else { [idx(i - 1), idx(i - 2), idx(i)] }
+                }).collect()
+            },
+            gl::TRIANGLE_FAN => {
+                (2..self.indices.len()).map(|i| [idx(0), idx(i - 1), idx(i)]).collect()
+            },
+            _ => Vec::new(),
+        }
+    }
+
+    // replaces strips and fans by a plain triangle list
+    pub fn convert_to_triangle_list(&mut self)
+    {
+        if self.mode == gl::TRIANGLE_STRIP || self.mode == gl::TRIANGLE_FAN {
+            self.indices = self.triangles().iter().flatten().map(|&i| i as u32).collect();
+            self.mode = gl::TRIANGLES;
+        }
+    }
+
+    pub fn is_uploaded(&self) -> bool
+    {
+        self.vao != 0
+    }
+
+    // creates the vertex array, vertex buffer and element buffer for this mesh
+    pub fn setup_mesh(&mut self)
+    {
+        if self.is_uploaded() {
+            self.update_buffers();
+            return;
+        }
+
+        unsafe {
+            gl::GenVertexArrays(1, &mut self.vao);
+            gl::GenBuffers(1, &mut self.vbo);
+            gl::GenBuffers(1, &mut self.ebo);
+
+            gl::BindVertexArray(self.vao);
+        }
+
+        self.update_buffers();
+
+        let stride = mem::size_of::<Vertex>() as i32;
+        let float_size = mem::size_of::<f32>();
+
+        unsafe {
+            // vertex positions
+            gl::EnableVertexAttribArray(0);
+            gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, stride, ptr::null());
+            // vertex texture coords
+            gl::EnableVertexAttribArray(1);
+            gl::VertexAttribPointer(1, 2, gl::FLOAT, gl::FALSE, stride, (3 * float_size) as *const std::ffi::c_void);
+            // vertex normals
+            gl::EnableVertexAttribArray(2);
+            gl::VertexAttribPointer(2, 3, gl::FLOAT, gl::FALSE, stride, (5 * float_size) as *const std::ffi::c_void);
+            // vertex tangent
+            gl::EnableVertexAttribArray(3);
+            gl::VertexAttribPointer(3, 4, gl::FLOAT, gl::FALSE, stride, (8 * float_size) as *const std::ffi::c_void);
+            // vertex color
+            gl::EnableVertexAttribArray(4);
+            gl::VertexAttribPointer(4, 4, gl::FLOAT, gl::FALSE, stride, (12 * float_size) as *const std::ffi::c_void);
+
+            gl::BindVertexArray(0);
+        }
+    }
+
+    // re-uploads vertices and indices, used after the CPU data was modified
+    fn update_buffers(&self)
+    {
+        unsafe {
+            gl::BindVertexArray(self.vao);
+
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                (self.vertices.len() * mem::size_of::<Vertex>()) as isize,
+                self.vertices.as_ptr() as *const std::ffi::c_void,
+                gl::STATIC_DRAW
+            );
+
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.ebo);
+            gl::BufferData(
+                gl::ELEMENT_ARRAY_BUFFER,
+                (self.indices.len() * mem::size_of::<u32>()) as isize,
+                self.indices.as_ptr() as *const std::ffi::c_void,
+                gl::STATIC_DRAW
+            );
+        }
+    }
+
+    // draws the mesh with whatever textures are currently bound. The shader must already be in use
+    pub fn draw(&self, shader: &Shader)
+    {
+        unsafe {
+            gl::BindVertexArray(self.vao);
+            gl::DrawElements(self.mode, self.indices.len() as i32, gl::UNSIGNED_INT, ptr::null());
+            gl::BindVertexArray(0);
+        }
+    }
+
+    // draws `count` indices starting at `first_index`, e.g. one level of detail stored in a shared index buffer
+    pub fn draw_range(&self, shader: &Shader, first_index: usize, count: usize)
+    {
+        unsafe {
+            gl::BindVertexArray(self.vao);
+            gl::DrawElements(
+                self.mode,
+                count as i32,
+                gl::UNSIGNED_INT,
+                (first_index * mem::size_of::<u32>()) as *const std::ffi::c_void
+            );
+            gl::BindVertexArray(0);
+        }
+    }
+
+    pub fn delete_buffers(&mut self)
+    {
+        if !self.is_uploaded() {
+            return;
+        }
+
+        unsafe {
+            gl::DeleteVertexArrays(1, &self.vao);
+            gl::DeleteBuffers(1, &self.vbo);
+            gl::DeleteBuffers(1, &self.ebo);
+        }
+
+        self.vao = 0;
+        self.vbo = 0;
+        self.ebo = 0;
+    }
+}
+
+impl Drop for Mesh
+{
+    fn drop(&mut self)
+    {
+        self.delete_buffers();
+    }
+}
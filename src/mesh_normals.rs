@@ -0,0 +1,239 @@
+use nalgebra_glm::{ Vec3 };
+
+use std::collections::HashMap;
+
+use super::mesh::{ Mesh, Vertex };
+
+// angle of the triangle corner at `p0`
+fn corner_angle(p0: &Vec3, p1: &Vec3, p2: &Vec3) -> f32
+{
+    let e1 = p1 - p0;
+    let e2 = p2 - p0;
+    let len = nalgebra_glm::length(&e1) * nalgebra_glm::length(&e2);
+    if len <= f32::EPSILON {
+        return 0.0;
+    }
+    (nalgebra_glm::dot(&e1, &e2) / len).clamp(-1.0, 1.0).acos()
+}
+
+fn face_normal(p0: &Vec3, p1: &Vec3, p2: &Vec3) -> Vec3
+{
+    let n = nalgebra_glm::cross(&(p1 - p0), &(p2 - p0));
+    let len = nalgebra_glm::length(&n);
+    if len <= f32::EPSILON {
+        return nalgebra_glm::vec3(0.0f32, 0.0, 0.0);
+    }
+    n / len
+}
+
+fn normalize_or(v: &Vec3, fallback: Vec3) -> Vec3
+{
+    let len = nalgebra_glm::length(v);
+    if len <= f32::EPSILON { fallback } else { v / len }
+}
+
+// any unit vector perpendicular to `n`
+fn perpendicular(n: &Vec3) -> Vec3
+{
+    let axis = if n.x.abs() < 0.9 { nalgebra_glm::vec3(1.0f32, 0.0, 0.0) } else { nalgebra_glm::vec3(0.0f32, 1.0, 0.0) };
+    normalize_or(&nalgebra_glm::cross(n, &axis), nalgebra_glm::vec3(1.0f32, 0.0, 0.0))
+}
+
+// Gives every triangle its own three vertices carrying the face normal (faceted look).
+// The mesh is converted into an unindexed-style triangle list, so vertex count becomes 3 * triangle count.
+// Point and line meshes have no faces and are left untouched
+pub fn compute_flat_normals(mesh: &mut Mesh)
+{
+    if ![gl::TRIANGLES, gl::TRIANGLE_STRIP, gl::TRIANGLE_FAN].contains(&mesh.mode) {
+        return;
+    }
+
+    let mut vertices = Vec::new();
+
+    for [a, b, c] in mesh.triangles() {
+        let corners = [mesh.vertices[a], mesh.vertices[b], mesh.vertices[c]];
+        let normal = face_normal(&corners[0].position, &corners[1].position, &corners[2].position);
+        for corner in corners.iter() {
+            let mut vertex: Vertex = *corner;
+            vertex.normal = normal;
+            vertices.push(vertex);
+        }
+    }
+
+    mesh.indices = (0..vertices.len() as u32).collect();
+    mesh.vertices = vertices;
+    mesh.mode = gl::TRIANGLES;
+}
+
+// Computes angle-weighted vertex normals: each face contributes its normal weighted by the angle of the corner
+// touching the vertex, which makes the result independent of how the faces are triangulated.
+// Vertices sharing a position (e.g. split along a UV seam) get the same normal so seams stay invisible
+pub fn compute_smooth_normals(mesh: &mut Mesh)
+{
+    let mut position_groups: HashMap<[u32; 3], usize> = HashMap::new();
+    let group_of: Vec<usize> = mesh.vertices.iter().map(|v| {
+        // adding 0.0 turns -0.0 into 0.0, the two compare equal but have different bits
+        let key = [(v.position.x + 0.0).to_bits(), (v.position.y + 0.0).to_bits(), (v.position.z + 0.0).to_bits()];
+        let next = position_groups.len();
+        *position_groups.entry(key).or_insert(next)
+    }).collect();
+
+    let mut accumulated = vec![nalgebra_glm::vec3(0.0f32, 0.0, 0.0); position_groups.len()];
+
+    for [a, b, c] in mesh.triangles() {
+        let (p0, p1, p2) = (mesh.vertices[a].position, mesh.vertices[b].position, mesh.vertices[c].position);
+        let normal = face_normal(&p0, &p1, &p2);
+
+        accumulated[group_of[a]] += normal * corner_angle(&p0, &p1, &p2);
+        accumulated[group_of[b]] += normal * corner_angle(&p1, &p2, &p0);
+        accumulated[group_of[c]] += normal * corner_angle(&p2, &p0, &p1);
+    }
+
+    for (vertex, group) in mesh.vertices.iter_mut().zip(group_of) {
+        vertex.normal = normalize_or(&accumulated[group], nalgebra_glm::vec3(0.0f32, 1.0, 0.0));
+    }
+}
+
+// Computes per-vertex tangents following the MikkTSpace conventions: face tangents are projected onto the plane
+// of each vertex normal and accumulated with angle weights, and tangent.w stores the handedness so that
+// bitangent = tangent.w * cross(normal, tangent.xyz).
+// Requires normals and texture coordinates; vertices split along UV seams keep their own tangent frames
+pub fn compute_tangents(mesh: &mut Mesh)
+{
+    let zero = nalgebra_glm::vec3(0.0f32, 0.0, 0.0);
+    let mut tangents = vec![zero; mesh.vertices.len()];
+    let mut bitangents = vec![zero; mesh.vertices.len()];
+
+    for [a, b, c] in mesh.triangles() {
+        let corners = [a, b, c];
+        let v = [mesh.vertices[a], mesh.vertices[b], mesh.vertices[c]];
+
+        let e1 = v[1].position - v[0].position;
+        let e2 = v[2].position - v[0].position;
+        let duv1 = v[1].tex_coords - v[0].tex_coords;
+        let duv2 = v[2].tex_coords - v[0].tex_coords;
+
+        let det = duv1.x * duv2.y - duv2.x * duv1.y;
+        if det.abs() <= f32::EPSILON {
+            // degenerate texture mapping, the fallback below picks a frame for vertices that get nothing else
+            continue;
+        }
+        let r = 1.0 / det;
+        let face_tangent = (e1 * duv2.y - e2 * duv1.y) * r;
+        let face_bitangent = (e2 * duv1.x - e1 * duv2.x) * r;
+
+        for k in 0..3 {
+            let p0 = v[k].position;
+            let p1 = v[(k + 1) % 3].position;
+            let p2 = v[(k + 2) % 3].position;
+            let angle = corner_angle(&p0, &p1, &p2);
+
+            let n = v[k].normal;
+            let t = normalize_or(&(face_tangent - n * nalgebra_glm::dot(&n, &face_tangent)), zero);
+            let bt = normalize_or(&(face_bitangent - n * nalgebra_glm::dot(&n, &face_bitangent)), zero);
+
+            tangents[corners[k]] += t * angle;
+            bitangents[corners[k]] += bt * angle;
+        }
+    }
+
+    for (i, vertex) in mesh.vertices.iter_mut().enumerate() {
+        let n = vertex.normal;
+
+        // Gram-Schmidt orthogonalize against the normal
+        let t = tangents[i] - n * nalgebra_glm::dot(&n, &tangents[i]);
+        let t = if nalgebra_glm::length(&t) <= f32::EPSILON { perpendicular(&n) } else { nalgebra_glm::normalize(&t) };
+
+        let handedness = if nalgebra_glm::dot(&nalgebra_glm::cross(&n, &t), &bitangents[i]) < 0.0 { -1.0 } else { 1.0 };
+
+        vertex.tangent = nalgebra_glm::vec4(t.x, t.y, t.z, handedness);
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use super::super::vertex_shapes;
+
+    fn approx_eq(a: &Vec3, b: &Vec3) -> bool
+    {
+        nalgebra_glm::length(&(a - b)) < 1e-4
+    }
+
+    #[test]
+    fn cube_flat_normals_are_the_axes()
+    {
+        let mut mesh = vertex_shapes::get_cube_mesh();
+        compute_flat_normals(&mut mesh);
+
+        assert_eq!(mesh.vertices.len(), 36);
+        let axes = [
+            nalgebra_glm::vec3(1.0f32, 0.0, 0.0), nalgebra_glm::vec3(-1.0f32, 0.0, 0.0),
+            nalgebra_glm::vec3(0.0f32, 1.0, 0.0), nalgebra_glm::vec3(0.0f32, -1.0, 0.0),
+            nalgebra_glm::vec3(0.0f32, 0.0, 1.0), nalgebra_glm::vec3(0.0f32, 0.0, -1.0),
+        ];
+        for axis in axes.iter() {
+            let count = mesh.vertices.iter().filter(|v| approx_eq(&v.normal, axis)).count();
+            assert_eq!(count, 6, "{:?}", axis);
+        }
+        // outward facing, the normal points the same way as the face center
+        for triangle in mesh.vertices.chunks_exact(3) {
+            let center = (triangle[0].position + triangle[1].position + triangle[2].position) / 3.0;
+            assert!(nalgebra_glm::dot(&center, &triangle[0].normal) > 0.0);
+        }
+    }
+
+    #[test]
+    fn flat_normals_leave_points_untouched()
+    {
+        let vertices = vec![Vertex::new(nalgebra_glm::vec3(0.0f32, 0.0, 0.0), nalgebra_glm::vec2(0.0f32, 0.0)); 4];
+        let mut mesh = Mesh::from_vertices(vertices, gl::POINTS);
+        compute_flat_normals(&mut mesh);
+
+        assert_eq!(mesh.vertices.len(), 4);
+        assert_eq!(mesh.indices, vec![0, 1, 2, 3]);
+        assert_eq!(mesh.mode, gl::POINTS);
+    }
+
+    #[test]
+    fn sphere_smooth_normals_point_outwards()
+    {
+        let mut mesh = vertex_shapes::get_uv_sphere_mesh(2.0, 16, 8);
+        for vertex in mesh.vertices.iter_mut() {
+            vertex.normal = nalgebra_glm::vec3(0.0f32, 0.0, 0.0);
+        }
+        compute_smooth_normals(&mut mesh);
+
+        for vertex in &mesh.vertices {
+            let expected = nalgebra_glm::normalize(&vertex.position);
+            assert!(nalgebra_glm::dot(&vertex.normal, &expected) > 0.99, "{:?} at {:?}", vertex.normal, vertex.position);
+        }
+    }
+
+    #[test]
+    fn tangents_are_orthogonal_with_unit_handedness()
+    {
+        let mut sphere = vertex_shapes::get_uv_sphere_mesh(1.0, 16, 8);
+        let mut cube = vertex_shapes::get_cube_mesh();
+        compute_flat_normals(&mut cube);
+        let mut plane = vertex_shapes::get_plane_mesh(2.0, 4);
+
+        for mesh in [&mut sphere, &mut cube, &mut plane].iter_mut() {
+            compute_tangents(mesh);
+            for vertex in &mesh.vertices {
+                let tangent = nalgebra_glm::vec3(vertex.tangent.x, vertex.tangent.y, vertex.tangent.z);
+                assert!((nalgebra_glm::length(&tangent) - 1.0).abs() < 1e-4);
+                assert!(nalgebra_glm::dot(&tangent, &vertex.normal).abs() < 1e-4);
+                assert!(vertex.tangent.w == 1.0 || vertex.tangent.w == -1.0);
+            }
+        }
+
+        // the plane's u runs along +x, v along -z, so the tangent is +x and the frame right handed
+        for vertex in &plane.vertices {
+            let tangent = nalgebra_glm::vec3(vertex.tangent.x, vertex.tangent.y, vertex.tangent.z);
+            assert!(approx_eq(&tangent, &nalgebra_glm::vec3(1.0, 0.0, 0.0)));
+            assert_eq!(vertex.tangent.w, 1.0);
+        }
+    }
+}
@@ -0,0 +1,571 @@
+use nalgebra_glm::{ Vec3 };
+
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::fmt;
+
+use super::mesh::{ Mesh, Vertex };
+
+// Size of the FIFO cache simulated when measuring ACMR. 16 is a common estimate for current GPUs
+pub const ACMR_CACHE_SIZE: usize = 16;
+
+// Size of the LRU cache modelled by the Forsyth reordering
+const FORSYTH_CACHE_SIZE: usize = 32;
+
+// clusters shorter than this are never split by the overdraw optimizer
+const MIN_CLUSTER_SIZE: usize = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WeldMode {
+    // merge vertices closer than epsilon, keeping the attributes of the first one (destroys UV seams and hard edges)
+    Position,
+    // merge only vertices whose position and every other attribute are within epsilon
+    AllAttributes,
+}
+
+// Average cache miss ratio: transformed vertices per triangle for a FIFO post-transform cache of `cache_size` entries.
+// 3.0 is the worst case, around 0.5 - 0.7 is typical for well optimized meshes
+pub fn acmr(indices: &[u32], cache_size: usize) -> f32
+{
+    let triangles = indices.len() / 3;
+    if triangles == 0 {
+        return 0.0;
+    }
+
+    let mut cache: VecDeque<u32> = VecDeque::with_capacity(cache_size);
+    let mut misses = 0;
+
+    for &index in indices {
+        if !cache.contains(&index) {
+            misses += 1;
+            if cache.len() == cache_size {
+                cache.pop_front();
+            }
+            cache.push_back(index);
+        }
+    }
+
+    misses as f32 / triangles as f32
+}
+
+fn vertex_distance_within(a: &Vertex, b: &Vertex, epsilon: f32, mode: WeldMode) -> bool
+{
+    let close = |x: &[f32], y: &[f32]| x.iter().zip(y).all(|(x, y)| (x - y).abs() <= epsilon);
+
+    if !close(a.position.as_slice(), b.position.as_slice()) {
+        return false;
+    }
+
+    match mode {
+        WeldMode::Position => true,
+        WeldMode::AllAttributes => {
+            close(a.tex_coords.as_slice(), b.tex_coords.as_slice()) &&
+            close(a.normal.as_slice(), b.normal.as_slice()) &&
+            close(a.tangent.as_slice(), b.tangent.as_slice()) &&
+            close(a.color.as_slice(), b.color.as_slice())
+        }
+    }
+}
+
+// Merges duplicated vertices closer than `epsilon` and rewrites the indices. Degenerate triangles created by the
+// merge are removed. Returns the number of vertices removed
+pub fn weld_vertices(mesh: &mut Mesh, epsilon: f32, mode: WeldMode) -> usize
+{
+    let before = mesh.vertices.len();
+    let cell_size = epsilon.max(f32::EPSILON) * 2.0;
+    let cell_of = |p: &Vec3| {
+        [(p.x / cell_size).floor() as i64, (p.y / cell_size).floor() as i64, (p.z / cell_size).floor() as i64]
+    };
+
+    // spatial hash of the vertices kept so far, candidates are looked up in the neighbouring cells
+    let mut grid: HashMap<[i64; 3], Vec<u32>> = HashMap::new();
+    let mut kept: Vec<Vertex> = Vec::new();
+    let mut remap = vec![0u32; mesh.vertices.len()];
+
+    for (i, vertex) in mesh.vertices.iter().enumerate() {
+        let cell = cell_of(&vertex.position);
+
+        let mut found = None;
+        'search: for dx in -1..=1 {
+            for dy in -1..=1 {
+                for dz in -1..=1 {
+                    if let Some(candidates) = grid.get(&[cell[0] + dx, cell[1] + dy, cell[2] + dz]) {
+                        for &candidate in candidates {
+                            if vertex_distance_within(&kept[candidate as usize], vertex, epsilon, mode) {
+                                found = Some(candidate);
+                                break 'search;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        remap[i] = match found {
+            Some(existing) => existing,
+            None => {
+                let new_index = kept.len() as u32;
+                kept.push(*vertex);
+                grid.entry(cell).or_default().push(new_index);
+                new_index
+            }
+        };
+    }
+
+    mesh.convert_to_triangle_list();
+    let indices: Vec<u32> = mesh.indices.iter().map(|&i| remap[i as usize]).collect();
+
+    mesh.indices = if mesh.mode == gl::TRIANGLES {
+        indices.chunks_exact(3)
+            .filter(|t| t[0] != t[1] && t[1] != t[2] && t[0] != t[2])
+            .flatten()
+            .cloned()
+            .collect()
+    } else {
+        indices
+    };
+    mesh.vertices = kept;
+    mesh.recompute_bounds();
+
+    before - mesh.vertices.len()
+}
+
+fn forsyth_vertex_score(cache_position: Option<usize>, remaining_valence: u32) -> f32
+{
+    if remaining_valence == 0 {
+        return -1.0;
+    }
+
+    let mut score = 0.0f32;
+    if let Some(position) = cache_position {
+        if position < 3 {
+            // the last triangle used these, a fixed score avoids favouring one of its edges
+            score = 0.75;
+        } else {
+            let scaler = 1.0 / (FORSYTH_CACHE_SIZE - 3) as f32;
+            score = (1.0 - (position - 3) as f32 * scaler).powf(1.5);
+        }
+    }
+
+    // boost vertices with few triangles left so they get finished and leave the cache
+    score + 2.0 * (remaining_valence as f32).powf(-0.5)
+}
+
+// Reorders triangles for the post-transform vertex cache using Tom Forsyth's linear-speed algorithm.
+// Works for any cache size, returns the new index list
+pub fn optimize_vertex_cache(indices: &[u32], vertex_count: usize) -> Vec<u32>
+{
+    let triangle_count = indices.len() / 3;
+    if triangle_count == 0 {
+        return indices.to_vec();
+    }
+
+    let mut valence = vec![0u32; vertex_count];
+    for &index in indices {
+        valence[index as usize] += 1;
+    }
+
+    // triangles using each vertex, compacted in one array
+    let mut adjacency_offset = vec![0usize; vertex_count + 1];
+    for v in 0..vertex_count {
+        adjacency_offset[v + 1] = adjacency_offset[v] + valence[v] as usize;
+    }
+    let mut adjacency = vec![0usize; indices.len()];
+    let mut fill = adjacency_offset.clone();
+    for (i, &index) in indices.iter().enumerate() {
+        adjacency[fill[index as usize]] = i / 3;
+        fill[index as usize] += 1;
+    }
+
+    let mut cache_position: Vec<Option<usize>> = vec![None; vertex_count];
+    let mut vertex_score: Vec<f32> = (0..vertex_count).map(|v| forsyth_vertex_score(None, valence[v])).collect();
+    let mut emitted = vec![false; triangle_count];
+
+    let mut cache: Vec<u32> = Vec::with_capacity(FORSYTH_CACHE_SIZE + 3);
+    let mut output = Vec::with_capacity(indices.len());
+    let mut next_unemitted = 0usize;
+
+    let mut best: Option<usize> = None;
+    for _ in 0..triangle_count {
+        let triangle = match best {
+            Some(t) => t,
+            None => {
+                // nothing good in the cache, continue with the first triangle not drawn yet
+                while emitted[next_unemitted] {
+                    next_unemitted += 1;
+                }
+                next_unemitted
+            }
+        };
+
+        emitted[triangle] = true;
+        let corners = [indices[triangle * 3], indices[triangle * 3 + 1], indices[triangle * 3 + 2]];
+        output.extend_from_slice(&corners);
+
+        // remove the triangle from the adjacency of its vertices
+        for &v in &corners {
+            let v = v as usize;
+            let start = adjacency_offset[v];
+            let end = start + valence[v] as usize;
+            if let Some(slot) = adjacency[start..end].iter().position(|&t| t == triangle) {
+                adjacency.swap(start + slot, end - 1);
+            }
+            valence[v] -= 1;
+        }
+
+        // move the vertices to the front of the LRU cache
+        let mut new_cache: Vec<u32> = Vec::with_capacity(FORSYTH_CACHE_SIZE + 3);
+        for &v in corners.iter().chain(cache.iter()) {
+            if !new_cache.contains(&v) {
+                new_cache.push(v);
+            }
+        }
+
+        // vertices that fell out of the cache lose their cache bonus
+        for &v in new_cache.iter().skip(FORSYTH_CACHE_SIZE) {
+            cache_position[v as usize] = None;
+            vertex_score[v as usize] = forsyth_vertex_score(None, valence[v as usize]);
+        }
+        new_cache.truncate(FORSYTH_CACHE_SIZE);
+
+        for (position, &v) in new_cache.iter().enumerate() {
+            cache_position[v as usize] = Some(position);
+            vertex_score[v as usize] = forsyth_vertex_score(Some(position), valence[v as usize]);
+        }
+        cache = new_cache;
+
+        // rescore the triangles touching the cache and pick the best one
+        best = None;
+        let mut best_score = -1.0f32;
+        for &v in &cache {
+            let v = v as usize;
+            let start = adjacency_offset[v];
+            for &t in &adjacency[start..start + valence[v] as usize] {
+                let score: f32 = (0..3).map(|k| vertex_score[indices[t * 3 + k] as usize]).sum();
+                if score > best_score {
+                    best_score = score;
+                    best = Some(t);
+                }
+            }
+        }
+    }
+
+    output
+}
+
+// Reorders triangle clusters to reduce overdraw while keeping most of the vertex cache efficiency.
+// The index list should already be optimized for the vertex cache. It is split into clusters at hard boundaries
+// (triangles where the cache had to be refilled) and at soft boundaries where the cluster ACMR stays below
+// `threshold` times the ACMR of the whole run. Clusters facing away from the mesh center are drawn first,
+// as they are the most likely to occlude the others. A threshold of 1.05 allows 5% worse vertex cache usage
+pub fn optimize_overdraw(indices: &[u32], positions: &[Vec3], threshold: f32) -> Vec<u32>
+{
+    let triangle_count = indices.len() / 3;
+    if triangle_count == 0 {
+        return indices.to_vec();
+    }
+
+    // simulate the cache to know the misses per triangle
+    let mut cache: VecDeque<u32> = VecDeque::with_capacity(ACMR_CACHE_SIZE);
+    let misses: Vec<u32> = indices.chunks_exact(3).map(|t| {
+        let mut m = 0;
+        for &index in t {
+            if !cache.contains(&index) {
+                m += 1;
+                if cache.len() == ACMR_CACHE_SIZE {
+                    cache.pop_front();
+                }
+                cache.push_back(index);
+            }
+        }
+        m
+    }).collect();
+
+    let mut hard_boundaries: Vec<usize> = (0..triangle_count).filter(|&t| t == 0 || misses[t] == 3).collect();
+    hard_boundaries.push(triangle_count);
+
+    let mut clusters: Vec<(usize, usize)> = Vec::new();
+    for run in hard_boundaries.windows(2) {
+        let (start, end) = (run[0], run[1]);
+        let run_acmr = misses[start..end].iter().sum::<u32>() as f32 / (end - start) as f32;
+
+        let mut cluster_start = start;
+        let mut cluster_misses = 0;
+        for (t, &triangle_misses) in misses.iter().enumerate().take(end).skip(start) {
+            cluster_misses += triangle_misses;
+            let count = t + 1 - cluster_start;
+            if count >= MIN_CLUSTER_SIZE && t + 1 < end && cluster_misses as f32 / count as f32 <= run_acmr * threshold {
+                clusters.push((cluster_start, t + 1));
+                cluster_start = t + 1;
+                cluster_misses = 0;
+            }
+        }
+        clusters.push((cluster_start, end));
+    }
+
+    let triangle_area_normal = |t: usize| {
+        let p0 = positions[indices[t * 3] as usize];
+        let p1 = positions[indices[t * 3 + 1] as usize];
+        let p2 = positions[indices[t * 3 + 2] as usize];
+        (nalgebra_glm::cross(&(p1 - p0), &(p2 - p0)), (p0 + p1 + p2) / 3.0)
+    };
+
+    let mut mesh_centroid = nalgebra_glm::vec3(0.0f32, 0.0, 0.0);
+    for t in 0..triangle_count {
+        mesh_centroid += triangle_area_normal(t).1;
+    }
+    mesh_centroid /= triangle_count as f32;
+
+    // sort key: how much the cluster faces away from the mesh center
+    let mut sorted: Vec<(f32, usize, usize)> = clusters.iter().map(|&(start, end)| {
+        let mut normal = nalgebra_glm::vec3(0.0f32, 0.0, 0.0);
+        let mut centroid = nalgebra_glm::vec3(0.0f32, 0.0, 0.0);
+        let mut area = 0.0f32;
+        for t in start..end {
+            let (area_normal, center) = triangle_area_normal(t);
+            let a = nalgebra_glm::length(&area_normal);
+            normal += area_normal;
+            centroid += center * a;
+            area += a;
+        }
+        if area > 0.0 {
+            centroid /= area;
+        }
+        let normal_length = nalgebra_glm::length(&normal);
+        let facing = if normal_length > 0.0 { nalgebra_glm::dot(&(centroid - mesh_centroid), &(normal / normal_length)) } else { 0.0 };
+        (facing, start, end)
+    }).collect();
+
+    sorted.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+
+    sorted.iter().flat_map(|&(_, start, end)| indices[start * 3..end * 3].iter().cloned()).collect()
+}
+
+// Reorders the vertex buffer in the order the indices first reference the vertices so fetches are sequential.
+// Unused vertices are dropped
+pub fn optimize_vertex_fetch(mesh: &mut Mesh)
+{
+    let mut remap: Vec<Option<u32>> = vec![None; mesh.vertices.len()];
+    let mut vertices = Vec::with_capacity(mesh.vertices.len());
+
+    for index in mesh.indices.iter_mut() {
+        let new_index = match remap[*index as usize] {
+            Some(new_index) => new_index,
+            None => {
+                let new_index = vertices.len() as u32;
+                vertices.push(mesh.vertices[*index as usize]);
+                remap[*index as usize] = Some(new_index);
+                new_index
+            }
+        };
+        *index = new_index;
+    }
+
+    mesh.vertices = vertices;
+    mesh.recompute_bounds();
+}
+
+// Before/after statistics of optimize_mesh
+#[derive(Clone, Copy, Debug)]
+pub struct OptimizationReport
+{
+    pub vertices_before: usize,
+    pub vertices_after: usize,
+    pub triangles_before: usize,
+    pub triangles_after: usize,
+    pub acmr_before: f32,
+    pub acmr_after: f32,
+}
+
+impl fmt::Display for OptimizationReport
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        write!(
+            f,
+            "vertices: {} -> {}, triangles: {} -> {}, ACMR: {:.3} -> {:.3}",
+            self.vertices_before, self.vertices_after,
+            self.triangles_before, self.triangles_after,
+            self.acmr_before, self.acmr_after
+        )
+    }
+}
+
+// Runs the whole pipeline on a triangle mesh: attribute welding, vertex cache reordering, overdraw reordering and
+// vertex fetch reordering. Point and line meshes are left untouched
+pub fn optimize_mesh(mesh: &mut Mesh, weld_epsilon: f32) -> OptimizationReport
+{
+    mesh.convert_to_triangle_list();
+
+    let mut report = OptimizationReport {
+        vertices_before: mesh.vertices.len(),
+        vertices_after: mesh.vertices.len(),
+        triangles_before: mesh.indices.len() / 3,
+        triangles_after: mesh.indices.len() / 3,
+        acmr_before: acmr(&mesh.indices, ACMR_CACHE_SIZE),
+        acmr_after: 0.0,
+    };
+
+    if mesh.mode == gl::TRIANGLES {
+        weld_vertices(mesh, weld_epsilon, WeldMode::AllAttributes);
+
+        mesh.indices = optimize_vertex_cache(&mesh.indices, mesh.vertices.len());
+        let positions: Vec<Vec3> = mesh.vertices.iter().map(|v| v.position).collect();
+        mesh.indices = optimize_overdraw(&mesh.indices, &positions, 1.05);
+
+        optimize_vertex_fetch(mesh);
+    }
+
+    report.vertices_after = mesh.vertices.len();
+    report.triangles_after = mesh.indices.len() / 3;
+    report.acmr_after = acmr(&mesh.indices, ACMR_CACHE_SIZE);
+
+    report
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use super::super::vertex_shapes;
+
+    // triangles with their winding kept but rotated to start at the smallest index, sorted
+    fn canonical_triangles(indices: &[u32]) -> Vec<[u32; 3]>
+    {
+        let mut triangles: Vec<[u32; 3]> = indices.chunks_exact(3).map(|t| {
+            let first = (0..3).min_by_key(|&k| t[k]).unwrap();
+            [t[first], t[(first + 1) % 3], t[(first + 2) % 3]]
+        }).collect();
+        triangles.sort_unstable();
+        triangles
+    }
+
+    // the triangle list in a scrambled order, as a cache-unfriendly exporter could produce it
+    fn shuffled_triangles(indices: &[u32]) -> Vec<u32>
+    {
+        let mut triangles: Vec<&[u32]> = indices.chunks_exact(3).collect();
+        let mut state = 12345u32;
+        for i in (1..triangles.len()).rev() {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            triangles.swap(i, (state >> 8) as usize % (i + 1));
+        }
+        triangles.concat()
+    }
+
+    fn vertex(x: f32, y: f32, u: f32) -> Vertex
+    {
+        Vertex::new(nalgebra_glm::vec3(x, y, 0.0), nalgebra_glm::vec2(u, 0.0))
+    }
+
+    #[test]
+    fn acmr_of_known_sequences()
+    {
+        assert_eq!(acmr(&[], ACMR_CACHE_SIZE), 0.0);
+        assert_eq!(acmr(&[0, 1, 2, 3, 4, 5], ACMR_CACHE_SIZE), 3.0);
+        // a strip of quads shares two vertices per triangle
+        assert_eq!(acmr(&[0, 1, 2, 2, 1, 3], ACMR_CACHE_SIZE), 2.0);
+    }
+
+    #[test]
+    fn vertex_cache_lowers_acmr_and_keeps_triangles()
+    {
+        let grid = vertex_shapes::get_plane_mesh(1.0, 32);
+        let indices = shuffled_triangles(&grid.indices);
+
+        let optimized = optimize_vertex_cache(&indices, grid.vertices.len());
+
+        let before = acmr(&indices, ACMR_CACHE_SIZE);
+        let after = acmr(&optimized, ACMR_CACHE_SIZE);
+        assert!(after < before, "ACMR {} -> {}", before, after);
+        assert!(after < 1.0, "ACMR {}", after);
+        assert_eq!(canonical_triangles(&optimized), canonical_triangles(&indices));
+    }
+
+    #[test]
+    fn optimize_mesh_reports_lower_acmr()
+    {
+        let mut sphere = vertex_shapes::get_uv_sphere_mesh(1.0, 32, 16);
+        sphere.indices = shuffled_triangles(&sphere.indices);
+        let position_triangles = |mesh: &Mesh| {
+            let mut triangles: Vec<Vec<[u32; 3]>> = mesh.indices.chunks_exact(3).map(|t| {
+                t.iter().map(|&i| {
+                    let p = mesh.vertices[i as usize].position;
+                    [p.x.to_bits(), p.y.to_bits(), p.z.to_bits()]
+                }).collect()
+            }).collect();
+            for triangle in triangles.iter_mut() {
+                let first = (0..3).min_by_key(|&k| triangle[k]).unwrap();
+                triangle.rotate_left(first);
+            }
+            triangles.sort_unstable();
+            triangles
+        };
+        let expected = position_triangles(&sphere);
+
+        let report = optimize_mesh(&mut sphere, 1e-6);
+
+        assert!(report.acmr_after < report.acmr_before, "{}", report);
+        assert_eq!(report.triangles_after, report.triangles_before);
+        assert_eq!(report.vertices_after, sphere.vertices.len());
+        assert_eq!(position_triangles(&sphere), expected);
+    }
+
+    #[test]
+    fn weld_merges_within_epsilon()
+    {
+        let vertices = vec![
+            vertex(0.0, 0.0, 0.0), vertex(1.0, 0.0, 0.0), vertex(0.0, 1.0, 0.0),
+            vertex(1.0005, 0.0, 0.0), vertex(1.0, 1.0, 0.0), vertex(0.0, 1.0, 0.0),
+        ];
+        let mut mesh = Mesh::new(vertices.clone(), vec![0, 1, 2, 3, 4, 5], gl::TRIANGLES);
+        assert_eq!(weld_vertices(&mut mesh, 0.001, WeldMode::Position), 2);
+        assert_eq!(mesh.vertices.len(), 4);
+        assert_eq!(mesh.indices, vec![0, 1, 2, 1, 3, 2]);
+
+        // too far apart for a smaller epsilon
+        let mut mesh = Mesh::new(vertices, vec![0, 1, 2, 3, 4, 5], gl::TRIANGLES);
+        assert_eq!(weld_vertices(&mut mesh, 0.0001, WeldMode::Position), 1);
+        assert_eq!(mesh.vertices.len(), 5);
+    }
+
+    #[test]
+    fn weld_keeps_attribute_seams()
+    {
+        // same positions, different texture coordinates on the second triangle
+        let vertices = vec![
+            vertex(0.0, 0.0, 0.0), vertex(1.0, 0.0, 0.0), vertex(0.0, 1.0, 0.0),
+            vertex(1.0, 0.0, 0.5), vertex(1.0, 1.0, 0.0), vertex(0.0, 1.0, 0.0),
+        ];
+        let mut mesh = Mesh::new(vertices.clone(), vec![0, 1, 2, 3, 4, 5], gl::TRIANGLES);
+        assert_eq!(weld_vertices(&mut mesh, 0.001, WeldMode::AllAttributes), 1);
+        assert_eq!(mesh.indices, vec![0, 1, 2, 3, 4, 2]);
+
+        let mut mesh = Mesh::new(vertices, vec![0, 1, 2, 3, 4, 5], gl::TRIANGLES);
+        assert_eq!(weld_vertices(&mut mesh, 0.001, WeldMode::Position), 2);
+    }
+
+    #[test]
+    fn weld_drops_collapsed_triangles()
+    {
+        let vertices = vec![
+            vertex(0.0, 0.0, 0.0), vertex(1.0, 0.0, 0.0), vertex(0.0, 1.0, 0.0),
+            vertex(0.0, 0.0, 0.0), vertex(0.0001, 0.0, 0.0), vertex(0.0, 1.0, 0.0),
+        ];
+        let mut mesh = Mesh::new(vertices, vec![0, 1, 2, 3, 4, 5], gl::TRIANGLES);
+        weld_vertices(&mut mesh, 0.001, WeldMode::Position);
+        assert_eq!(mesh.indices, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn vertex_fetch_follows_first_use()
+    {
+        let vertices: Vec<Vertex> = (0..5).map(|i| vertex(i as f32, 0.0, 0.0)).collect();
+        // vertex 3 is never used
+        let mut mesh = Mesh::new(vertices, vec![4, 2, 0, 0, 2, 1], gl::TRIANGLES);
+        optimize_vertex_fetch(&mut mesh);
+
+        assert_eq!(mesh.indices, vec![0, 1, 2, 2, 1, 3]);
+        let xs: Vec<f32> = mesh.vertices.iter().map(|v| v.position.x).collect();
+        assert_eq!(xs, vec![4.0, 2.0, 0.0, 1.0]);
+    }
+}
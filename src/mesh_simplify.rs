@@ -0,0 +1,591 @@
+use nalgebra_glm::{ Vec3 };
+
+use std::collections::{ HashMap, HashSet };
+
+use super::mesh::{ Mesh };
+
+// Symmetric 4x4 quadric stored as its 10 unique coefficients (Garland & Heckbert)
+#[derive(Clone, Copy, Debug, Default)]
+struct Quadric
+{
+    a2: f64, ab: f64, ac: f64, ad: f64,
+    b2: f64, bc: f64, bd: f64,
+    c2: f64, cd: f64,
+    d2: f64,
+}
+
+impl Quadric
+{
+    // quadric of the plane through `point` with unit `normal`, scaled by `weight`
+    fn from_plane(normal: &Vec3, point: &Vec3, weight: f64) -> Quadric
+    {
+        let (a, b, c) = (normal.x as f64, normal.y as f64, normal.z as f64);
+        let d = -(a * point.x as f64 + b * point.y as f64 + c * point.z as f64);
+
+        Quadric {
+            a2: a * a * weight, ab: a * b * weight, ac: a * c * weight, ad: a * d * weight,
+            b2: b * b * weight, bc: b * c * weight, bd: b * d * weight,
+            c2: c * c * weight, cd: c * d * weight,
+            d2: d * d * weight,
+        }
+    }
+
+    fn add(&mut self, other: &Quadric)
+    {
+        self.a2 += other.a2; self.ab += other.ab; self.ac += other.ac; self.ad += other.ad;
+        self.b2 += other.b2; self.bc += other.bc; self.bd += other.bd;
+        self.c2 += other.c2; self.cd += other.cd;
+        self.d2 += other.d2;
+    }
+
+    // squared distance of `p` to the planes accumulated in the quadric
+    fn error(&self, p: &Vec3) -> f64
+    {
+        let (x, y, z) = (p.x as f64, p.y as f64, p.z as f64);
+
+        let e = self.a2 * x * x + 2.0 * self.ab * x * y + 2.0 * self.ac * x * z + 2.0 * self.ad * x
+            + self.b2 * y * y + 2.0 * self.bc * y * z + 2.0 * self.bd * y
+            + self.c2 * z * z + 2.0 * self.cd * z
+            + self.d2;
+
+        e.max(0.0)
+    }
+}
+
+// Vertices sharing a position (UV seam, hard normal edge) are on the border of their own side of the seam, so
+// seams are kept like open borders. Such copies always collapse together, see seam_collapse
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum VertexKind {
+    // interior vertex, can collapse along any edge
+    Manifold,
+    // on an open boundary, can only slide along boundary edges so the outline is kept
+    Border,
+}
+
+// weight of the perpendicular planes added along border edges, makes sliding away from the border expensive
+const BORDER_WEIGHT: f64 = 10.0;
+
+fn position_key(p: &Vec3) -> [u32; 3]
+{
+    // adding 0.0 turns -0.0 into 0.0, the two compare equal but have different bits
+    [(p.x + 0.0).to_bits(), (p.y + 0.0).to_bits(), (p.z + 0.0).to_bits()]
+}
+
+fn triangle_normal(p0: &Vec3, p1: &Vec3, p2: &Vec3) -> Vec3
+{
+    nalgebra_glm::cross(&(p1 - p0), &(p2 - p0))
+}
+
+// Reduces an indexed triangle mesh with quadric error metric edge collapses. Vertices are never moved, an edge
+// collapse merges one endpoint into the other, so attributes stay valid and the vertex buffer can be shared by
+// every level of detail. Collapsing stops when the index count reaches `target_index_count` or when the next
+// collapse would exceed `target_error`, expressed relative to the mesh extent (0.01 = 1% of the bounding box
+// diagonal). Border vertices only slide along the border. Vertices sharing a position on a UV seam or hard edge
+// move together along the seam, so it neither opens nor changes shape. Meshes where no vertex is shared between
+// triangles can't be reduced, weld them first. Returns the new index list and the relative error reached
+pub fn simplify(mesh: &Mesh, target_index_count: usize, target_error: f32) -> (Vec<u32>, f32)
+{
+    let mut indices: Vec<u32> = mesh.triangles().iter().flatten().map(|&i| i as u32).collect();
+    let vertex_count = mesh.vertices.len();
+    if indices.len() <= target_index_count || vertex_count == 0 {
+        return (indices, 0.0);
+    }
+
+    let positions: Vec<Vec3> = mesh.vertices.iter().map(|v| v.position).collect();
+
+    // relative errors are computed against the bounding box diagonal
+    let mut min = positions[0];
+    let mut max = positions[0];
+    for p in &positions {
+        min = nalgebra_glm::min2(&min, p);
+        max = nalgebra_glm::max2(&max, p);
+    }
+    let extent = (nalgebra_glm::length(&(max - min)) as f64).max(1e-12);
+    let max_error = (target_error as f64 * extent).powi(2);
+
+    // directed edges without a twin are on the border
+    let mut edge_count: HashMap<(u32, u32), u32> = HashMap::new();
+    for t in indices.chunks_exact(3) {
+        for k in 0..3 {
+            *edge_count.entry((t[k], t[(k + 1) % 3])).or_insert(0) += 1;
+        }
+    }
+    let is_border_edge = |a: u32, b: u32| !edge_count.contains_key(&(b, a));
+
+    let mut kind = vec![VertexKind::Manifold; vertex_count];
+    for t in indices.chunks_exact(3) {
+        for k in 0..3 {
+            let (a, b) = (t[k], t[(k + 1) % 3]);
+            if is_border_edge(a, b) {
+                for &v in &[a, b] {
+                    if kind[v as usize] == VertexKind::Manifold {
+                        kind[v as usize] = VertexKind::Border;
+                    }
+                }
+            }
+        }
+    }
+
+    // quadrics: triangle planes weighted by area, plus planes perpendicular to border edges
+    let mut quadrics = vec![Quadric::default(); vertex_count];
+    for t in indices.chunks_exact(3) {
+        let (p0, p1, p2) = (positions[t[0] as usize], positions[t[1] as usize], positions[t[2] as usize]);
+        let n = triangle_normal(&p0, &p1, &p2);
+        let double_area = nalgebra_glm::length(&n);
+        if double_area <= 0.0 {
+            continue;
+        }
+        let plane = Quadric::from_plane(&(n / double_area), &p0, double_area as f64 * 0.5);
+        for &v in t {
+            quadrics[v as usize].add(&plane);
+        }
+
+        for k in 0..3 {
+            let (a, b) = (t[k], t[(k + 1) % 3]);
+            if is_border_edge(a, b) {
+                let edge = positions[b as usize] - positions[a as usize];
+                let edge_length = nalgebra_glm::length(&edge);
+                let perpendicular = nalgebra_glm::cross(&edge, &n);
+                let perpendicular_length = nalgebra_glm::length(&perpendicular);
+                if perpendicular_length > 0.0 {
+                    let border_plane = Quadric::from_plane(
+                        &(perpendicular / perpendicular_length),
+                        &positions[a as usize],
+                        (edge_length * edge_length) as f64 * BORDER_WEIGHT
+                    );
+                    quadrics[a as usize].add(&border_plane);
+                    quadrics[b as usize].add(&border_plane);
+                }
+            }
+        }
+    }
+
+    // union-find style remap: collapsed vertices point to the vertex they were merged into
+    let mut remap: Vec<u32> = (0..vertex_count as u32).collect();
+    let mut reached_error = 0.0f64;
+
+    loop {
+        if indices.len() <= target_index_count {
+            break;
+        }
+
+        // connectivity of the current triangles, borders change as collapses create new edges
+        let mut directed_edges: HashSet<(u32, u32)> = HashSet::new();
+        let mut vertex_triangles: Vec<Vec<usize>> = vec![Vec::new(); vertex_count];
+        for (i, t) in indices.chunks_exact(3).enumerate() {
+            for k in 0..3 {
+                directed_edges.insert((t[k], t[(k + 1) % 3]));
+                vertex_triangles[t[k] as usize].push(i);
+            }
+        }
+        // the vertices still in use at each position
+        let mut copies: HashMap<[u32; 3], Vec<u32>> = HashMap::new();
+        for (v, triangles) in vertex_triangles.iter().enumerate() {
+            if !triangles.is_empty() {
+                copies.entry(position_key(&positions[v])).or_default().push(v as u32);
+            }
+        }
+        let topology = Topology { kind: &kind, directed_edges: &directed_edges, copies: &copies, positions: &positions };
+
+        // candidate collapses of every copy of `from` onto a copy of `to`, with their cost
+        let mut candidates: Vec<(f64, Vec<(u32, u32)>)> = Vec::new();
+        let mut considered: HashSet<(u32, u32)> = HashSet::new();
+        for t in indices.chunks_exact(3) {
+            for k in 0..3 {
+                let (a, b) = (t[k], t[(k + 1) % 3]);
+                for &(from, to) in &[(a, b), (b, a)] {
+                    if !considered.insert((from, to)) {
+                        continue;
+                    }
+                    if let Some(pairs) = topology.seam_collapse(from, to) {
+                        let cost = pairs.iter().map(|&(from, to)| {
+                            let mut q = quadrics[from as usize];
+                            q.add(&quadrics[to as usize]);
+                            q.error(&positions[to as usize])
+                        }).sum();
+                        candidates.push((cost, pairs));
+                    }
+                }
+            }
+        }
+
+        if candidates.is_empty() {
+            break;
+        }
+        candidates.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+
+        // collapse greedily, each vertex takes part in at most one collapse per pass
+        let mut touched = vec![false; vertex_count];
+        let mut triangles_left = indices.len() / 3;
+        let target_triangles = target_index_count / 3;
+        let mut collapsed_any = false;
+
+        for (cost, pairs) in &candidates {
+            if *cost > max_error || triangles_left <= target_triangles {
+                break;
+            }
+            if pairs.iter().any(|&(from, to)| touched[from as usize] || touched[to as usize]) {
+                continue;
+            }
+            if pairs.iter().any(|&(from, to)| flips_triangle(&indices, &vertex_triangles[from as usize], &positions, from, to)) {
+                continue;
+            }
+
+            for &(from, to) in pairs {
+                // every triangle using the edge disappears
+                let removed = vertex_triangles[from as usize].iter()
+                    .filter(|&&t| indices[t * 3..t * 3 + 3].contains(&to))
+                    .count();
+
+                remap[from as usize] = to;
+                let q = quadrics[from as usize];
+                quadrics[to as usize].add(&q);
+
+                // neighbours of both vertices can't collapse again in this pass, their triangles changed
+                for &t in vertex_triangles[from as usize].iter().chain(vertex_triangles[to as usize].iter()) {
+                    for &v in &indices[t * 3..t * 3 + 3] {
+                        touched[v as usize] = true;
+                    }
+                }
+
+                triangles_left -= removed;
+            }
+            reached_error = reached_error.max(*cost);
+            collapsed_any = true;
+        }
+
+        if !collapsed_any {
+            break;
+        }
+
+        // apply the remap and drop degenerate triangles
+        for index in indices.iter_mut() {
+            let mut v = *index;
+            while remap[v as usize] != v {
+                v = remap[v as usize];
+            }
+            *index = v;
+        }
+        indices = indices.chunks_exact(3)
+            .filter(|t| t[0] != t[1] && t[1] != t[2] && t[0] != t[2])
+            .flatten()
+            .cloned()
+            .collect();
+    }
+
+    (indices, (reached_error.sqrt() / extent) as f32)
+}
+
+// Connectivity of one simplification pass
+struct Topology<'a>
+{
+    kind: &'a [VertexKind],
+    directed_edges: &'a HashSet<(u32, u32)>,
+    copies: &'a HashMap<[u32; 3], Vec<u32>>,
+    positions: &'a [Vec3],
+}
+
+impl<'a> Topology<'a>
+{
+    fn is_edge(&self, a: u32, b: u32) -> bool
+    {
+        self.directed_edges.contains(&(a, b)) || self.directed_edges.contains(&(b, a))
+    }
+
+    // edges used by a single triangle, the border of the mesh or of one side of a seam
+    fn is_border_edge(&self, a: u32, b: u32) -> bool
+    {
+        self.directed_edges.contains(&(a, b)) != self.directed_edges.contains(&(b, a))
+    }
+
+    fn can_collapse(&self, from: u32, to: u32) -> bool
+    {
+        match self.kind[from as usize] {
+            VertexKind::Manifold => true,
+            VertexKind::Border => self.kind[to as usize] != VertexKind::Manifold && self.is_border_edge(from, to),
+        }
+    }
+
+    // The (from, to) pairs that move every copy of the position of `from` onto the position of `to`. Each copy needs
+    // an edge to a copy of `to`, otherwise the copies would be pulled apart and open a crack, so a seam vertex only
+    // collapses along the seam. None if the collapse isn't allowed
+    fn seam_collapse(&self, from: u32, to: u32) -> Option<Vec<(u32, u32)>>
+    {
+        if !self.can_collapse(from, to) {
+            return None;
+        }
+
+        let from_copies = &self.copies[&position_key(&self.positions[from as usize])];
+        let to_copies = &self.copies[&position_key(&self.positions[to as usize])];
+        if from_copies.len() == 1 {
+            return Some(vec![(from, to)]);
+        }
+
+        from_copies.iter().map(|&copy| {
+            if copy == from {
+                return Some((from, to));
+            }
+            to_copies.iter()
+                .find(|&&target| self.is_edge(copy, target) && self.can_collapse(copy, target))
+                .map(|&target| (copy, target))
+        }).collect()
+    }
+}
+
+// true if moving `from` onto `to` turns one of the remaining triangles around `from` upside down
+fn flips_triangle(indices: &[u32], triangles: &[usize], positions: &[Vec3], from: u32, to: u32) -> bool
+{
+    for &t in triangles {
+        let corners = &indices[t * 3..t * 3 + 3];
+        if corners.contains(&to) {
+            // removed by the collapse
+            continue;
+        }
+
+        let p: Vec<Vec3> = corners.iter().map(|&v| positions[v as usize]).collect();
+        let moved: Vec<Vec3> = corners.iter().map(|&v| positions[if v == from { to } else { v } as usize]).collect();
+
+        let before = triangle_normal(&p[0], &p[1], &p[2]);
+        let after = triangle_normal(&moved[0], &moved[1], &moved[2]);
+        if nalgebra_glm::dot(&before, &after) <= 0.0 {
+            return true;
+        }
+    }
+    false
+}
+
+// One level of detail: an index list into the shared vertex buffer and its simplification error
+#[derive(Clone, Debug)]
+pub struct LodLevel
+{
+    pub indices: Vec<u32>,
+    // error relative to the mesh extent, see simplify
+    pub error: f32,
+}
+
+// Chain of levels of detail for one mesh, level 0 is the original mesh
+pub struct MeshLod
+{
+    pub levels: Vec<LodLevel>,
+}
+
+impl MeshLod
+{
+    // builds `level_count` levels, each one targeting `ratio` times the triangles of the previous level.
+    // Generation stops early when the simplifier can't reduce the mesh further
+    pub fn generate(mesh: &Mesh, level_count: usize, ratio: f32, max_error: f32) -> MeshLod
+    {
+        let base: Vec<u32> = mesh.triangles().iter().flatten().map(|&i| i as u32).collect();
+        let mut levels = vec![LodLevel { indices: base, error: 0.0 }];
+
+        while levels.len() < level_count {
+            let previous = levels.last().unwrap();
+            let target = ((previous.indices.len() / 3) as f32 * ratio) as usize * 3;
+
+            let source = Mesh::new(mesh.vertices.clone(), previous.indices.clone(), gl::TRIANGLES);
+            let (indices, error) = simplify(&source, target, max_error);
+            if indices.len() >= previous.indices.len() {
+                break;
+            }
+
+            let error = error.max(previous.error);
+            levels.push(LodLevel { indices, error });
+        }
+
+        MeshLod { levels }
+    }
+
+    // all levels appended in one index list, with the (first index, index count) range of each level.
+    // Upload it as the mesh indices and draw a level with Mesh::draw_range
+    pub fn merged_indices(&self) -> (Vec<u32>, Vec<(usize, usize)>)
+    {
+        let mut merged = Vec::new();
+        let mut ranges = Vec::new();
+        for level in &self.levels {
+            ranges.push((merged.len(), level.indices.len()));
+            merged.extend_from_slice(&level.indices);
+        }
+        (merged, ranges)
+    }
+
+    // Picks the coarsest level whose error, projected on screen, stays under `pixel_threshold` pixels.
+    // `radius` is the bounding sphere radius of the object (its extent is about 2 * radius), `distance` the
+    // distance from the camera to the sphere center, `fov_y` the vertical field of view in radians
+    pub fn select_level(&self, radius: f32, distance: f32, fov_y: f32, screen_height: f32, pixel_threshold: f32) -> usize
+    {
+        let distance = distance.max(radius).max(1e-6);
+        // pixels covered by one world unit at this distance
+        let pixels_per_unit = screen_height / (2.0 * distance * (fov_y * 0.5).tan());
+        let extent = 2.0 * radius;
+
+        let mut selected = 0;
+        for (i, level) in self.levels.iter().enumerate() {
+            if level.error * extent * pixels_per_unit <= pixel_threshold {
+                selected = i;
+            }
+        }
+        selected
+    }
+
+    // Same as select_level using the camera zoom (field of view in degrees) and position
+    pub fn select_level_for_camera(&self, camera: &super::camera::Camera, center: &Vec3, radius: f32, screen_height: f32) -> usize
+    {
+        let distance = nalgebra_glm::distance(&camera.position, center);
+        self.select_level(radius, distance, super::utils::degree_to_radian(camera.zoom), screen_height, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use super::super::mesh::Vertex;
+    use super::super::vertex_shapes;
+
+    fn area(mesh: &Mesh, indices: &[u32]) -> f32
+    {
+        indices.chunks_exact(3).map(|t| {
+            let p: Vec<Vec3> = t.iter().map(|&i| mesh.vertices[i as usize].position).collect();
+            nalgebra_glm::length(&triangle_normal(&p[0], &p[1], &p[2])) * 0.5
+        }).sum()
+    }
+
+    // every edge, compared by position, is used once in each direction
+    fn is_closed(mesh: &Mesh, indices: &[u32]) -> bool
+    {
+        let mut edges: HashMap<([u32; 3], [u32; 3]), i32> = HashMap::new();
+        for t in indices.chunks_exact(3) {
+            for k in 0..3 {
+                let a = position_key(&mesh.vertices[t[k] as usize].position);
+                let b = position_key(&mesh.vertices[t[(k + 1) % 3] as usize].position);
+                *edges.entry((a, b)).or_insert(0) += 1;
+            }
+        }
+        edges.iter().all(|(&(a, b), &count)| edges.get(&(b, a)) == Some(&count))
+    }
+
+    // cube made of six subdivided faces that don't share vertices, like a hard-edged import
+    fn faceted_cube(subdivisions: u32) -> Mesh
+    {
+        let mut vertices: Vec<Vertex> = Vec::new();
+        let mut indices = Vec::new();
+        let rotations = [
+            (0.0f32, nalgebra_glm::vec3(1.0f32, 0.0, 0.0)),
+            (180.0, nalgebra_glm::vec3(1.0, 0.0, 0.0)),
+            (90.0, nalgebra_glm::vec3(1.0, 0.0, 0.0)),
+            (-90.0, nalgebra_glm::vec3(1.0, 0.0, 0.0)),
+            (90.0, nalgebra_glm::vec3(0.0, 0.0, 1.0)),
+            (-90.0, nalgebra_glm::vec3(0.0, 0.0, 1.0)),
+        ];
+        for (angle, axis) in rotations.iter() {
+            let face = vertex_shapes::get_plane_mesh(2.0, subdivisions);
+            let transform = nalgebra_glm::rotation(angle.to_radians(), axis)
+                * nalgebra_glm::translation(&nalgebra_glm::vec3(0.0, 1.0, 0.0));
+            let offset = vertices.len() as u32;
+            for vertex in &face.vertices {
+                let mut vertex = *vertex;
+                let p = transform * nalgebra_glm::vec4(vertex.position.x, vertex.position.y, vertex.position.z, 1.0);
+                // rounded so the copies on shared edges get exactly the same position
+                vertex.position = p.xyz().map(|c| (c * 1024.0).round() / 1024.0);
+                vertices.push(vertex);
+            }
+            indices.extend(face.indices.iter().map(|&i| i + offset));
+        }
+        Mesh::new(vertices, indices, gl::TRIANGLES)
+    }
+
+
+    #[test]
+    fn reaches_target_triangle_count()
+    {
+        let sphere = vertex_shapes::get_uv_sphere_mesh(1.0, 32, 16);
+        let triangles = sphere.indices.len() / 3;
+
+        let (indices, error) = simplify(&sphere, triangles / 4 * 3, 1.0);
+
+        assert!(indices.len() / 3 <= triangles / 4, "{} triangles left of {}", indices.len() / 3, triangles);
+        assert!(indices.len() / 3 > triangles / 8, "{} triangles left of {}", indices.len() / 3, triangles);
+        assert!(error > 0.0 && error < 0.1, "error {}", error);
+    }
+
+    #[test]
+    fn error_limit_stops_early()
+    {
+        let sphere = vertex_shapes::get_uv_sphere_mesh(1.0, 32, 16);
+        let (indices, error) = simplify(&sphere, 0, 0.001);
+
+        assert!(indices.len() > sphere.indices.len() / 2);
+        assert!(error <= 0.001);
+    }
+
+    #[test]
+    fn keeps_the_border()
+    {
+        let plane = vertex_shapes::get_plane_mesh(1.0, 16);
+        let (indices, _) = simplify(&plane, 0, 0.01);
+
+        // flat, so everything inside collapses, but the square outline and its area stay
+        assert!(indices.len() / 3 < 64, "{} triangles left", indices.len() / 3);
+        assert!((area(&plane, &indices) - 1.0).abs() < 1e-4, "area {}", area(&plane, &indices));
+        for corner in [(-0.5f32, -0.5f32), (0.5, -0.5), (-0.5, 0.5), (0.5, 0.5)].iter() {
+            assert!(indices.iter().any(|&i| {
+                let p = plane.vertices[i as usize].position;
+                p.x == corner.0 && p.z == corner.1
+            }), "corner {:?} removed", corner);
+        }
+    }
+
+    #[test]
+    fn seams_stay_closed()
+    {
+        // the sphere duplicates its seam column and pole vertices for the texture coordinates
+        let sphere = vertex_shapes::get_uv_sphere_mesh(1.0, 32, 16);
+        assert!(is_closed(&sphere, &sphere.indices));
+
+        let (indices, _) = simplify(&sphere, sphere.indices.len() / 4, 1.0);
+
+        assert!(is_closed(&sphere, &indices));
+        let seam_vertices = indices.iter().filter(|&&i| sphere.vertices[i as usize].tex_coords.x == 1.0).count();
+        assert!(seam_vertices > 0);
+    }
+
+    #[test]
+    fn reduces_hard_edged_meshes()
+    {
+        let cube = faceted_cube(8);
+        assert!(is_closed(&cube, &cube.indices));
+
+        let (indices, _) = simplify(&cube, 0, 0.01);
+
+        // the faces are flat, each one reduces to two triangles while the edges and corners stay in place
+        assert_eq!(indices.len() / 3, 12);
+        assert!(is_closed(&cube, &indices));
+        assert!((area(&cube, &indices) - 24.0).abs() < 1e-3, "area {}", area(&cube, &indices));
+    }
+
+    #[test]
+    fn lod_chain_and_selection()
+    {
+        let sphere = vertex_shapes::get_uv_sphere_mesh(1.0, 32, 16);
+        let lod = MeshLod::generate(&sphere, 4, 0.5, 1.0);
+
+        assert_eq!(lod.levels.len(), 4);
+        assert_eq!(lod.levels[0].indices, sphere.indices);
+        for pair in lod.levels.windows(2) {
+            assert!(pair[1].indices.len() < pair[0].indices.len());
+            assert!(pair[1].error >= pair[0].error);
+        }
+
+        let (merged, ranges) = lod.merged_indices();
+        assert_eq!(ranges.len(), 4);
+        for (level, &(first, count)) in lod.levels.iter().zip(&ranges) {
+            assert_eq!(&merged[first..first + count], &level.indices[..]);
+        }
+
+        let fov = 45.0f32.to_radians();
+        assert_eq!(lod.select_level(1.0, 1.0, fov, 1080.0, 1.0), 0);
+        assert_eq!(lod.select_level(1.0, 10000.0, fov, 1080.0, 1.0), 3);
+        let levels: Vec<usize> = [2.0, 20.0, 200.0, 2000.0].iter().map(|&d| lod.select_level(1.0, d, fov, 1080.0, 1.0)).collect();
+        assert!(levels.windows(2).all(|pair| pair[0] <= pair[1]), "{:?}", levels);
+    }
+}
@@ -0,0 +1,367 @@
+use image::RgbaImage;
+
+use super::texture;
+
+// Downsampling kernel. Kaiser and Lanczos keep mips sharp where the box filter (what glGenerateMipmap usually does)
+// blurs, at the cost of slight ringing on hard edges
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MipFilter {
+    Box,
+    // sinc windowed by a Kaiser window, 3 texels wide, alpha 4
+    Kaiser,
+    Lanczos3,
+}
+
+impl MipFilter
+{
+    // half width of the kernel, in texels of the smaller level
+    fn radius(self) -> f32
+    {
+        match self {
+            MipFilter::Box => 0.5,
+            MipFilter::Kaiser | MipFilter::Lanczos3 => 3.0,
+        }
+    }
+
+    fn weight(self, x: f32) -> f32
+    {
+        let x = x.abs();
+        match self {
+            MipFilter::Box => if x <= 0.5 { 1.0 } else { 0.0 },
+            MipFilter::Kaiser => {
+                let width = 3.0;
+                if x >= width {
+                    return 0.0;
+                }
+                let alpha = 4.0;
+                sinc(x) * bessel_i0(alpha * (1.0 - (x / width) * (x / width)).sqrt()) / bessel_i0(alpha)
+            },
+            MipFilter::Lanczos3 => if x < 3.0 { sinc(x) * sinc(x / 3.0) } else { 0.0 },
+        }
+    }
+}
+
+fn sinc(x: f32) -> f32
+{
+    if x.abs() < 1e-5 {
+        return 1.0;
+    }
+    let x = x * std::f32::consts::PI;
+    x.sin() / x
+}
+
+// modified Bessel function of the first kind, order 0, from its power series
+fn bessel_i0(x: f32) -> f32
+{
+    let mut sum = 1.0f32;
+    let mut term = 1.0f32;
+    let half_square = x * x / 4.0;
+    for k in 1..32 {
+        term *= half_square / (k * k) as f32;
+        sum += term;
+        if term < sum * 1e-7 {
+            break;
+        }
+    }
+    sum
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MipOptions
+{
+    pub filter: MipFilter,
+    // the color channels are sRGB encoded: they are averaged in linear space and encoded again
+    pub srgb: bool,
+    // alpha test cutoff of a cutout texture (foliage, fences, ...). Alpha in every level is scaled so the same
+    // fraction of texels passes the test as in level 0, otherwise cutouts thin out and vanish in the distance
+    pub alpha_cutoff: Option<f32>,
+    // None generates the full chain down to 1x1
+    pub max_levels: Option<u32>,
+}
+
+impl Default for MipOptions
+{
+    fn default() -> MipOptions
+    {
+        MipOptions { filter: MipFilter::Kaiser, srgb: false, alpha_cutoff: None, max_levels: None }
+    }
+}
+
+// Linear RGBA image with f32 channels, the working format between levels so rounding doesn't accumulate
+struct FloatLevel
+{
+    width: u32,
+    height: u32,
+    pixels: Vec<[f32; 4]>,
+}
+
+fn srgb_to_linear(value: f32) -> f32
+{
+    if value <= 0.04045 { value / 12.92 } else { ((value + 0.055) / 1.055).powf(2.4) }
+}
+
+fn linear_to_srgb(value: f32) -> f32
+{
+    if value <= 0.0031308 { value * 12.92 } else { 1.055 * value.powf(1.0 / 2.4) - 0.055 }
+}
+
+fn to_u8(value: f32) -> u8
+{
+    (value.clamp(0.0, 1.0) * 255.0 + 0.5) as u8
+}
+
+// (source index, weight) taps of every destination texel along one axis, edges clamped
+fn filter_taps(source_size: u32, destination_size: u32, filter: MipFilter) -> Vec<Vec<(usize, f32)>>
+{
+    let scale = source_size as f32 / destination_size as f32;
+    let support = filter.radius() * scale;
+
+    (0..destination_size).map(|i| {
+        // center of the destination texel in source texel coordinates
+        let center = (i as f32 + 0.5) * scale - 0.5;
+        let first = (center - support).floor() as i64;
+        let last = (center + support).ceil() as i64;
+
+        let mut taps: Vec<(usize, f32)> = (first..=last)
+            .map(|j| (j.clamp(0, source_size as i64 - 1) as usize, filter.weight((j as f32 - center) / scale)))
+            .filter(|&(_, weight)| weight != 0.0)
+            .collect();
+
+        let total: f32 = taps.iter().map(|&(_, weight)| weight).sum();
+        if total.abs() > 1e-6 {
+            for tap in taps.iter_mut() {
+                tap.1 /= total;
+            }
+        } else {
+            taps = vec![(center.round().clamp(0.0, source_size as f32 - 1.0) as usize, 1.0)];
+        }
+        taps
+    }).collect()
+}
+
+// separable resample, rows first then columns
+fn downsample(level: &FloatLevel, width: u32, height: u32, filter: MipFilter) -> FloatLevel
+{
+    let horizontal = filter_taps(level.width, width, filter);
+    let vertical = filter_taps(level.height, height, filter);
+    let source_width = level.width as usize;
+
+    let mut rows = vec![[0.0f32; 4]; width as usize * level.height as usize];
+    for y in 0..level.height as usize {
+        for (x, taps) in horizontal.iter().enumerate() {
+            let mut sum = [0.0f32; 4];
+            for &(i, weight) in taps {
+                let pixel = level.pixels[y * source_width + i];
+                for c in 0..4 {
+                    sum[c] += pixel[c] * weight;
+                }
+            }
+            rows[y * width as usize + x] = sum;
+        }
+    }
+
+    let mut pixels = vec![[0.0f32; 4]; width as usize * height as usize];
+    for (y, taps) in vertical.iter().enumerate() {
+        for x in 0..width as usize {
+            let mut sum = [0.0f32; 4];
+            for &(i, weight) in taps {
+                let pixel = rows[i * width as usize + x];
+                for c in 0..4 {
+                    sum[c] += pixel[c] * weight;
+                }
+            }
+            pixels[y * width as usize + x] = sum;
+        }
+    }
+
+    FloatLevel { width, height, pixels }
+}
+
+// fraction of texels whose scaled alpha passes the cutoff
+fn alpha_coverage(pixels: &[[f32; 4]], cutoff: f32, scale: f32) -> f32
+{
+    let passing = pixels.iter().filter(|pixel| pixel[3] * scale > cutoff).count();
+    passing as f32 / pixels.len().max(1) as f32
+}
+
+// alpha scale giving `target` coverage, found by bisection since coverage grows with the scale
+fn coverage_scale(pixels: &[[f32; 4]], cutoff: f32, target: f32) -> f32
+{
+    let (mut low, mut high) = (0.0f32, 8.0f32);
+    for _ in 0..20 {
+        let middle = (low + high) * 0.5;
+        if alpha_coverage(pixels, cutoff, middle) < target {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+    high
+}
+
+fn to_image(level: &FloatLevel, options: &MipOptions, alpha_scale: f32) -> RgbaImage
+{
+    let mut bytes = Vec::with_capacity(level.pixels.len() * 4);
+    for pixel in &level.pixels {
+        for &value in &pixel[0..3] {
+            bytes.push(to_u8(if options.srgb { linear_to_srgb(value.max(0.0)) } else { value }));
+        }
+        bytes.push(to_u8(pixel[3] * alpha_scale));
+    }
+    RgbaImage::from_raw(level.width, level.height, bytes).unwrap()
+}
+
+// Builds the mip chain of `image` on the CPU, level 0 (a copy of the image) first. Every level is filtered from the
+// previous one in linear floating point
+pub fn generate_mips(image: &RgbaImage, options: &MipOptions) -> Vec<RgbaImage>
+{
+    let (width, height) = image.dimensions();
+    let level_count = texture::mip_level_count(width, height).min(options.max_levels.unwrap_or(u32::MAX).max(1));
+
+    let decode: Vec<f32> = (0..256)
+        .map(|i| if options.srgb { srgb_to_linear(i as f32 / 255.0) } else { i as f32 / 255.0 })
+        .collect();
+    let mut level = FloatLevel {
+        width,
+        height,
+        pixels: image.pixels()
+            .map(|p| [decode[p[0] as usize], decode[p[1] as usize], decode[p[2] as usize], p[3] as f32 / 255.0])
+            .collect(),
+    };
+
+    let target_coverage = options.alpha_cutoff.map(|cutoff| alpha_coverage(&level.pixels, cutoff, 1.0));
+
+    let mut levels = vec![image.clone()];
+    for _ in 1..level_count {
+        let (next_width, next_height) = ((level.width / 2).max(1), (level.height / 2).max(1));
+        level = downsample(&level, next_width, next_height, options.filter);
+
+        // the scaled alpha is only written out, the next level is filtered from the unscaled values
+        let alpha_scale = match (options.alpha_cutoff, target_coverage) {
+            (Some(cutoff), Some(target)) => coverage_scale(&level.pixels, cutoff, target),
+            _ => 1.0,
+        };
+        levels.push(to_image(&level, options, alpha_scale));
+    }
+    levels
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    const FILTERS: [MipFilter; 3] = [MipFilter::Box, MipFilter::Kaiser, MipFilter::Lanczos3];
+
+    fn sizes(levels: &[RgbaImage]) -> Vec<(u32, u32)>
+    {
+        levels.iter().map(|level| level.dimensions()).collect()
+    }
+
+    // fraction of the texels of `image` passing the alpha test
+    fn coverage(image: &RgbaImage, cutoff: f32) -> f32
+    {
+        let passing = image.pixels().filter(|p| p[3] as f32 / 255.0 > cutoff).count();
+        passing as f32 / (image.width() * image.height()) as f32
+    }
+
+    #[test]
+    fn level_sizes_halve_down_to_1x1()
+    {
+        let options = MipOptions::default();
+        assert_eq!(sizes(&generate_mips(&RgbaImage::new(16, 4), &options)), [(16, 4), (8, 2), (4, 1), (2, 1), (1, 1)]);
+        assert_eq!(sizes(&generate_mips(&RgbaImage::new(7, 3), &options)), [(7, 3), (3, 1), (1, 1)]);
+        assert_eq!(sizes(&generate_mips(&RgbaImage::new(1, 5), &options)), [(1, 5), (1, 2), (1, 1)]);
+        assert_eq!(sizes(&generate_mips(&RgbaImage::new(1, 1), &options)), [(1, 1)]);
+
+        // max_levels cuts the chain short, but level 0 is always there
+        let two = MipOptions { max_levels: Some(2), ..options };
+        assert_eq!(sizes(&generate_mips(&RgbaImage::new(16, 4), &two)), [(16, 4), (8, 2)]);
+        let none = MipOptions { max_levels: Some(0), ..options };
+        assert_eq!(sizes(&generate_mips(&RgbaImage::new(16, 4), &none)), [(16, 4)]);
+    }
+
+    #[test]
+    fn taps_are_normalized_and_inside_the_source()
+    {
+        for filter in FILTERS.iter() {
+            for &(source, destination) in [(8, 4), (7, 3), (5, 2), (3, 1), (2, 1)].iter() {
+                let taps = filter_taps(source, destination, *filter);
+                assert_eq!(taps.len(), destination as usize);
+                for texel in &taps {
+                    let total: f32 = texel.iter().map(|&(_, weight)| weight).sum();
+                    assert!((total - 1.0).abs() < 1e-5, "{:?} {} -> {}: weights sum to {}", filter, source, destination, total);
+                    assert!(texel.iter().all(|&(index, _)| index < source as usize));
+                }
+            }
+        }
+
+        // halving with the box filter averages pairs
+        assert_eq!(filter_taps(4, 2, MipFilter::Box), vec![vec![(0, 0.5), (1, 0.5)], vec![(2, 0.5), (3, 0.5)]]);
+    }
+
+    #[test]
+    fn constant_images_stay_constant()
+    {
+        let image = RgbaImage::from_pixel(13, 6, image::Rgba([200, 90, 17, 140]));
+        for filter in FILTERS.iter() {
+            for &srgb in [false, true].iter() {
+                let options = MipOptions { filter: *filter, srgb, ..Default::default() };
+                for level in generate_mips(&image, &options) {
+                    assert!(level.pixels().all(|p| p.0 == [200, 90, 17, 140]), "{:?}, sRGB {}", filter, srgb);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn srgb_levels_are_averaged_in_linear_space()
+    {
+        let checkerboard = RgbaImage::from_fn(8, 8, |x, y| {
+            let value = if (x + y) % 2 == 0 { 255 } else { 0 };
+            image::Rgba([value, value, value, 255])
+        });
+
+        // half the light of white is 188 in sRGB, averaging the encoded values would give 128
+        let srgb = generate_mips(&checkerboard, &MipOptions { filter: MipFilter::Box, srgb: true, ..Default::default() });
+        assert!(srgb[1].pixels().all(|p| p.0 == [188, 188, 188, 255]));
+        let linear = generate_mips(&checkerboard, &MipOptions { filter: MipFilter::Box, srgb: false, ..Default::default() });
+        assert!(linear[1].pixels().all(|p| p.0 == [128, 128, 128, 255]));
+    }
+
+    #[test]
+    fn coverage_scale_hits_the_target()
+    {
+        let pixels: Vec<[f32; 4]> = (0..10).map(|i| [0.0, 0.0, 0.0, i as f32 / 10.0]).collect();
+        let scale = coverage_scale(&pixels, 0.5, 0.7);
+        assert!((alpha_coverage(&pixels, 0.5, scale) - 0.7).abs() < 1e-6);
+        // already at the target, the texels just above and below the cutoff stay where they are
+        let scale = coverage_scale(&pixels, 0.5, 0.4);
+        assert!(scale > 0.5 / 0.6 && scale <= 1.0 + 1e-3, "scale {}", scale);
+    }
+
+    #[test]
+    fn cutout_coverage_is_preserved()
+    {
+        // noisy alpha (grass, foliage), averaging pulls it towards 0.5 so fewer texels pass a 0.7 cutoff every level
+        let mut seed = 12345u32;
+        let cutout = RgbaImage::from_fn(64, 64, |_, _| {
+            seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+            image::Rgba([255, 255, 255, (seed >> 24) as u8])
+        });
+        let cutoff = 0.7;
+        let target = coverage(&cutout, cutoff);
+
+        for filter in FILTERS.iter() {
+            let scaled = generate_mips(&cutout, &MipOptions { filter: *filter, alpha_cutoff: Some(cutoff), ..Default::default() });
+            let plain = generate_mips(&cutout, &MipOptions { filter: *filter, ..Default::default() });
+
+            // levels of 8x8 and up, smaller ones can't get within a few percent
+            for (level, image) in scaled.iter().enumerate().take(4).skip(1) {
+                let kept = coverage(image, cutoff);
+                assert!((kept - target).abs() < 0.03, "{:?} level {}: coverage {} instead of {}", filter, level, kept, target);
+            }
+            assert!(coverage(&plain[3], cutoff) < target / 2.0);
+        }
+    }
+}
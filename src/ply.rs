@@ -0,0 +1,499 @@
+use std::fs::File;
+use std::io::{ BufWriter, Read, Write };
+use std::path::Path;
+
+use super::mesh::{ Mesh, Vertex };
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PlyFormat {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ScalarType {
+    Int8,
+    UInt8,
+    Int16,
+    UInt16,
+    Int32,
+    UInt32,
+    Float32,
+    Float64,
+}
+
+impl ScalarType
+{
+    fn parse(name: &str) -> Result<ScalarType, String>
+    {
+        Ok(match name {
+            "char" | "int8" => ScalarType::Int8,
+            "uchar" | "uint8" => ScalarType::UInt8,
+            "short" | "int16" => ScalarType::Int16,
+            "ushort" | "uint16" => ScalarType::UInt16,
+            "int" | "int32" => ScalarType::Int32,
+            "uint" | "uint32" => ScalarType::UInt32,
+            "float" | "float32" => ScalarType::Float32,
+            "double" | "float64" => ScalarType::Float64,
+            _ => return Err(format!("Unknown PLY property type: {}", name)),
+        })
+    }
+
+    fn size(self) -> usize
+    {
+        match self {
+            ScalarType::Int8 | ScalarType::UInt8 => 1,
+            ScalarType::Int16 | ScalarType::UInt16 => 2,
+            ScalarType::Int32 | ScalarType::UInt32 | ScalarType::Float32 => 4,
+            ScalarType::Float64 => 8,
+        }
+    }
+
+    fn is_float(self) -> bool
+    {
+        self == ScalarType::Float32 || self == ScalarType::Float64
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Property
+{
+    name: String,
+    type_: ScalarType,
+    // type of the item count for list properties
+    list_count: Option<ScalarType>,
+}
+
+#[derive(Clone, Debug)]
+struct Element
+{
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+struct Header
+{
+    format: PlyFormat,
+    elements: Vec<Element>,
+}
+
+fn parse_header(text: &str) -> Result<Header, String>
+{
+    let mut lines = text.lines();
+    if lines.next().map(|l| l.trim()) != Some("ply") {
+        return Err("Not a PLY file".to_string());
+    }
+
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+
+    for line in lines {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["format", "ascii", _] => format = Some(PlyFormat::Ascii),
+            ["format", "binary_little_endian", _] => format = Some(PlyFormat::BinaryLittleEndian),
+            ["format", "binary_big_endian", _] => format = Some(PlyFormat::BinaryBigEndian),
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count.parse().map_err(|_| format!("Invalid PLY element count: {}", count))?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count_type, item_type, name] => {
+                let element = elements.last_mut().ok_or("PLY property before any element")?;
+                element.properties.push(Property {
+                    name: name.to_string(),
+                    type_: ScalarType::parse(item_type)?,
+                    list_count: Some(ScalarType::parse(count_type)?),
+                });
+            },
+            ["property", type_, name] => {
+                let element = elements.last_mut().ok_or("PLY property before any element")?;
+                element.properties.push(Property { name: name.to_string(), type_: ScalarType::parse(type_)?, list_count: None });
+            },
+            ["end_header"] => break,
+            _ => {} // comment, obj_info and blank lines
+        }
+    }
+
+    Ok(Header { format: format.ok_or("PLY header without format")?, elements })
+}
+
+// Reads scalar values from the body, in any of the three encodings
+struct BodyReader<'a>
+{
+    data: &'a [u8],
+    position: usize,
+    format: PlyFormat,
+}
+
+impl<'a> BodyReader<'a>
+{
+    fn remaining(&self) -> usize
+    {
+        self.data.len() - self.position
+    }
+
+    fn next_ascii_token(&mut self) -> Result<&'a str, String>
+    {
+        while self.position < self.data.len() && self.data[self.position].is_ascii_whitespace() {
+            self.position += 1;
+        }
+        let start = self.position;
+        while self.position < self.data.len() && !self.data[self.position].is_ascii_whitespace() {
+            self.position += 1;
+        }
+        if start == self.position {
+            return Err("Unexpected end of PLY data".to_string());
+        }
+        std::str::from_utf8(&self.data[start..self.position]).map_err(|_| "Invalid ASCII PLY data".to_string())
+    }
+
+    fn read(&mut self, type_: ScalarType) -> Result<f64, String>
+    {
+        if self.format == PlyFormat::Ascii {
+            let token = self.next_ascii_token()?;
+            return token.parse::<f64>().map_err(|_| format!("Invalid number in PLY data: {}", token));
+        }
+
+        let size = type_.size();
+        if self.position + size > self.data.len() {
+            return Err("Unexpected end of PLY data".to_string());
+        }
+        let mut bytes = [0u8; 8];
+        bytes[..size].copy_from_slice(&self.data[self.position..self.position + size]);
+        self.position += size;
+
+        // normalize to little endian so a single decoding path remains
+        if self.format == PlyFormat::BinaryBigEndian {
+            bytes[..size].reverse();
+        }
+
+        Ok(match type_ {
+            ScalarType::Int8 => bytes[0] as i8 as f64,
+            ScalarType::UInt8 => bytes[0] as f64,
+            ScalarType::Int16 => i16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+            ScalarType::UInt16 => u16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+            ScalarType::Int32 => i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64,
+            ScalarType::UInt32 => u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64,
+            ScalarType::Float32 => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64,
+            ScalarType::Float64 => f64::from_le_bytes(bytes),
+        })
+    }
+}
+
+// Reads ASCII, binary little endian and binary big endian PLY files. Vertex positions, normals (nx, ny, nz),
+// colors (red, green, blue, alpha; integer colors are normalized) and texture coordinates (s/t, u/v or
+// texture_u/texture_v) are mapped to the mesh vertices. Polygon faces are triangulated as fans.
+// Files without faces become point clouds drawn with gl::POINTS
+pub fn read_ply<R: Read>(reader: &mut R) -> Result<Mesh, String>
+{
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data).map_err(|e| format!("Failed to read PLY: {}", e))?;
+
+    let header_end = data.windows(10).position(|w| w == b"end_header").ok_or("PLY header without end_header")?;
+    let body_start = header_end + 10 + data[header_end + 10..].iter().position(|&b| b == b'\n').map(|p| p + 1).unwrap_or(0);
+
+    let header_text = std::str::from_utf8(&data[..header_end + 10]).map_err(|_| "Invalid PLY header".to_string())?;
+    let header = parse_header(header_text)?;
+
+    let mut body = BodyReader { data: &data, position: body_start, format: header.format };
+
+    let mut vertices: Vec<Vertex> = Vec::new();
+    let mut indices: Vec<u32> = Vec::new();
+    let mut has_faces = false;
+
+    for element in &header.elements {
+        for _ in 0..element.count {
+            let mut vertex = Vertex::new(nalgebra_glm::vec3(0.0f32, 0.0, 0.0), nalgebra_glm::vec2(0.0f32, 0.0));
+
+            for property in &element.properties {
+                if let Some(count_type) = property.list_count {
+                    // every item takes at least one byte, a longer list can only come from a corrupt file
+                    let count = body.read(count_type)?;
+                    if count < 0.0 || count.fract() != 0.0 || count > body.remaining() as f64 {
+                        return Err(format!("Invalid PLY list length {}", count));
+                    }
+                    let count = count as usize;
+                    let is_index_list = element.name == "face" && (property.name == "vertex_indices" || property.name == "vertex_index");
+                    let mut items = Vec::with_capacity(count);
+                    for _ in 0..count {
+                        let item = body.read(property.type_)?;
+                        // indices past the last vertex are checked once all vertices are read, they may come after the faces
+                        if is_index_list && (item < 0.0 || item.fract() != 0.0 || item > u32::MAX as f64) {
+                            return Err(format!("Invalid PLY vertex index {}", item));
+                        }
+                        items.push(item as u32);
+                    }
+
+                    if is_index_list {
+                        for i in 2..items.len() {
+                            indices.extend_from_slice(&[items[0], items[i - 1], items[i]]);
+                        }
+                    }
+                    continue;
+                }
+
+                let value = body.read(property.type_)?;
+                if element.name != "vertex" {
+                    continue;
+                }
+
+                // integer colors use the full range of their type
+                let color = |value: f64| {
+                    if property.type_.is_float() { value as f32 } else { (value / ((1u64 << (property.type_.size() * 8)) - 1) as f64) as f32 }
+                };
+
+                match property.name.as_str() {
+                    "x" => vertex.position.x = value as f32,
+                    "y" => vertex.position.y = value as f32,
+                    "z" => vertex.position.z = value as f32,
+                    "nx" => vertex.normal.x = value as f32,
+                    "ny" => vertex.normal.y = value as f32,
+                    "nz" => vertex.normal.z = value as f32,
+                    "red" | "r" => vertex.color.x = color(value),
+                    "green" | "g" => vertex.color.y = color(value),
+                    "blue" | "b" => vertex.color.z = color(value),
+                    "alpha" | "a" => vertex.color.w = color(value),
+                    "s" | "u" | "texture_u" => vertex.tex_coords.x = value as f32,
+                    "t" | "v" | "texture_v" => vertex.tex_coords.y = value as f32,
+                    _ => {}
+                }
+            }
+
+            if element.name == "vertex" {
+                vertices.push(vertex);
+            }
+        }
+
+        if element.name == "face" && element.count > 0 {
+            has_faces = true;
+        }
+    }
+
+    if let Some(&bad) = indices.iter().find(|&&i| i as usize >= vertices.len()) {
+        return Err(format!("PLY face references vertex {} of {}", bad, vertices.len()));
+    }
+
+    Ok(if has_faces {
+        Mesh::new(vertices, indices, gl::TRIANGLES)
+    } else {
+        Mesh::from_vertices(vertices, gl::POINTS)
+    })
+}
+
+pub fn load_ply(path: &Path) -> Result<Mesh, String>
+{
+    let mut file = File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    read_ply(&mut file)
+}
+
+fn write_f32<W: Write>(writer: &mut W, format: PlyFormat, value: f32) -> std::io::Result<()>
+{
+    match format {
+        PlyFormat::Ascii => write!(writer, "{} ", value),
+        PlyFormat::BinaryLittleEndian => writer.write_all(&value.to_le_bytes()),
+        PlyFormat::BinaryBigEndian => writer.write_all(&value.to_be_bytes()),
+    }
+}
+
+fn write_u8<W: Write>(writer: &mut W, format: PlyFormat, value: u8) -> std::io::Result<()>
+{
+    match format {
+        PlyFormat::Ascii => write!(writer, "{} ", value),
+        _ => writer.write_all(&[value]),
+    }
+}
+
+fn write_u32<W: Write>(writer: &mut W, format: PlyFormat, value: u32) -> std::io::Result<()>
+{
+    match format {
+        PlyFormat::Ascii => write!(writer, "{} ", value),
+        PlyFormat::BinaryLittleEndian => writer.write_all(&value.to_le_bytes()),
+        PlyFormat::BinaryBigEndian => writer.write_all(&value.to_be_bytes()),
+    }
+}
+
+// Writes positions, normals, 8 bit colors and texture coordinates of every vertex. Triangle meshes (strips and
+// fans included) get a face element, any other mode is written as a point cloud
+pub fn write_ply<W: Write>(mesh: &Mesh, format: PlyFormat, writer: &mut W) -> std::io::Result<()>
+{
+    let triangles = mesh.triangles();
+
+    writeln!(writer, "ply")?;
+    writeln!(writer, "format {} 1.0", match format {
+        PlyFormat::Ascii => "ascii",
+        PlyFormat::BinaryLittleEndian => "binary_little_endian",
+        PlyFormat::BinaryBigEndian => "binary_big_endian",
+    })?;
+    writeln!(writer, "comment written by rust_opengl")?;
+    writeln!(writer, "element vertex {}", mesh.vertices.len())?;
+    for name in ["x", "y", "z", "nx", "ny", "nz"].iter() {
+        writeln!(writer, "property float {}", name)?;
+    }
+    for name in ["red", "green", "blue", "alpha"].iter() {
+        writeln!(writer, "property uchar {}", name)?;
+    }
+    writeln!(writer, "property float s")?;
+    writeln!(writer, "property float t")?;
+    if !triangles.is_empty() {
+        writeln!(writer, "element face {}", triangles.len())?;
+        writeln!(writer, "property list uchar uint vertex_indices")?;
+    }
+    writeln!(writer, "end_header")?;
+
+    for vertex in &mesh.vertices {
+        for &value in vertex.position.iter().chain(vertex.normal.iter()) {
+            write_f32(writer, format, value)?;
+        }
+        for &value in vertex.color.iter() {
+            write_u8(writer, format, (value.clamp(0.0, 1.0) * 255.0).round() as u8)?;
+        }
+        write_f32(writer, format, vertex.tex_coords.x)?;
+        write_f32(writer, format, vertex.tex_coords.y)?;
+        if format == PlyFormat::Ascii {
+            writeln!(writer)?;
+        }
+    }
+
+    for triangle in &triangles {
+        write_u8(writer, format, 3)?;
+        for &index in triangle.iter() {
+            write_u32(writer, format, index as u32)?;
+        }
+        if format == PlyFormat::Ascii {
+            writeln!(writer)?;
+        }
+    }
+
+    Ok(())
+}
+
+pub fn save_ply(mesh: &Mesh, path: &Path, format: PlyFormat) -> std::io::Result<()>
+{
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_ply(mesh, format, &mut writer)
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    const FORMATS: [PlyFormat; 3] = [PlyFormat::Ascii, PlyFormat::BinaryLittleEndian, PlyFormat::BinaryBigEndian];
+
+    // vertices with distinct positions, normals, 8 bit colors and texture coordinates
+    fn vertices(count: usize) -> Vec<Vertex>
+    {
+        (0..count).map(|i| {
+            let f = i as f32;
+            let mut vertex = Vertex::new(nalgebra_glm::vec3(f * 0.5, -f / 3.0, 1.0e-3 * f), nalgebra_glm::vec2(f / 7.0, 1.0 - f / 9.0));
+            vertex.normal = nalgebra_glm::normalize(&nalgebra_glm::vec3(1.0, f, -2.0));
+            vertex.color = nalgebra_glm::vec4(i as f32 * 20.0 / 255.0, 1.0, 0.0, (255 - i) as f32 / 255.0);
+            vertex
+        }).collect()
+    }
+
+    fn round_trip(mesh: &Mesh, format: PlyFormat) -> Mesh
+    {
+        let mut data = Vec::new();
+        write_ply(mesh, format, &mut data).unwrap();
+        read_ply(&mut &data[..]).unwrap_or_else(|e| panic!("{:?}: {}", format, e))
+    }
+
+    fn assert_same_vertices(read: &Mesh, written: &Mesh)
+    {
+        assert_eq!(read.vertices.len(), written.vertices.len());
+        for (a, b) in read.vertices.iter().zip(&written.vertices) {
+            assert_eq!(a.position, b.position);
+            assert_eq!(a.normal, b.normal);
+            assert_eq!(a.tex_coords, b.tex_coords);
+            assert!(nalgebra_glm::distance(&a.color, &b.color) < 1e-6, "{:?} != {:?}", a.color, b.color);
+        }
+    }
+
+    #[test]
+    fn triangle_mesh_round_trip()
+    {
+        let mesh = Mesh::new(vertices(6), vec![0, 1, 2, 2, 1, 3, 3, 4, 5], gl::TRIANGLES);
+        for &format in FORMATS.iter() {
+            let read = round_trip(&mesh, format);
+            assert_eq!(read.mode, gl::TRIANGLES);
+            assert_eq!(read.indices, mesh.indices);
+            assert_same_vertices(&read, &mesh);
+        }
+    }
+
+    #[test]
+    fn point_cloud_round_trip()
+    {
+        let mesh = Mesh::from_vertices(vertices(10), gl::POINTS);
+        for &format in FORMATS.iter() {
+            let read = round_trip(&mesh, format);
+            assert_eq!(read.mode, gl::POINTS);
+            assert_eq!(read.indices, mesh.indices);
+            assert_same_vertices(&read, &mesh);
+        }
+    }
+
+    #[test]
+    fn polygons_are_triangulated()
+    {
+        let text = "ply\nformat ascii 1.0\nelement vertex 5\nproperty float x\nproperty float y\nproperty float z\n\
+                    element face 1\nproperty list uchar int vertex_index\nend_header\n\
+                    0 0 0\n1 0 0\n1 1 0\n0 1 0\n-1 1 0\n5 0 1 2 3 4\n";
+        let mesh = read_ply(&mut text.as_bytes()).unwrap();
+        assert_eq!(mesh.indices, vec![0, 1, 2, 0, 2, 3, 0, 3, 4]);
+    }
+
+    #[test]
+    fn corrupt_files_fail()
+    {
+        let mesh = Mesh::new(vertices(3), vec![0, 1, 2], gl::TRIANGLES);
+        let mut data = Vec::new();
+        write_ply(&mesh, PlyFormat::BinaryLittleEndian, &mut data).unwrap();
+        assert!(read_ply(&mut &data[..data.len() - 1]).is_err());
+
+        // a list claiming 0xFFFFFFFF items
+        let mut header = b"ply\nformat binary_little_endian 1.0\nelement face 1\nproperty list uint uint vertex_indices\nend_header\n".to_vec();
+        header.extend_from_slice(&u32::MAX.to_le_bytes());
+        header.extend_from_slice(&[0; 12]);
+        assert!(read_ply(&mut &header[..]).is_err());
+
+        // a float count that doesn't fit
+        let mut header = b"ply\nformat binary_big_endian 1.0\nelement face 1\nproperty list float uint vertex_indices\nend_header\n".to_vec();
+        header.extend_from_slice(&1.0e30f32.to_be_bytes());
+        assert!(read_ply(&mut &header[..]).is_err());
+
+        let text = "ply\nformat ascii 1.0\nelement vertex 1\nproperty float x\nelement face 1\n\
+                    property list uchar int vertex_index\nend_header\n0\n3 0 1 2\n";
+        assert!(read_ply(&mut text.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn invalid_indices_fail()
+    {
+        let header = "ply\nformat ascii 1.0\nelement vertex 3\nproperty float x\nproperty float y\nproperty float z\n\
+                      element face 1\nproperty list uchar int vertex_index\nend_header\n0 0 0\n1 0 0\n1 1 0\n";
+        for face in ["3 0 1 -1\n", "3 0 1 3\n", "3 0 1 1.5\n", "3 0 1 4294967296\n"].iter() {
+            let text = format!("{}{}", header, face);
+            assert!(read_ply(&mut text.as_bytes()).is_err(), "{}", face.trim());
+        }
+
+        // a negative index no longer reads as vertex 0 in binary files either
+        let mut data = b"ply\nformat binary_little_endian 1.0\nelement vertex 3\nproperty float x\nproperty float y\n\
+                         property float z\nelement face 1\nproperty list uchar int vertex_indices\nend_header\n".to_vec();
+        data.extend_from_slice(&[0; 36]);
+        data.push(3);
+        for index in [0i32, 1, -2].iter() {
+            data.extend_from_slice(&index.to_le_bytes());
+        }
+        assert_eq!(read_ply(&mut &data[..]).err(), Some("Invalid PLY vertex index -2".to_string()));
+
+        // faces may come before the vertices they reference
+        let text = "ply\nformat ascii 1.0\nelement face 1\nproperty list uchar int vertex_index\nelement vertex 3\n\
+                    property float x\nproperty float y\nproperty float z\nend_header\n3 0 1 2\n0 0 0\n1 0 0\n1 1 0\n";
+        assert_eq!(read_ply(&mut text.as_bytes()).unwrap().indices, vec![0, 1, 2]);
+    }
+}
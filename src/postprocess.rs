@@ -0,0 +1,564 @@
+use gl::types::*;
+use image::RgbaImage;
+use nalgebra_glm::{ Vec2, Vec3, Vec4 };
+
+use std::path::{ Path, PathBuf };
+
+use super::assets::{ AssetManager, Handle };
+use super::bloom::{ Bloom, BloomOptions };
+use super::framebuffer::{ Attachment, AttachmentStorage, Framebuffer, FramebufferDesc };
+use super::hdr_image::{ self, HdrImage };
+use super::shader::Shader;
+use super::tonemap::{ ToneMapOptions, ToneMapping };
+use super::utils;
+
+// the passes `PostProcessPass::standard` knows, in the order they are usually chained
+pub const STANDARD_PASSES: [&str; 6] = ["fxaa", "sharpen", "chromatic_aberration", "color_grading", "grayscale", "vignette"];
+
+// value of a pass uniform, set every time the pass runs
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Uniform {
+    Int(i32),
+    Float(f32),
+    Vec2(Vec2),
+    Vec3(Vec3),
+    Vec4(Vec4),
+    // texture of the given target (gl::TEXTURE_2D, gl::TEXTURE_3D, ...), bound to the next free unit after the input
+    Texture(GLenum, GLuint),
+}
+
+// 3D color lookup table for color grading, sampled with the input color as coordinates
+pub struct ColorLut
+{
+    pub id: GLuint,
+    // entries per channel
+    pub size: u32,
+}
+
+impl ColorLut
+{
+    // `pixels` holds size³ RGBA entries, red varying fastest and blue slowest
+    fn from_pixels(size: u32, pixels: &[u8]) -> ColorLut
+    {
+        let mut id: GLuint = 0;
+        unsafe {
+            gl::GenTextures(1, &mut id);
+            gl::BindTexture(gl::TEXTURE_3D, id);
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            gl::TexImage3D(gl::TEXTURE_3D, 0, gl::RGBA8 as GLint, size as GLsizei, size as GLsizei, size as GLsizei, 0,
+                gl::RGBA, gl::UNSIGNED_BYTE, pixels.as_ptr() as *const std::ffi::c_void);
+            // filtering interpolates between the table entries
+            gl::TexParameteri(gl::TEXTURE_3D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as GLint);
+            gl::TexParameteri(gl::TEXTURE_3D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
+            gl::TexParameteri(gl::TEXTURE_3D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as GLint);
+            gl::TexParameteri(gl::TEXTURE_3D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as GLint);
+            gl::TexParameteri(gl::TEXTURE_3D, gl::TEXTURE_WRAP_R, gl::CLAMP_TO_EDGE as GLint);
+            gl::TexParameteri(gl::TEXTURE_3D, gl::TEXTURE_MAX_LEVEL, 0);
+            gl::BindTexture(gl::TEXTURE_3D, 0);
+        }
+        ColorLut { id, size }
+    }
+
+    // leaves every color unchanged, a starting point for grading in an image editor
+    pub fn identity(size: u32) -> ColorLut
+    {
+        ColorLut::from_image(&identity_strip(size)).expect("Identity LUT strip has the wrong size")
+    }
+
+    // The common strip layout: size x size slices side by side, an image of size² x size. Blue picks the slice,
+    // red grows to the right and green downwards within it
+    pub fn from_image(image: &RgbaImage) -> Result<ColorLut, String>
+    {
+        let (size, pixels) = strip_to_table(image)?;
+        Ok(ColorLut::from_pixels(size, &pixels))
+    }
+
+    pub fn from_file(path: &Path) -> Result<ColorLut, String>
+    {
+        let image = image::open(path).map_err(|e| format!("Failed to load color LUT {}: {}", path.display(), e))?;
+        ColorLut::from_image(&image.to_rgba8()).map_err(|e| format!("Failed to load color LUT {}: {}", path.display(), e))
+    }
+}
+
+impl Drop for ColorLut
+{
+    fn drop(&mut self)
+    {
+        unsafe { gl::DeleteTextures(1, &self.id); }
+    }
+}
+
+// (size, size³ RGBA entries in the order `ColorLut::from_pixels` expects) of a strip in the layout described at
+// `ColorLut::from_image`
+fn strip_to_table(image: &RgbaImage) -> Result<(u32, Vec<u8>), String>
+{
+    let size = image.height();
+    if size < 2 || image.width() != size * size {
+        return Err(format!("A color LUT strip is size² x size pixels, got {}x{}", image.width(), image.height()));
+    }
+
+    let mut pixels = Vec::with_capacity((size * size * size * 4) as usize);
+    for blue in 0..size {
+        for green in 0..size {
+            for red in 0..size {
+                pixels.extend_from_slice(&image.get_pixel(blue * size + red, green).0);
+            }
+        }
+    }
+    Ok((size, pixels))
+}
+
+// identity table in the strip layout `ColorLut::from_image` reads
+pub fn identity_strip(size: u32) -> RgbaImage
+{
+    let scale = 255.0 / (size - 1) as f32;
+    RgbaImage::from_fn(size * size, size, |x, y| {
+        let (red, green, blue) = (x % size, y, x / size);
+        image::Rgba([
+            (red as f32 * scale).round() as u8,
+            (green as f32 * scale).round() as u8,
+            (blue as f32 * scale).round() as u8,
+            255,
+        ])
+    })
+}
+
+// One fullscreen shader pass. The shader reads the previous result from the `image` sampler (unit 0) and gets
+// `texelSize`, one pixel in texture coordinates; everything else comes from `uniforms`
+pub struct PostProcessPass
+{
+    pub name: String,
+    pub shader: Handle<Shader>,
+    pub enabled: bool,
+    pub uniforms: Vec<(String, Uniform)>,
+    // textures owned by the pass, referenced by Uniform::Texture entries
+    luts: Vec<ColorLut>,
+}
+
+impl PostProcessPass
+{
+    // `fragment_path` is paired with the shared fullscreen vertex shader `<shader_dir>/post.vs`
+    pub fn new(assets: &mut AssetManager, shader_dir: &Path, name: &str, fragment_path: &Path) -> Result<PostProcessPass, String>
+    {
+        let shader = assets.shader(&shader_dir.join("post.vs"), fragment_path)?;
+        Ok(PostProcessPass { name: name.to_string(), shader, enabled: true, uniforms: Vec::new(), luts: Vec::new() })
+    }
+
+    // one of STANDARD_PASSES with its default settings, the shader is `<shader_dir>/post_<name>.fs`
+    pub fn standard(assets: &mut AssetManager, shader_dir: &Path, name: &str) -> Result<PostProcessPass, String>
+    {
+        if !STANDARD_PASSES.contains(&name) {
+            return Err(format!("Unknown post processing pass {}, expected one of {}", name, STANDARD_PASSES.join(", ")));
+        }
+
+        let mut pass = PostProcessPass::new(assets, shader_dir, name, &shader_dir.join(format!("post_{}.fs", name)))?;
+        match name {
+            "grayscale" => pass.set("amount", Uniform::Float(1.0)),
+            "sharpen" => pass.set("amount", Uniform::Float(0.3)),
+            "vignette" => {
+                pass.set("strength", Uniform::Float(0.6));
+                pass.set("radius", Uniform::Float(0.45));
+                pass.set("softness", Uniform::Float(0.45));
+            },
+            "chromatic_aberration" => pass.set("strength", Uniform::Float(0.003)),
+            "fxaa" => {
+                pass.set("spanMax", Uniform::Float(8.0));
+                pass.set("reduceMul", Uniform::Float(1.0 / 8.0));
+                pass.set("reduceMin", Uniform::Float(1.0 / 128.0));
+            },
+            "color_grading" => {
+                pass.set("amount", Uniform::Float(1.0));
+                pass.set_lut("lut", ColorLut::identity(16));
+            },
+            _ => {},
+        }
+        Ok(pass)
+    }
+
+    // adds the uniform or replaces its value
+    pub fn set(&mut self, name: &str, value: Uniform)
+    {
+        match self.uniforms.iter_mut().find(|(uniform, _)| uniform == name) {
+            Some((_, current)) => *current = value,
+            None => self.uniforms.push((name.to_string(), value)),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<Uniform>
+    {
+        self.uniforms.iter().find(|(uniform, _)| uniform == name).map(|(_, value)| *value)
+    }
+
+    // binds `lut` to the sampler3D `name`, with its size in `<name>Size`. A previous table is released
+    pub fn set_lut(&mut self, name: &str, lut: ColorLut)
+    {
+        if let Some(Uniform::Texture(_, previous)) = self.get(name) {
+            self.luts.retain(|lut| lut.id != previous);
+        }
+        self.set(name, Uniform::Texture(gl::TEXTURE_3D, lut.id));
+        self.set(&format!("{}Size", name), Uniform::Float(lut.size as f32));
+        self.luts.push(lut);
+    }
+
+    // uses the shader and uploads the uniforms, textures go to units 1 and up
+    fn apply(&self, texel_size: Vec2)
+    {
+        self.shader.use_shader();
+        self.shader.set_int("image", 0);
+        self.shader.set_vec2("texelSize", &texel_size);
+
+        let mut unit = 1;
+        for (name, value) in &self.uniforms {
+            match *value {
+                Uniform::Int(value) => self.shader.set_int(name, value),
+                Uniform::Float(value) => self.shader.set_float(name, value),
+                Uniform::Vec2(value) => self.shader.set_vec2(name, &value),
+                Uniform::Vec3(value) => self.shader.set_vec3(name, &value),
+                Uniform::Vec4(value) => self.shader.set_vec4(name, &value),
+                Uniform::Texture(target, id) => {
+                    unsafe {
+                        gl::ActiveTexture(gl::TEXTURE0 + unit);
+                        gl::BindTexture(target, id);
+                    }
+                    self.shader.set_int(name, unit as GLint);
+                    unit += 1;
+                },
+            }
+        }
+    }
+}
+
+// which passes to chain, from the command line
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PostProcessOptions
+{
+    // STANDARD_PASSES names in the order they run
+    pub passes: Vec<String>,
+    // strip image for the color_grading pass, identity if None
+    pub lut: Option<PathBuf>,
+    // tone map the HDR scene before the passes, without it values over 1 are clipped
+    pub tone_mapping: Option<ToneMapOptions>,
+    // added to the HDR scene before tone mapping
+    pub bloom: Option<BloomOptions>,
+    // MSAA samples of the scene framebuffer, 0 or 1 for none
+    pub samples: u32,
+    // runs the fxaa pass first if `passes` doesn't place it
+    pub fxaa: bool,
+}
+
+impl PostProcessOptions
+{
+    // `--post <pass>,<pass>,...`, `--lut <path>`, `--msaa <samples>`, `--fxaa`, the tone mapping and the bloom options
+    pub fn from_args() -> PostProcessOptions
+    {
+        PostProcessOptions {
+            passes: utils::arg_value("--post")
+                .map(|list| list.split(',').map(|name| name.trim().to_string()).filter(|name| !name.is_empty()).collect())
+                .unwrap_or_default(),
+            lut: utils::arg_value("--lut").map(PathBuf::from),
+            tone_mapping: ToneMapOptions::from_args(),
+            bloom: BloomOptions::from_args(),
+            samples: utils::arg_value("--msaa").and_then(|value| value.parse().ok()).unwrap_or(0),
+            fxaa: utils::has_flag("--fxaa"),
+        }
+    }
+
+    // whether a chain is needed at all. MSAA alone isn't a reason, the window can be multisampled itself
+    pub fn is_enabled(&self) -> bool
+    {
+        !self.passes.is_empty() || self.tone_mapping.is_some() || self.bloom.is_some() || self.fxaa
+    }
+}
+
+// The scene is drawn into an HDR (RGBA16F) framebuffer between `begin` and `apply`, multisampled if MSAA is on.
+// `apply` then resolves it, adds the bloom, runs the tone mapping and the enabled passes in order, ping-ponging
+// between two RGBA16F buffers, and the last one writes straight to the output
+pub struct PostProcessChain
+{
+    pub passes: Vec<PostProcessPass>,
+    // added to the scene before the tone mapping
+    pub bloom: Option<Bloom>,
+    // runs before `passes`, which then see [0, 1] colors
+    pub tone_mapping: Option<ToneMapping>,
+    scene: Framebuffer,
+    // drawn to instead of `scene` with MSAA, resolved into it
+    msaa: Option<Framebuffer>,
+    ping_pong: [Framebuffer; 2],
+    // presents the scene when every pass is disabled
+    copy: Handle<Shader>,
+    // empty, the fullscreen triangle comes from gl_VertexID but the core profile needs a bound vertex array
+    vao: GLuint,
+}
+
+impl PostProcessChain
+{
+    // an empty chain, `shader_dir` holds post.vs and post_copy.fs
+    pub fn new(assets: &mut AssetManager, shader_dir: &Path, width: u32, height: u32) -> Result<PostProcessChain, String>
+    {
+        let copy = assets.shader(&shader_dir.join("post.vs"), &shader_dir.join("post_copy.fs"))?;
+
+        let scene = Framebuffer::with_color_and_depth(width, height, gl::RGBA16F, AttachmentStorage::Texture)?;
+        let ping_pong_desc = FramebufferDesc { color: vec![Attachment::texture(gl::RGBA16F)], depth: None, ..Default::default() };
+        let ping_pong = [
+            Framebuffer::new(width, height, ping_pong_desc.clone())?,
+            Framebuffer::new(width, height, ping_pong_desc)?,
+        ];
+
+        let mut vao: GLuint = 0;
+        unsafe { gl::GenVertexArrays(1, &mut vao); }
+
+        Ok(PostProcessChain { passes: Vec::new(), bloom: None, tone_mapping: None, scene, msaa: None, ping_pong, copy, vao })
+    }
+
+    // a chain of the MSAA, bloom, tone mapping and standard passes in `options`
+    pub fn from_options(assets: &mut AssetManager, shader_dir: &Path, width: u32, height: u32, options: &PostProcessOptions) -> Result<PostProcessChain, String>
+    {
+        let mut chain = PostProcessChain::new(assets, shader_dir, width, height)?;
+        chain.set_samples(options.samples)?;
+        if let Some(bloom) = options.bloom {
+            chain.bloom = Some(Bloom::new(assets, shader_dir, width, height, bloom)?);
+        }
+        if let Some(tone_mapping) = options.tone_mapping {
+            chain.tone_mapping = Some(ToneMapping::new(assets, shader_dir, tone_mapping)?);
+        }
+        for name in &options.passes {
+            let mut pass = PostProcessPass::standard(assets, shader_dir, name)?;
+            if let (Some(path), "color_grading") = (&options.lut, name.as_str()) {
+                pass.set_lut("lut", ColorLut::from_file(path)?);
+            }
+            chain.passes.push(pass);
+        }
+        if options.fxaa {
+            chain.set_fxaa(assets, shader_dir, true)?;
+        }
+        Ok(chain)
+    }
+
+    pub fn width(&self) -> u32
+    {
+        self.scene.width
+    }
+
+    pub fn height(&self) -> u32
+    {
+        self.scene.height
+    }
+
+    // follows the window size, does nothing if it didn't change
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), String>
+    {
+        self.scene.resize(width, height)?;
+        if let Some(msaa) = &mut self.msaa {
+            msaa.resize(width, height)?;
+        }
+        for buffer in &mut self.ping_pong {
+            buffer.resize(width, height)?;
+        }
+        Ok(())
+    }
+
+    pub fn pass(&self, name: &str) -> Option<&PostProcessPass>
+    {
+        self.passes.iter().find(|pass| pass.name == name)
+    }
+
+    pub fn pass_mut(&mut self, name: &str) -> Option<&mut PostProcessPass>
+    {
+        self.passes.iter_mut().find(|pass| pass.name == name)
+    }
+
+    // MSAA samples of the scene, 0 without
+    pub fn samples(&self) -> u32
+    {
+        self.msaa.as_ref().map_or(0, |msaa| msaa.desc.samples)
+    }
+
+    // turns MSAA off with 0 or 1 samples, fails if the driver doesn't support `samples`
+    pub fn set_samples(&mut self, samples: u32) -> Result<(), String>
+    {
+        if samples == self.samples() || (samples <= 1 && self.msaa.is_none()) {
+            return Ok(());
+        }
+        self.msaa = if samples > 1 {
+            Some(Framebuffer::multisampled(self.width(), self.height(), gl::RGBA16F, samples)?)
+        } else {
+            None
+        };
+        Ok(())
+    }
+
+    // turns the fxaa pass on or off, adding it in front of the other passes the first time
+    pub fn set_fxaa(&mut self, assets: &mut AssetManager, shader_dir: &Path, enabled: bool) -> Result<(), String>
+    {
+        match self.pass_mut("fxaa") {
+            Some(pass) => pass.enabled = enabled,
+            None if enabled => self.passes.insert(0, PostProcessPass::standard(assets, shader_dir, "fxaa")?),
+            None => {},
+        }
+        Ok(())
+    }
+
+    pub fn fxaa(&self) -> bool
+    {
+        self.pass("fxaa").is_some_and(|pass| pass.enabled)
+    }
+
+    // binds the HDR scene framebuffer, draw the scene after this
+    pub fn begin(&self)
+    {
+        match &self.msaa {
+            Some(msaa) => msaa.bind(),
+            None => self.scene.bind(),
+        }
+    }
+
+    // the HDR scene color, valid after `begin` and drawing
+    pub fn scene_texture(&self) -> GLuint
+    {
+        self.scene.color_texture(0).unwrap_or(0)
+    }
+
+    // the HDR scene as RGBA floats, top row first, e.g. to dump lighting values before the tone mapping. Valid
+    // between `apply` and the next `begin`, MSAA is resolved by then
+    pub fn read_scene(&self) -> HdrImage
+    {
+        let mut read_framebuffer: GLint = 0;
+        unsafe {
+            gl::GetIntegerv(gl::READ_FRAMEBUFFER_BINDING, &mut read_framebuffer);
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.scene.id);
+        }
+        let image = hdr_image::read_framebuffer(0, 0, self.width(), self.height());
+        unsafe { gl::BindFramebuffer(gl::READ_FRAMEBUFFER, read_framebuffer as GLuint); }
+        image
+    }
+
+    // Resolves MSAA, adds the bloom, runs the tone mapping and the enabled passes over the scene and writes the
+    // result to `output`, or to the default framebuffer (which must have the chain's size) if None. `delta_time` in
+    // seconds paces the exposure adaptation. Depth testing is off meanwhile and restored afterwards
+    pub fn apply(&mut self, output: Option<&Framebuffer>, delta_time: f32)
+    {
+        if let Some(msaa) = &self.msaa {
+            msaa.blit_to(&self.scene, 0, gl::COLOR_BUFFER_BIT, gl::NEAREST);
+        }
+
+        let texel_size = nalgebra_glm::vec2(1.0 / self.width() as f32, 1.0 / self.height() as f32);
+
+        let depth_test = unsafe { gl::IsEnabled(gl::DEPTH_TEST) == gl::TRUE };
+        unsafe {
+            gl::Disable(gl::DEPTH_TEST);
+            gl::BindVertexArray(self.vao);
+        }
+
+        if let Some(bloom) = &mut self.bloom {
+            bloom.apply(&self.scene);
+        }
+        if let Some(tone_mapping) = &mut self.tone_mapping {
+            tone_mapping.update(&self.scene, delta_time);
+        }
+        let enabled: Vec<&PostProcessPass> = self.tone_mapping.iter().map(|tone_mapping| tone_mapping.pass())
+            .chain(self.passes.iter().filter(|pass| pass.enabled))
+            .collect();
+
+        let bind_output = || match output {
+            Some(framebuffer) => framebuffer.bind(),
+            None => Framebuffer::bind_default(self.width(), self.height()),
+        };
+
+        let mut input = &self.scene;
+        if enabled.is_empty() {
+            bind_output();
+            self.copy.use_shader();
+            self.copy.set_int("image", 0);
+            self.draw_fullscreen(input);
+        }
+
+        for (i, pass) in enabled.iter().enumerate() {
+            let target = &self.ping_pong[i % 2];
+            if i + 1 == enabled.len() {
+                bind_output();
+            } else {
+                target.bind();
+            }
+
+            pass.apply(texel_size);
+            self.draw_fullscreen(input);
+            input = target;
+        }
+
+        unsafe {
+            gl::BindVertexArray(0);
+            if depth_test {
+                gl::Enable(gl::DEPTH_TEST);
+            }
+        }
+    }
+
+    // draws the fullscreen triangle with the bound shader, reading `input` on unit 0
+    fn draw_fullscreen(&self, input: &Framebuffer)
+    {
+        input.bind_color_texture(0, 0);
+        unsafe { gl::DrawArrays(gl::TRIANGLES, 0, 3); }
+    }
+}
+
+impl Drop for PostProcessChain
+{
+    fn drop(&mut self)
+    {
+        unsafe { gl::DeleteVertexArrays(1, &self.vao); }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn identity_strip_maps_every_entry_to_itself()
+    {
+        for &size in &[2u32, 5, 16] {
+            let strip = identity_strip(size);
+            assert_eq!(strip.dimensions(), (size * size, size));
+
+            let (table_size, table) = strip_to_table(&strip).unwrap();
+            assert_eq!(table_size, size);
+            assert_eq!(table.len(), (size * size * size * 4) as usize);
+
+            // red varies fastest and blue slowest, the value of each channel follows its own coordinate
+            let scale = 255.0 / (size - 1) as f32;
+            let expected = |i: u32| (i as f32 * scale).round() as u8;
+            for (index, entry) in table.chunks_exact(4).enumerate() {
+                let index = index as u32;
+                let (red, green, blue) = (index % size, index / size % size, index / (size * size));
+                assert_eq!(entry, [expected(red), expected(green), expected(blue), 255], "entry {}", index);
+            }
+        }
+    }
+
+    #[test]
+    fn strip_slices_are_read_in_blue_order()
+    {
+        // every pixel tagged with its position, entry (r, g, b) must come from pixel (b * size + r, g)
+        let size = 3;
+        let strip = RgbaImage::from_fn(size * size, size, |x, y| image::Rgba([x as u8, y as u8, 0, 0]));
+        let (_, table) = strip_to_table(&strip).unwrap();
+
+        let entry = |red: u32, green: u32, blue: u32| {
+            let index = ((blue * size + green) * size + red) as usize * 4;
+            [table[index], table[index + 1]]
+        };
+        assert_eq!(entry(1, 2, 0), [1, 2]);
+        assert_eq!(entry(0, 1, 2), [6, 1]);
+        assert_eq!(entry(2, 0, 1), [5, 0]);
+    }
+
+    #[test]
+    fn malformed_strips_fail()
+    {
+        assert!(strip_to_table(&RgbaImage::new(4, 16)).is_err());
+        assert!(strip_to_table(&RgbaImage::new(15, 4)).is_err());
+        assert!(strip_to_table(&RgbaImage::new(1, 1)).is_err());
+        assert!(strip_to_table(&RgbaImage::new(0, 0)).is_err());
+    }
+}
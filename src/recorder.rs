@@ -0,0 +1,242 @@
+use gl::types::*;
+
+use std::collections::VecDeque;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{ Child, Command, Stdio };
+use std::sync::mpsc::{ sync_channel, SyncSender };
+use std::thread::JoinHandle;
+
+// frames in flight between glReadPixels and mapping the buffer. Three lets the GPU finish a frame while the next two
+// are drawn, so mapping never waits
+const PBO_COUNT: usize = 3;
+
+pub enum RecordingOutput {
+    // numbered PNGs (frame_000000.png, ...) in a directory
+    Frames(PathBuf),
+    // raw RGB24 frames, top row first, written to the stdin of a shell command, e.g.
+    // `ffmpeg -y -f rawvideo -pix_fmt rgb24 -s 800x600 -r 60 -i - demo.mp4`
+    Pipe(String),
+}
+
+type Writer = JoinHandle<Result<(), String>>;
+
+// Encodes on its own thread so PNG compression or a slow encoder doesn't stall rendering. Only a few frames are
+// queued, when the writer falls further behind capturing waits for it instead of buffering frames without limit
+fn spawn_writer(output: RecordingOutput, width: u32, height: u32) -> Result<(SyncSender<Vec<u8>>, Writer), String>
+{
+    let mut child: Option<Child> = None;
+    let frames_dir = match output {
+        RecordingOutput::Frames(dir) => {
+            std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+            Some(dir)
+        },
+        RecordingOutput::Pipe(command) => {
+            let shell = if cfg!(windows) { ("cmd", "/C") } else { ("sh", "-c") };
+            child = Some(Command::new(shell.0).arg(shell.1).arg(&command).stdin(Stdio::piped()).spawn()
+                .map_err(|e| format!("Failed to start {}: {}", command, e))?);
+            None
+        },
+    };
+
+    let (sender, receiver) = sync_channel::<Vec<u8>>(PBO_COUNT);
+    let writer = std::thread::spawn(move || -> Result<(), String> {
+        let row_size = width as usize * 4;
+        let mut stdin = child.as_mut().and_then(|child| child.stdin.take());
+
+        for (index, pixels) in receiver.into_iter().enumerate() {
+            // GL rows start at the bottom
+            let rows = pixels.chunks_exact(row_size).rev();
+
+            if let Some(dir) = &frames_dir {
+                let mut image = image::RgbImage::new(width, height);
+                for (target, row) in image.chunks_exact_mut(width as usize * 3).zip(rows) {
+                    for (rgb, rgba) in target.chunks_exact_mut(3).zip(row.chunks_exact(4)) {
+                        rgb.copy_from_slice(&rgba[0..3]);
+                    }
+                }
+                let path = dir.join(format!("frame_{:06}.png", index));
+                image.save(&path).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+            } else if let Some(stdin) = stdin.as_mut() {
+                let mut frame = Vec::with_capacity(width as usize * height as usize * 3);
+                for row in rows {
+                    for rgba in row.chunks_exact(4) {
+                        frame.extend_from_slice(&rgba[0..3]);
+                    }
+                }
+                stdin.write_all(&frame).map_err(|e| format!("Failed to write frame {} to the encoder: {}", index, e))?;
+            }
+        }
+
+        // closing stdin tells the encoder the video ended
+        drop(stdin);
+        if let Some(mut child) = child {
+            let status = child.wait().map_err(|e| format!("Failed to wait for the encoder: {}", e))?;
+            if !status.success() {
+                return Err(format!("Encoder exited with {}", status));
+            }
+        }
+        Ok(())
+    });
+
+    Ok((sender, writer))
+}
+
+// Records every drawn frame. Time is simulated: frame n shows time n / fps, however long it took to render and
+// capture, so recordings play back smoothly. Pixels go through a ring of pixel pack buffers and are read back a
+// couple of frames later, when the GPU is done with them
+pub struct Recorder
+{
+    pub width: u32,
+    pub height: u32,
+    pub fps: f32,
+    // frames handed to `capture` so far
+    pub frames: u64,
+    pbos: [GLuint; PBO_COUNT],
+    // (pbo index, fence) of the frames being read back, oldest first
+    in_flight: VecDeque<(usize, GLsync)>,
+    sender: Option<SyncSender<Vec<u8>>>,
+    writer: Option<Writer>,
+}
+
+impl Recorder
+{
+    pub fn new(width: u32, height: u32, fps: f32, output: RecordingOutput) -> Result<Recorder, String>
+    {
+        if fps <= 0.0 {
+            return Err(format!("Invalid recording frame rate {}", fps));
+        }
+        let (sender, writer) = spawn_writer(output, width, height)?;
+
+        let mut pbos = [0; PBO_COUNT];
+        unsafe {
+            gl::GenBuffers(PBO_COUNT as GLsizei, pbos.as_mut_ptr());
+            for &pbo in &pbos {
+                gl::BindBuffer(gl::PIXEL_PACK_BUFFER, pbo);
+                gl::BufferData(gl::PIXEL_PACK_BUFFER, (width * height * 4) as GLsizeiptr, std::ptr::null(), gl::STREAM_READ);
+            }
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, 0);
+        }
+
+        Ok(Recorder {
+            width,
+            height,
+            fps,
+            frames: 0,
+            pbos,
+            in_flight: VecDeque::new(),
+            sender: Some(sender),
+            writer: Some(writer),
+        })
+    }
+
+    // simulated time in seconds of the next frame
+    pub fn time(&self) -> f32
+    {
+        self.frames as f32 / self.fps
+    }
+
+    // simulated time between two frames
+    pub fn timestep(&self) -> f32
+    {
+        1.0 / self.fps
+    }
+
+    // Queues the readback of the bound read framebuffer (the back buffer for the window). Call after drawing and
+    // before swapping. Fails once the writer stopped, e.g. because the encoder exited
+    pub fn capture(&mut self) -> Result<(), String>
+    {
+        if self.in_flight.len() == PBO_COUNT {
+            self.retire_oldest()?;
+        }
+
+        let slot = (self.frames % PBO_COUNT as u64) as usize;
+        unsafe {
+            let mut read_framebuffer: GLint = 0;
+            gl::GetIntegerv(gl::READ_FRAMEBUFFER_BINDING, &mut read_framebuffer);
+            if read_framebuffer == 0 {
+                gl::ReadBuffer(gl::BACK);
+            }
+
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, self.pbos[slot]);
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 4);
+            // with a pack buffer bound the pointer is an offset into it and the call returns right away
+            gl::ReadPixels(0, 0, self.width as GLsizei, self.height as GLsizei, gl::RGBA, gl::UNSIGNED_BYTE, std::ptr::null_mut());
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, 0);
+
+            let fence = gl::FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0);
+            self.in_flight.push_back((slot, fence));
+        }
+
+        self.frames += 1;
+        Ok(())
+    }
+
+    // maps the oldest buffer (waiting for its fence if the GPU is behind) and hands the pixels to the writer
+    fn retire_oldest(&mut self) -> Result<(), String>
+    {
+        let (slot, fence) = match self.in_flight.pop_front() {
+            Some(frame) => frame,
+            None => return Ok(()),
+        };
+        let size = self.width as usize * self.height as usize * 4;
+        let mut pixels = vec![0u8; size];
+
+        unsafe {
+            gl::ClientWaitSync(fence, gl::SYNC_FLUSH_COMMANDS_BIT, u64::MAX);
+            gl::DeleteSync(fence);
+
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, self.pbos[slot]);
+            let mapped = gl::MapBufferRange(gl::PIXEL_PACK_BUFFER, 0, size as GLsizeiptr, gl::MAP_READ_BIT);
+            if mapped.is_null() {
+                gl::BindBuffer(gl::PIXEL_PACK_BUFFER, 0);
+                let frame = self.frames - self.in_flight.len() as u64 - 1;
+                return Err(format!("Failed to map the pixel buffer of frame {}", frame));
+            }
+            std::ptr::copy_nonoverlapping(mapped as *const u8, pixels.as_mut_ptr(), size);
+            gl::UnmapBuffer(gl::PIXEL_PACK_BUFFER);
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, 0);
+        }
+
+        let sender = self.sender.as_ref().ok_or("Recording already finished")?;
+        if sender.send(pixels).is_err() {
+            // the writer thread quit, its result says why
+            return Err(self.join_writer().err().unwrap_or_else(|| "Recording writer stopped".to_string()));
+        }
+        Ok(())
+    }
+
+    fn join_writer(&mut self) -> Result<(), String>
+    {
+        self.sender = None;
+        match self.writer.take() {
+            Some(writer) => writer.join().map_err(|_| "Recording writer panicked".to_string())?,
+            None => Ok(()),
+        }
+    }
+
+    // writes the frames still in flight and waits for the files or the encoder to be done
+    pub fn finish(&mut self) -> Result<(), String>
+    {
+        while !self.in_flight.is_empty() {
+            self.retire_oldest()?;
+        }
+        self.join_writer()
+    }
+}
+
+impl Drop for Recorder
+{
+    fn drop(&mut self)
+    {
+        unsafe {
+            for (_, fence) in self.in_flight.drain(..) {
+                gl::DeleteSync(fence);
+            }
+            gl::DeleteBuffers(PBO_COUNT as GLsizei, self.pbos.as_ptr());
+        }
+        // don't leave the writer (and the encoder it waits for) running detached. Its error is lost here, `finish`
+        // reports it
+        let _ = self.join_writer();
+    }
+}
@@ -0,0 +1,200 @@
+use gl::types::*;
+
+use super::texture::{ self, TextureFilter, TextureOptions, TextureWrap };
+use super::utils;
+
+// GL_EXT_texture_filter_anisotropic, core only since 4.6
+const TEXTURE_MAX_ANISOTROPY_EXT: GLenum = 0x84FE;
+const MAX_TEXTURE_MAX_ANISOTROPY_EXT: GLenum = 0x84FF;
+
+// Depth comparison used when sampling a depth texture through a sampler2DShadow
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CompareFunc {
+    Never,
+    Less,
+    LessEqual,
+    Equal,
+    NotEqual,
+    GreaterEqual,
+    Greater,
+    Always,
+}
+
+impl CompareFunc
+{
+    pub fn to_gl(self) -> GLint
+    {
+        (match self {
+            CompareFunc::Never => gl::NEVER,
+            CompareFunc::Less => gl::LESS,
+            CompareFunc::LessEqual => gl::LEQUAL,
+            CompareFunc::Equal => gl::EQUAL,
+            CompareFunc::NotEqual => gl::NOTEQUAL,
+            CompareFunc::GreaterEqual => gl::GEQUAL,
+            CompareFunc::Greater => gl::GREATER,
+            CompareFunc::Always => gl::ALWAYS,
+        }) as GLint
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SamplerOptions
+{
+    pub wrap_s: TextureWrap,
+    pub wrap_t: TextureWrap,
+    pub wrap_r: TextureWrap,
+    pub min_filter: TextureFilter,
+    pub mag_filter: TextureFilter,
+    // None samples level 0 only
+    pub mipmap_filter: Option<TextureFilter>,
+    // added to the computed mip level, negative values sharpen
+    pub lod_bias: f32,
+    pub min_lod: f32,
+    pub max_lod: f32,
+    // Some turns sampling into a depth comparison for shadow maps
+    pub compare: Option<CompareFunc>,
+    // 1 disables anisotropic filtering. Clamped to what the driver supports, ignored without the extension
+    pub max_anisotropy: f32,
+    // used by TextureWrap::ClampToBorder
+    pub border_color: [f32; 4],
+}
+
+impl Default for SamplerOptions
+{
+    fn default() -> SamplerOptions
+    {
+        SamplerOptions {
+            wrap_s: TextureWrap::Repeat,
+            wrap_t: TextureWrap::Repeat,
+            wrap_r: TextureWrap::Repeat,
+            min_filter: TextureFilter::Linear,
+            mag_filter: TextureFilter::Linear,
+            mipmap_filter: Some(TextureFilter::Linear),
+            lod_bias: 0.0,
+            min_lod: -1000.0,
+            max_lod: 1000.0,
+            compare: None,
+            max_anisotropy: 1.0,
+            border_color: [0.0, 0.0, 0.0, 0.0],
+        }
+    }
+}
+
+impl SamplerOptions
+{
+    // depth comparison with everything outside the map unshadowed (depth 1 at the border)
+    pub fn shadow_map() -> SamplerOptions
+    {
+        SamplerOptions {
+            wrap_s: TextureWrap::ClampToBorder,
+            wrap_t: TextureWrap::ClampToBorder,
+            wrap_r: TextureWrap::ClampToBorder,
+            mipmap_filter: None,
+            compare: Some(CompareFunc::LessEqual),
+            border_color: [1.0, 1.0, 1.0, 1.0],
+            ..Default::default()
+        }
+    }
+}
+
+impl From<TextureOptions> for SamplerOptions
+{
+    fn from(options: TextureOptions) -> SamplerOptions
+    {
+        SamplerOptions {
+            wrap_s: options.wrap_s,
+            wrap_t: options.wrap_t,
+            min_filter: options.min_filter,
+            mag_filter: options.mag_filter,
+            mipmap_filter: options.mipmap_filter,
+            ..Default::default()
+        }
+    }
+}
+
+// largest anisotropy the driver accepts, None without anisotropic filtering support
+pub fn max_supported_anisotropy() -> Option<f32>
+{
+    let supported = utils::has_gl_extension("GL_EXT_texture_filter_anisotropic") ||
+        utils::has_gl_extension("GL_ARB_texture_filter_anisotropic") ||
+        utils::gl_version() >= (4, 6);
+    if !supported {
+        return None;
+    }
+
+    let mut max = 1.0f32;
+    unsafe { gl::GetFloatv(MAX_TEXTURE_MAX_ANISOTROPY_EXT, &mut max); }
+    Some(max)
+}
+
+// GL sampler object. A sampler bound to a texture unit overrides the filtering and wrap parameters of whatever
+// texture is bound there, so the same texture can be sampled differently on two units
+pub struct Sampler
+{
+    pub id: GLuint,
+    pub options: SamplerOptions,
+}
+
+impl Sampler
+{
+    pub fn new(options: SamplerOptions) -> Sampler
+    {
+        let mut id: GLuint = 0;
+        unsafe { gl::GenSamplers(1, &mut id); }
+
+        let mut sampler = Sampler { id, options };
+        sampler.set_options(options);
+        sampler
+    }
+
+    pub fn set_options(&mut self, options: SamplerOptions)
+    {
+        self.options = options;
+        let id = self.id;
+
+        unsafe {
+            gl::SamplerParameteri(id, gl::TEXTURE_WRAP_S, options.wrap_s.to_gl());
+            gl::SamplerParameteri(id, gl::TEXTURE_WRAP_T, options.wrap_t.to_gl());
+            gl::SamplerParameteri(id, gl::TEXTURE_WRAP_R, options.wrap_r.to_gl());
+            gl::SamplerParameteri(id, gl::TEXTURE_MIN_FILTER, texture::min_filter_to_gl(options.min_filter, options.mipmap_filter));
+            gl::SamplerParameteri(id, gl::TEXTURE_MAG_FILTER, texture::mag_filter_to_gl(options.mag_filter));
+            gl::SamplerParameterf(id, gl::TEXTURE_LOD_BIAS, options.lod_bias);
+            gl::SamplerParameterf(id, gl::TEXTURE_MIN_LOD, options.min_lod);
+            gl::SamplerParameterf(id, gl::TEXTURE_MAX_LOD, options.max_lod);
+            gl::SamplerParameterfv(id, gl::TEXTURE_BORDER_COLOR, options.border_color.as_ptr());
+
+            match options.compare {
+                Some(func) => {
+                    gl::SamplerParameteri(id, gl::TEXTURE_COMPARE_MODE, gl::COMPARE_REF_TO_TEXTURE as GLint);
+                    gl::SamplerParameteri(id, gl::TEXTURE_COMPARE_FUNC, func.to_gl());
+                },
+                None => gl::SamplerParameteri(id, gl::TEXTURE_COMPARE_MODE, gl::NONE as GLint),
+            }
+        }
+
+        if let Some(max) = max_supported_anisotropy() {
+            let anisotropy = options.max_anisotropy.clamp(1.0, max);
+            unsafe { gl::SamplerParameterf(id, TEXTURE_MAX_ANISOTROPY_EXT, anisotropy); }
+        }
+    }
+
+    // `unit` is 0 for GL_TEXTURE0, ...
+    pub fn bind(&self, unit: u32)
+    {
+        unsafe { gl::BindSampler(unit, self.id); }
+    }
+
+    // back to the parameters of the texture itself
+    pub fn unbind(unit: u32)
+    {
+        unsafe { gl::BindSampler(unit, 0); }
+    }
+}
+
+impl Drop for Sampler
+{
+    fn drop(&mut self)
+    {
+        unsafe { gl::DeleteSamplers(1, &self.id); }
+    }
+}
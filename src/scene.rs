@@ -0,0 +1,320 @@
+use nalgebra_glm::{ Mat4, Vec3 };
+
+use std::path::{ Path, PathBuf };
+
+use super::assets::{ AssetManager, Handle };
+use super::async_loader::{ AsyncHandle, AsyncLoader };
+use super::bounds::{ Aabb, BoundingSphere };
+use super::camera::Camera;
+use super::cubemap::Cubemap;
+use super::gltf_model::GltfModel;
+use super::lighting::{ Attenuation, Light, Lighting, Material };
+use super::mesh::Mesh;
+use super::mesh_normals;
+use super::sampler::{ Sampler, SamplerOptions };
+use super::shader::Shader;
+use super::skybox::Skybox;
+use super::texture::{ Texture2D, TextureOptions };
+use super::utils;
+use super::vertex_shapes;
+
+// What to load besides the cubes, usually taken from the command line
+#[derive(Clone, Debug, PartialEq)]
+pub struct SceneOptions
+{
+    // glTF scene, drawn with the metallic-roughness materials of gltf.fs
+    pub gltf: Option<PathBuf>,
+    // reorder the glTF meshes for the vertex cache and overdraw
+    pub optimize: bool,
+    // build levels of detail for the glTF meshes and pick one by their size on screen
+    pub lod: bool,
+    // either an equirectangular panorama or a directory holding the right, left, top, bottom, front and back faces
+    pub skybox: Option<PathBuf>,
+    // anisotropic filtering level of the cube textures
+    pub anisotropy: f32,
+    // shade the cubes with lit.fs instead of blending the two textures
+    pub lighting: bool,
+}
+
+impl Default for SceneOptions
+{
+    fn default() -> SceneOptions
+    {
+        SceneOptions { gltf: None, optimize: false, lod: false, skybox: None, anisotropy: 16.0, lighting: false }
+    }
+}
+
+impl SceneOptions
+{
+    // `--gltf <path>`, `--optimize`, `--lod`, `--skybox <path>`, `--anisotropy <n>` and `--lighting`
+    pub fn from_args() -> SceneOptions
+    {
+        SceneOptions {
+            gltf: utils::arg_value("--gltf").map(PathBuf::from),
+            optimize: utils::has_flag("--optimize"),
+            lod: utils::has_flag("--lod"),
+            skybox: utils::arg_value("--skybox").map(PathBuf::from),
+            anisotropy: utils::arg_value("--anisotropy").and_then(|value| value.parse().ok()).unwrap_or(16.0),
+            lighting: utils::has_flag("--lighting"),
+        }
+    }
+}
+
+// The cubes of `--lighting`, a container material lit by a sun, an orbiting lamp and a flashlight held by the camera
+struct LitCubes
+{
+    shader: Handle<Shader>,
+    material: Material,
+}
+
+// A `--gltf` scene and the material shader it is drawn with
+struct GltfScene
+{
+    shader: Handle<Shader>,
+    model: GltfModel,
+}
+
+// The textured cubes plus the optional glTF scene and skybox. Rendering only depends on the camera and the time, so
+// the windowed and the headless mode draw the same frames
+pub struct Scene
+{
+    shader: Handle<Shader>,
+    // drawn at every position, with face normals for lit.fs
+    cube: Mesh,
+    cube_positions: [Vec3; 10],
+    texture1: AsyncHandle<Texture2D>,
+    texture2: AsyncHandle<Texture2D>,
+    cube_sampler: Sampler,
+    gltf: Option<GltfScene>,
+    skybox: Option<Skybox>,
+    lit: Option<LitCubes>,
+}
+
+impl Scene
+{
+    // `asset_dir` holds the shaders and textures. The cube textures are decoded by `loader`, they show a placeholder
+    // until it uploaded them
+    pub fn load(assets: &mut AssetManager, loader: &mut AsyncLoader, asset_dir: &Path, options: &SceneOptions) -> Result<Scene, String>
+    {
+        let shader = assets.shader(&asset_dir.join("3.3.shader.vs"), &asset_dir.join("3.3.shader.fs"))?;
+
+        let cube_positions = [
+            nalgebra_glm::vec3( 0.0f32,  0.0,  0.0),
+            nalgebra_glm::vec3( 2.0f32,  5.0, -15.0),
+            nalgebra_glm::vec3(-1.5f32, -2.2, -2.5),
+            nalgebra_glm::vec3(-3.8f32, -2.0, -12.3),
+            nalgebra_glm::vec3( 2.4f32, -0.4, -3.5),
+            nalgebra_glm::vec3(-1.7f32,  3.0, -7.5),
+            nalgebra_glm::vec3( 1.3f32, -2.0, -2.5),
+            nalgebra_glm::vec3( 1.5f32,  2.0, -2.5),
+            nalgebra_glm::vec3( 1.5f32,  0.2, -1.5),
+            nalgebra_glm::vec3(-1.3f32,  1.0, -1.5)
+        ];
+
+        let mut cube = vertex_shapes::get_cube_mesh();
+        mesh_normals::compute_flat_normals(&mut cube);
+        cube.setup_mesh();
+
+        let texture1 = loader.texture(assets, &asset_dir.join("container.jpg"), TextureOptions::default());
+        // rotated by 180 degrees like the original tutorial, which keeps the face upright in OpenGL's bottom-up
+        // texture space and mirrored left-right
+        let texture2 = loader.texture(
+            assets,
+            &asset_dir.join("awesomeface.png"),
+            TextureOptions { flip_vertically: true, flip_horizontally: true, ..Default::default() }
+        );
+        texture2.on_ready(|result| match result {
+            Ok(texture) => println!("Loaded awesomeface.png ({}x{})", texture.width, texture.height),
+            Err(e) => println!("Failed to load awesomeface.png: {}", e),
+        });
+
+        shader.use_shader();
+        shader.set_int("texture1", 0);
+        shader.set_int("texture2", 1);
+
+        // the cube textures are sampled through a sampler object
+        let cube_sampler = Sampler::new(SamplerOptions { max_anisotropy: options.anisotropy, ..Default::default() });
+
+        let gltf = match &options.gltf {
+            Some(path) => {
+                let mut model = GltfModel::load(path)?;
+                if options.optimize {
+                    for report in model.optimize_meshes() {
+                        println!("Optimized mesh: {}", report);
+                    }
+                }
+                if options.lod {
+                    for triangles in model.generate_lods(4, 0.5, 0.05) {
+                        println!("Levels of detail: {:?} triangles", triangles);
+                    }
+                }
+                let shader = assets.shader(&asset_dir.join("gltf.vs"), &asset_dir.join("gltf.fs"))?;
+                Some(GltfScene { shader, model })
+            },
+            None => None,
+        };
+
+        let skybox = match &options.skybox {
+            Some(path) => {
+                let cubemap = if path.is_dir() {
+                    Cubemap::from_directory(path, false)?
+                } else {
+                    Cubemap::from_equirectangular(path, None, false)?
+                };
+                let skybox_shader = assets.shader(&asset_dir.join("skybox.vs"), &asset_dir.join("skybox.fs"))?;
+                Some(Skybox::new(cubemap, skybox_shader))
+            },
+            None => None,
+        };
+
+        let lit = if options.lighting {
+            let lit_shader = assets.shader(&asset_dir.join("lit.vs"), &asset_dir.join("lit.fs"))?;
+            let material = Material {
+                diffuse: texture1.clone(),
+                specular: loader.texture(assets, &asset_dir.join("container_specular.png"), TextureOptions::default()),
+                shininess: 32.0,
+            };
+            Some(LitCubes { shader: lit_shader, material })
+        } else {
+            None
+        };
+
+        Ok(Scene { shader, cube, cube_positions, texture1, texture2, cube_sampler, gltf, skybox, lit })
+    }
+
+    // clears and draws everything into the bound framebuffer. `time` in seconds drives the cube rotation
+    pub fn draw(&self, camera: &Camera, aspect_ratio: f32, time: f32)
+    {
+        unsafe {
+            gl::ClearColor(0.2, 0.3, 0.3, 1.0);
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+        }
+
+        let projection = self.projection(camera, aspect_ratio);
+        let view = camera.get_view_matrix();
+
+        if let Some(lit) = &self.lit {
+            self.draw_lit_cubes(lit, camera, &view, &projection, time);
+        } else {
+            self.draw_cubes(&view, &projection, time);
+        }
+
+        if let Some(gltf) = &self.gltf {
+            gltf.shader.use_shader();
+            gltf.shader.set_mat4("projection", &projection);
+            gltf.shader.set_mat4("view", &view);
+            // the glTF scene shares the lights of `--lighting`, otherwise it gets a sun of its own
+            let lighting = if self.lit.is_some() { Scene::lights(camera, time) } else { Scene::sunlight() };
+            lighting.upload(&gltf.shader, &camera.position);
+            // levels of detail are picked for the height of the target drawn to
+            let mut viewport = [0; 4];
+            unsafe { gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr()); }
+            gltf.model.draw_lod(&gltf.shader, &Mat4::identity(), camera, viewport[3] as f32);
+        }
+
+        // drawn last so it is only shaded where no geometry was drawn
+        if let Some(skybox) = &self.skybox {
+            skybox.draw(&view, &projection);
+        }
+    }
+
+    // rotation of cube `index` at `time`
+    fn cube_model(&self, index: usize, time: f32) -> Mat4
+    {
+        let model = nalgebra_glm::translation(&self.cube_positions[index]);
+        let angle = 20.0f32 * (index + 1) as f32;
+        nalgebra_glm::rotate(&model, time * utils::degree_to_radian(angle), &nalgebra_glm::vec3(1.0f32, 0.3, 0.5))
+    }
+
+    // world space bounds of every cube at `time`
+    pub fn cube_bounds(&self, time: f32) -> Vec<(Aabb, BoundingSphere)>
+    {
+        (0..self.cube_positions.len()).map(|i| self.cube.world_bounds(&self.cube_model(i, time))).collect()
+    }
+
+    fn draw_cubes(&self, view: &Mat4, projection: &Mat4, time: f32)
+    {
+        // bind textures on corresponding texture units
+        self.texture1.bind(0);
+        self.texture2.bind(1);
+        self.cube_sampler.bind(0);
+        self.cube_sampler.bind(1);
+
+        self.shader.use_shader();
+        self.shader.set_mat4("projection", projection);
+        self.shader.set_mat4("view", view);
+
+        for i in 0..self.cube_positions.len() {
+            self.shader.set_mat4("model", &self.cube_model(i, time));
+            self.cube.draw(&self.shader);
+        }
+        // the glTF materials and the skybox use the parameters of their own textures
+        Sampler::unbind(0);
+        Sampler::unbind(1);
+    }
+
+    fn draw_lit_cubes(&self, lit: &LitCubes, camera: &Camera, view: &Mat4, projection: &Mat4, time: f32)
+    {
+        lit.shader.use_shader();
+        lit.shader.set_mat4("projection", projection);
+        lit.shader.set_mat4("view", view);
+        Scene::lights(camera, time).upload(&lit.shader, &camera.position);
+
+        lit.material.bind(&lit.shader, 0);
+        self.cube_sampler.bind(0);
+        self.cube_sampler.bind(1);
+
+        for i in 0..self.cube_positions.len() {
+            lit.shader.set_mat4("model", &self.cube_model(i, time));
+            self.cube.draw(&lit.shader);
+        }
+        Sampler::unbind(0);
+        Sampler::unbind(1);
+    }
+
+    // the lights of `--lighting` at `time`, rebuilt every frame since the lamp moves and the flashlight follows the
+    // camera
+    pub fn lights(camera: &Camera, time: f32) -> Lighting
+    {
+        let lamp_angle = time * 0.5;
+        Lighting {
+            ambient: nalgebra_glm::vec3(0.05, 0.05, 0.06),
+            lights: vec![
+                Light::Directional {
+                    direction: nalgebra_glm::vec3(-0.2, -1.0, -0.3),
+                    color: nalgebra_glm::vec3(0.6, 0.6, 0.5),
+                },
+                Light::Point {
+                    position: nalgebra_glm::vec3(4.0 * lamp_angle.cos(), 1.5, -6.0 + 4.0 * lamp_angle.sin()),
+                    color: nalgebra_glm::vec3(2.0, 1.2, 0.6),
+                    attenuation: Attenuation::for_range(20.0),
+                },
+                Light::Spot {
+                    position: camera.position,
+                    direction: camera.front,
+                    color: nalgebra_glm::vec3(1.0, 1.0, 1.0),
+                    attenuation: Attenuation::default(),
+                    inner_angle: 12.5,
+                    outer_angle: 17.5,
+                },
+            ],
+        }
+    }
+
+    // lights the glTF scene without `--lighting`
+    pub fn sunlight() -> Lighting
+    {
+        Lighting {
+            ambient: nalgebra_glm::vec3(0.15, 0.15, 0.17),
+            lights: vec![Light::Directional {
+                direction: nalgebra_glm::vec3(-0.4, -1.0, -0.6),
+                color: nalgebra_glm::vec3(1.0, 0.98, 0.92),
+            }],
+        }
+    }
+
+    pub fn projection(&self, camera: &Camera, aspect_ratio: f32) -> Mat4
+    {
+        nalgebra_glm::perspective(utils::degree_to_radian(camera.zoom), aspect_ratio, 0.1f32, 100.0f32)
+    }
+}
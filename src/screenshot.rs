@@ -0,0 +1,93 @@
+use gl::types::*;
+use image::buffer::ConvertBuffer;
+use image::{ RgbImage, RgbaImage };
+
+use std::path::{ Path, PathBuf };
+use std::time::{ SystemTime, UNIX_EPOCH };
+
+// Reads `width` x `height` pixels of the bound read framebuffer, top row first. With the default framebuffer bound
+// this is the back buffer, i.e. the frame about to be swapped. Alpha is forced to opaque since window alpha is
+// meaningless in an image viewer
+pub fn capture_frame(width: u32, height: u32) -> RgbaImage
+{
+    let mut pixels = vec![0u8; width as usize * height as usize * 4];
+    unsafe {
+        let mut read_framebuffer: GLint = 0;
+        gl::GetIntegerv(gl::READ_FRAMEBUFFER_BINDING, &mut read_framebuffer);
+        if read_framebuffer == 0 {
+            gl::ReadBuffer(gl::BACK);
+        }
+
+        gl::PixelStorei(gl::PACK_ALIGNMENT, 4);
+        gl::ReadPixels(0, 0, width as GLsizei, height as GLsizei, gl::RGBA, gl::UNSIGNED_BYTE,
+            pixels.as_mut_ptr() as *mut std::ffi::c_void);
+    }
+
+    for pixel in pixels.chunks_exact_mut(4) {
+        pixel[3] = 255;
+    }
+
+    let image = RgbaImage::from_raw(width, height, pixels).unwrap();
+    // GL returns the bottom row first
+    image::imageops::flip_vertical(&image)
+}
+
+// PNG or JPEG by the extension of `path`. JPEG has no alpha, it is dropped
+pub fn save_image(image: &RgbaImage, path: &Path) -> Result<(), String>
+{
+    let extension = path.extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase());
+    let result = match extension.as_deref() {
+        Some("png") => image.save(path),
+        Some("jpg") | Some("jpeg") => {
+            let rgb: RgbImage = image.convert();
+            rgb.save(path)
+        },
+        _ => return Err(format!("Unsupported screenshot format: {}", path.display())),
+    };
+    result.map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+// (year, month, day) of a day count since 1970-01-01, from Howard Hinnant's civil_from_days
+fn civil_from_days(days: i64) -> (i64, u32, u32)
+{
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+// `<dir>/screenshot_YYYYMMDD_HHMMSS_mmm.<extension>` for the current UTC time. Milliseconds keep quick successive
+// captures apart, a counter is appended if the file exists anyway
+pub fn timestamped_path(dir: &Path, extension: &str) -> PathBuf
+{
+    let since_epoch = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = since_epoch.as_secs() as i64;
+    let (year, month, day) = civil_from_days(seconds.div_euclid(86400));
+    let time_of_day = seconds.rem_euclid(86400);
+
+    let name = format!("screenshot_{:04}{:02}{:02}_{:02}{:02}{:02}_{:03}",
+        year, month, day, time_of_day / 3600, time_of_day / 60 % 60, time_of_day % 60, since_epoch.subsec_millis());
+
+    let mut path = dir.join(format!("{}.{}", name, extension));
+    let mut counter = 1;
+    while path.exists() {
+        path = dir.join(format!("{}_{}.{}", name, counter, extension));
+        counter += 1;
+    }
+    path
+}
+
+// captures the bound read framebuffer into a new timestamped file in `dir` (created if needed)
+pub fn take_screenshot(dir: &Path, extension: &str, width: u32, height: u32) -> Result<PathBuf, String>
+{
+    std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let path = timestamped_path(dir, extension);
+    save_image(&capture_frame(width, height), &path)?;
+    Ok(path)
+}
@@ -120,6 +120,36 @@ impl Shader
         }
     }
 
+    pub fn set_vec2(&self, name: &str, value: &nalgebra_glm::Vec2)
+    {
+        unsafe {
+            gl::Uniform2f(
+                gl::GetUniformLocation( self.id, super::utils::new_c_string(name).as_ptr() ),
+                value.x, value.y
+            );
+        }
+    }
+
+    pub fn set_vec3(&self, name: &str, value: &nalgebra_glm::Vec3)
+    {
+        unsafe {
+            gl::Uniform3f(
+                gl::GetUniformLocation( self.id, super::utils::new_c_string(name).as_ptr() ),
+                value.x, value.y, value.z
+            );
+        }
+    }
+
+    pub fn set_vec4(&self, name: &str, value: &nalgebra_glm::Vec4)
+    {
+        unsafe {
+            gl::Uniform4f(
+                gl::GetUniformLocation( self.id, super::utils::new_c_string(name).as_ptr() ),
+                value.x, value.y, value.z, value.w
+            );
+        }
+    }
+
     pub fn set_mat4(&self, name: &str, value: &nalgebra_glm::TMat4<f32>)
     {
         unsafe {
@@ -163,4 +193,14 @@ impl Shader
             }
         }
     }
+}
+
+impl Drop for Shader
+{
+    fn drop(&mut self)
+    {
+        if self.id != 0 {
+            unsafe { gl::DeleteProgram(self.id); }
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,83 @@
+use gl::types::*;
+use nalgebra_glm::{ Mat4 };
+
+use std::mem;
+use std::ptr;
+
+use super::assets::Handle;
+use super::cubemap::Cubemap;
+use super::shader::Shader;
+use super::vertex_shapes;
+
+// Environment background drawn as a unit cube around the camera
+pub struct Skybox
+{
+    pub cubemap: Cubemap,
+    shader: Handle<Shader>,
+    vao: GLuint,
+    vbo: GLuint,
+}
+
+impl Skybox
+{
+    // `shader` is expected to be assets/skybox.vs and assets/skybox.fs
+    pub fn new(cubemap: Cubemap, shader: Handle<Shader>) -> Skybox
+    {
+        // only the positions of the textured cube are used, they double as cube map directions
+        let vertices = vertex_shapes::get_cube();
+        let mut vao: GLuint = 0;
+        let mut vbo: GLuint = 0;
+
+        unsafe {
+            gl::GenVertexArrays(1, &mut vao);
+            gl::GenBuffers(1, &mut vbo);
+
+            gl::BindVertexArray(vao);
+            gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+            gl::BufferData(gl::ARRAY_BUFFER, mem::size_of_val(&vertices) as isize, vertices.as_ptr() as *const std::ffi::c_void, gl::STATIC_DRAW);
+
+            gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, 5 * mem::size_of::<f32>() as i32, ptr::null());
+            gl::EnableVertexAttribArray(0);
+
+            gl::BindVertexArray(0);
+        }
+
+        shader.use_shader();
+        shader.set_int("skybox", 0);
+
+        Skybox { cubemap, shader, vao, vbo }
+    }
+
+    // draws after the opaque geometry so only the pixels left at the far plane are shaded. The translation is
+    // removed from the view matrix so the box stays centered on the camera
+    pub fn draw(&self, view: &Mat4, projection: &Mat4)
+    {
+        let rotation = nalgebra_glm::mat3_to_mat4(&nalgebra_glm::mat4_to_mat3(view));
+
+        self.shader.use_shader();
+        self.shader.set_mat4("view", &rotation);
+        self.shader.set_mat4("projection", projection);
+
+        self.cubemap.bind(0);
+
+        unsafe {
+            // the cube is drawn at depth 1.0, which fails the default LESS test against the cleared depth buffer
+            gl::DepthFunc(gl::LEQUAL);
+            gl::BindVertexArray(self.vao);
+            gl::DrawArrays(gl::TRIANGLES, 0, 36);
+            gl::BindVertexArray(0);
+            gl::DepthFunc(gl::LESS);
+        }
+    }
+}
+
+impl Drop for Skybox
+{
+    fn drop(&mut self)
+    {
+        unsafe {
+            gl::DeleteVertexArrays(1, &self.vao);
+            gl::DeleteBuffers(1, &self.vbo);
+        }
+    }
+}
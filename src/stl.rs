@@ -0,0 +1,266 @@
+use nalgebra_glm::{ Vec3 };
+
+use std::fs::File;
+use std::io::{ BufWriter, Read, Write };
+use std::path::Path;
+
+use super::mesh::{ Mesh, Vertex };
+
+// size of the binary header before the triangle count
+const HEADER_SIZE: usize = 80;
+// normal, three vertices and the attribute byte count
+const FACET_SIZE: usize = 50;
+
+fn facet_normal(p: &[Vec3; 3]) -> Vec3
+{
+    let n = nalgebra_glm::cross(&(p[1] - p[0]), &(p[2] - p[0]));
+    let len = nalgebra_glm::length(&n);
+    if len <= f32::EPSILON { n } else { n / len }
+}
+
+// appends the three vertices of a facet, using the stored normal unless it is missing (all zeros)
+fn push_facet(vertices: &mut Vec<Vertex>, normal: Vec3, corners: [Vec3; 3])
+{
+    let normal = if nalgebra_glm::length(&normal) <= f32::EPSILON { facet_normal(&corners) } else { normal };
+
+    for p in corners.iter() {
+        let mut vertex = Vertex::new(*p, nalgebra_glm::vec2(0.0f32, 0.0));
+        vertex.normal = normal;
+        vertices.push(vertex);
+    }
+}
+
+// Reads an ASCII or binary STL. Every facet gets its own three vertices carrying the facet normal, use
+// mesh_optimizer::weld_vertices afterwards to share them
+pub fn read_stl<R: Read>(reader: &mut R) -> Result<Mesh, String>
+{
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data).map_err(|e| format!("Failed to read STL: {}", e))?;
+
+    // binary files may also start with "solid", so the size has to match the triangle count to be binary
+    let binary_size_matches = data.len() >= HEADER_SIZE + 4 && {
+        let count = u32::from_le_bytes([data[80], data[81], data[82], data[83]]) as usize;
+        data.len() == HEADER_SIZE + 4 + count * FACET_SIZE
+    };
+
+    if data.starts_with(b"solid") && !binary_size_matches {
+        read_ascii(&data)
+    } else {
+        read_binary(&data)
+    }
+}
+
+pub fn load_stl(path: &Path) -> Result<Mesh, String>
+{
+    let mut file = File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    read_stl(&mut file)
+}
+
+fn read_binary(data: &[u8]) -> Result<Mesh, String>
+{
+    if data.len() < HEADER_SIZE + 4 {
+        return Err("STL file too short".to_string());
+    }
+
+    let count = u32::from_le_bytes([data[80], data[81], data[82], data[83]]) as usize;
+    if data.len() < HEADER_SIZE + 4 + count * FACET_SIZE {
+        return Err(format!("Binary STL truncated: {} triangles declared", count));
+    }
+
+    let read_vec3 = |offset: usize| {
+        let f = |i: usize| {
+            let o = offset + i * 4;
+            f32::from_le_bytes([data[o], data[o + 1], data[o + 2], data[o + 3]])
+        };
+        nalgebra_glm::vec3(f(0), f(1), f(2))
+    };
+
+    let mut vertices = Vec::with_capacity(count * 3);
+    for facet in 0..count {
+        let offset = HEADER_SIZE + 4 + facet * FACET_SIZE;
+        push_facet(
+            &mut vertices,
+            read_vec3(offset),
+            [read_vec3(offset + 12), read_vec3(offset + 24), read_vec3(offset + 36)]
+        );
+    }
+
+    Ok(Mesh::from_vertices(vertices, gl::TRIANGLES))
+}
+
+fn next_vec3(tokens: &mut std::str::SplitWhitespace) -> Result<Vec3, String>
+{
+    let mut v = [0.0f32; 3];
+    for value in v.iter_mut() {
+        let token = tokens.next().ok_or("Unexpected end of ASCII STL")?;
+        *value = token.parse().map_err(|_| format!("Invalid number in ASCII STL: {}", token))?;
+    }
+    Ok(nalgebra_glm::vec3(v[0], v[1], v[2]))
+}
+
+fn read_ascii(data: &[u8]) -> Result<Mesh, String>
+{
+    let text = std::str::from_utf8(data).map_err(|_| "ASCII STL is not valid UTF-8".to_string())?;
+    let mut tokens = text.split_whitespace();
+
+    let mut vertices = Vec::new();
+    let mut normal = nalgebra_glm::vec3(0.0f32, 0.0, 0.0);
+    let mut corners = Vec::with_capacity(3);
+
+    while let Some(token) = tokens.next() {
+        match token {
+            "normal" => normal = next_vec3(&mut tokens)?,
+            "vertex" => corners.push(next_vec3(&mut tokens)?),
+            "endfacet" => {
+                if corners.len() != 3 {
+                    return Err(format!("ASCII STL facet with {} vertices", corners.len()));
+                }
+                push_facet(&mut vertices, normal, [corners[0], corners[1], corners[2]]);
+                corners.clear();
+                normal = nalgebra_glm::vec3(0.0f32, 0.0, 0.0);
+            },
+            _ => {}
+        }
+    }
+
+    Ok(Mesh::from_vertices(vertices, gl::TRIANGLES))
+}
+
+fn triangle_positions(mesh: &Mesh) -> Vec<[Vec3; 3]>
+{
+    mesh.triangles().iter()
+        .map(|&[a, b, c]| [mesh.vertices[a].position, mesh.vertices[b].position, mesh.vertices[c].position])
+        .collect()
+}
+
+// Writes the triangles of the mesh as binary STL. Only positions are stored, facet normals are recomputed
+pub fn write_stl_binary<W: Write>(mesh: &Mesh, writer: &mut W) -> std::io::Result<()>
+{
+    let triangles = triangle_positions(mesh);
+
+    let mut header = [0u8; HEADER_SIZE];
+    let label = b"binary STL written by rust_opengl";
+    header[..label.len()].copy_from_slice(label);
+    writer.write_all(&header)?;
+    writer.write_all(&(triangles.len() as u32).to_le_bytes())?;
+
+    for corners in &triangles {
+        let normal = facet_normal(corners);
+        for v in std::iter::once(&normal).chain(corners.iter()) {
+            for i in 0..3 {
+                writer.write_all(&v[i].to_le_bytes())?;
+            }
+        }
+        writer.write_all(&0u16.to_le_bytes())?;
+    }
+
+    Ok(())
+}
+
+pub fn write_stl_ascii<W: Write>(mesh: &Mesh, name: &str, writer: &mut W) -> std::io::Result<()>
+{
+    writeln!(writer, "solid {}", name)?;
+    for corners in triangle_positions(mesh) {
+        let n = facet_normal(&corners);
+        writeln!(writer, "  facet normal {:e} {:e} {:e}", n.x, n.y, n.z)?;
+        writeln!(writer, "    outer loop")?;
+        for p in corners.iter() {
+            writeln!(writer, "      vertex {:e} {:e} {:e}", p.x, p.y, p.z)?;
+        }
+        writeln!(writer, "    endloop")?;
+        writeln!(writer, "  endfacet")?;
+    }
+    writeln!(writer, "endsolid {}", name)
+}
+
+pub fn save_stl(mesh: &Mesh, path: &Path, binary: bool) -> std::io::Result<()>
+{
+    let mut writer = BufWriter::new(File::create(path)?);
+    if binary {
+        write_stl_binary(mesh, &mut writer)
+    } else {
+        let name = path.file_stem().and_then(|s| s.to_str()).unwrap_or("mesh");
+        write_stl_ascii(mesh, name, &mut writer)
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use super::super::vertex_shapes;
+    use super::super::mesh_normals;
+
+    fn cube() -> Mesh
+    {
+        let mut mesh = vertex_shapes::get_cube_mesh();
+        // off-center and with values that need every digit to survive
+        for vertex in mesh.vertices.iter_mut() {
+            vertex.position = vertex.position * 1.7 + nalgebra_glm::vec3(0.1, -2.3, 1.0 / 3.0);
+        }
+        mesh_normals::compute_flat_normals(&mut mesh);
+        mesh
+    }
+
+    fn assert_same_triangles(read: &Mesh, written: &Mesh)
+    {
+        assert_eq!(read.mode, gl::TRIANGLES);
+        assert_eq!(triangle_positions(read), triangle_positions(written));
+        for (a, b) in read.vertices.iter().zip(&written.vertices) {
+            assert!(nalgebra_glm::distance(&a.normal, &b.normal) < 1e-6, "{:?} != {:?}", a.normal, b.normal);
+        }
+    }
+
+    #[test]
+    fn binary_round_trip()
+    {
+        let mesh = cube();
+        let mut data = Vec::new();
+        write_stl_binary(&mesh, &mut data).unwrap();
+        assert_eq!(data.len(), HEADER_SIZE + 4 + 12 * FACET_SIZE);
+
+        assert_same_triangles(&read_stl(&mut &data[..]).unwrap(), &mesh);
+    }
+
+    #[test]
+    fn binary_starting_with_solid()
+    {
+        let mesh = cube();
+        let mut data = Vec::new();
+        write_stl_binary(&mesh, &mut data).unwrap();
+        data[..5].copy_from_slice(b"solid");
+
+        assert_same_triangles(&read_stl(&mut &data[..]).unwrap(), &mesh);
+    }
+
+    #[test]
+    fn ascii_round_trip()
+    {
+        let mesh = cube();
+        let mut data = Vec::new();
+        write_stl_ascii(&mesh, "cube", &mut data).unwrap();
+        assert!(data.starts_with(b"solid cube"));
+
+        assert_same_triangles(&read_stl(&mut &data[..]).unwrap(), &mesh);
+    }
+
+    #[test]
+    fn missing_normals_are_computed()
+    {
+        let text = "solid t\nfacet normal 0 0 0\nouter loop\nvertex 0 0 0\nvertex 1 0 0\nvertex 0 1 0\nendloop\nendfacet\nendsolid t\n";
+        let mesh = read_stl(&mut text.as_bytes()).unwrap();
+        assert_eq!(mesh.vertices[0].normal, nalgebra_glm::vec3(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn truncated_files_fail()
+    {
+        let mut data = Vec::new();
+        write_stl_binary(&cube(), &mut data).unwrap();
+        assert!(read_stl(&mut &data[..data.len() - 1]).is_err());
+        assert!(read_stl(&mut &data[..40]).is_err());
+
+        let text = "solid t\nfacet normal 0 0 1\nouter loop\nvertex 0 0 0\nvertex 1 0\n";
+        assert!(read_stl(&mut text.as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,23 @@
+// Fixture helpers shared by the dds and ktx2 tests
+
+use super::bcn;
+use super::compressed_texture::CompressedImage;
+
+// the fixtures fill every image with a single color
+pub fn assert_solid(image: &CompressedImage, level: usize, index: usize, color: [u8; 4])
+{
+    let level = &image.levels[level];
+    let pixels = bcn::decode_image(image.format, level.width, level.height, &level.images[index]).unwrap();
+    assert_eq!(pixels.len(), (level.width * level.height * 4) as usize);
+    for texel in pixels.chunks_exact(4) {
+        assert_eq!(texel, color);
+    }
+}
+
+// copy of `data` with a little endian u32 patched in, for corrupting headers
+pub fn with_u32(data: &[u8], offset: usize, value: u32) -> Vec<u8>
+{
+    let mut data = data.to_vec();
+    data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    data
+}
@@ -0,0 +1,560 @@
+use gl::types::*;
+use image::{ DynamicImage, GenericImageView };
+
+use std::path::Path;
+
+use super::hdr_image::{ self, HdrImage };
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TextureWrap {
+    Repeat,
+    MirroredRepeat,
+    ClampToEdge,
+    ClampToBorder,
+}
+
+impl TextureWrap
+{
+    pub fn to_gl(self) -> GLint
+    {
+        (match self {
+            TextureWrap::Repeat => gl::REPEAT,
+            TextureWrap::MirroredRepeat => gl::MIRRORED_REPEAT,
+            TextureWrap::ClampToEdge => gl::CLAMP_TO_EDGE,
+            TextureWrap::ClampToBorder => gl::CLAMP_TO_BORDER,
+        }) as GLint
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TextureFilter {
+    Nearest,
+    Linear,
+}
+
+// GL minification filter for a texel filter and an optional filter between mip levels
+pub fn min_filter_to_gl(filter: TextureFilter, mipmap_filter: Option<TextureFilter>) -> GLint
+{
+    (match (filter, mipmap_filter) {
+        (TextureFilter::Nearest, None) => gl::NEAREST,
+        (TextureFilter::Linear, None) => gl::LINEAR,
+        (TextureFilter::Nearest, Some(TextureFilter::Nearest)) => gl::NEAREST_MIPMAP_NEAREST,
+        (TextureFilter::Linear, Some(TextureFilter::Nearest)) => gl::LINEAR_MIPMAP_NEAREST,
+        (TextureFilter::Nearest, Some(TextureFilter::Linear)) => gl::NEAREST_MIPMAP_LINEAR,
+        (TextureFilter::Linear, Some(TextureFilter::Linear)) => gl::LINEAR_MIPMAP_LINEAR,
+    }) as GLint
+}
+
+pub fn mag_filter_to_gl(filter: TextureFilter) -> GLint
+{
+    (match filter {
+        TextureFilter::Nearest => gl::NEAREST,
+        TextureFilter::Linear => gl::LINEAR,
+    }) as GLint
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TextureOptions
+{
+    pub wrap_s: TextureWrap,
+    pub wrap_t: TextureWrap,
+    pub min_filter: TextureFilter,
+    pub mag_filter: TextureFilter,
+    // filter between mip levels. None disables mipmapping, otherwise the mip chain is generated on upload
+    pub mipmap_filter: Option<TextureFilter>,
+    // images are stored top row first while OpenGL expects the bottom row first
+    pub flip_vertically: bool,
+    // mirrors the image left-right, together with `flip_vertically` this rotates it by 180 degrees
+    pub flip_horizontally: bool,
+    // color textures authored in sRGB should set this so sampling returns linear values
+    pub srgb: bool,
+}
+
+impl Default for TextureOptions
+{
+    fn default() -> TextureOptions
+    {
+        TextureOptions {
+            wrap_s: TextureWrap::Repeat,
+            wrap_t: TextureWrap::Repeat,
+            min_filter: TextureFilter::Linear,
+            mag_filter: TextureFilter::Linear,
+            mipmap_filter: Some(TextureFilter::Linear),
+            flip_vertically: false,
+            flip_horizontally: false,
+            srgb: false,
+        }
+    }
+}
+
+// Storage of floating point textures
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FloatPrecision {
+    // 16 bit half floats, enough for color and lighting data
+    Half,
+    Full,
+}
+
+// GL upload description of a block of pixels
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PixelFormat
+{
+    pub internal_format: GLenum,
+    pub format: GLenum,
+    pub type_: GLenum,
+    // bytes per pixel of the source data
+    pub pixel_size: usize,
+}
+
+// Picks the GL format for an image color type. BGR images are converted by the caller
+fn pixel_format(color: image::ColorType, srgb: bool) -> Option<PixelFormat>
+{
+    use image::ColorType;
+
+    let (internal_format, format, type_, pixel_size) = match color {
+        ColorType::L8 => (gl::R8, gl::RED, gl::UNSIGNED_BYTE, 1),
+        ColorType::La8 => (gl::RG8, gl::RG, gl::UNSIGNED_BYTE, 2),
+        ColorType::Rgb8 => (if srgb { gl::SRGB8 } else { gl::RGB8 }, gl::RGB, gl::UNSIGNED_BYTE, 3),
+        ColorType::Rgba8 => (if srgb { gl::SRGB8_ALPHA8 } else { gl::RGBA8 }, gl::RGBA, gl::UNSIGNED_BYTE, 4),
+        // there are no 16 bit sRGB formats, such images are expected to be linear
+        ColorType::L16 => (gl::R16, gl::RED, gl::UNSIGNED_SHORT, 2),
+        ColorType::La16 => (gl::RG16, gl::RG, gl::UNSIGNED_SHORT, 4),
+        ColorType::Rgb16 => (gl::RGB16, gl::RGB, gl::UNSIGNED_SHORT, 6),
+        ColorType::Rgba16 => (gl::RGBA16, gl::RGBA, gl::UNSIGNED_SHORT, 8),
+        _ => return None,
+    };
+
+    Some(PixelFormat { internal_format, format, type_, pixel_size })
+}
+
+// largest unpack alignment (8, 4, 2 or 1) dividing the row size. Odd-width RGB images need 1
+pub fn unpack_alignment(width: u32, pixel_size: usize) -> GLint
+{
+    let row_size = width as usize * pixel_size;
+    if row_size == 0 {
+        return 4;
+    }
+    1 << row_size.trailing_zeros().min(3)
+}
+
+// number of levels in a full mip chain down to 1x1
+pub fn mip_level_count(width: u32, height: u32) -> u32
+{
+    32 - width.max(height).max(1).leading_zeros()
+}
+
+// size of one texel in GPU memory for the uncompressed internal formats used here
+pub fn internal_format_size(internal_format: GLenum) -> usize
+{
+    match internal_format {
+        gl::R8 => 1,
+        gl::RG8 | gl::R16 | gl::R16F => 2,
+        gl::RGB8 | gl::SRGB8 => 3,
+        gl::RGBA8 | gl::SRGB8_ALPHA8 | gl::RG16 | gl::R32F => 4,
+        gl::RGB16 | gl::RGB16F => 6,
+        gl::RGBA16 | gl::RGBA16F => 8,
+        gl::RGB32F => 12,
+        gl::RGBA32F => 16,
+        _ => 4,
+    }
+}
+
+// Decoded image file, not uploaded yet. Decoding doesn't touch GL so it can run on any thread
+pub enum TextureData {
+    Image(DynamicImage),
+    Float(HdrImage),
+}
+
+impl TextureData
+{
+    pub fn load(path: &Path) -> Result<TextureData, String>
+    {
+        if hdr_image::is_float_image_path(path) {
+            return Ok(TextureData::Float(hdr_image::load_float_image(path)?));
+        }
+
+        let image = image::open(path).map_err(|e| format!("Failed to load image {}: {}", path.display(), e))?;
+        Ok(TextureData::Image(image))
+    }
+}
+
+// sets wrap and filter parameters of the texture bound to `target`
+fn apply_options(target: GLenum, options: TextureOptions)
+{
+    unsafe {
+        gl::TexParameteri(target, gl::TEXTURE_WRAP_S, options.wrap_s.to_gl());
+        gl::TexParameteri(target, gl::TEXTURE_WRAP_T, options.wrap_t.to_gl());
+        gl::TexParameteri(target, gl::TEXTURE_MIN_FILTER, min_filter_to_gl(options.min_filter, options.mipmap_filter));
+        gl::TexParameteri(target, gl::TEXTURE_MAG_FILTER, mag_filter_to_gl(options.mag_filter));
+    }
+}
+
+pub struct Texture2D
+{
+    pub id: GLuint,
+    pub width: u32,
+    pub height: u32,
+    pub internal_format: GLenum,
+    // 1 when the texture has no mip chain
+    pub mip_levels: u32,
+}
+
+impl Texture2D
+{
+    // .hdr and .exr files are loaded as half float textures, other formats through the `image` crate
+    pub fn from_file(path: &Path, options: TextureOptions) -> Result<Texture2D, String>
+    {
+        Ok(Texture2D::from_data(&TextureData::load(path)?, options))
+    }
+
+    pub fn from_data(data: &TextureData, options: TextureOptions) -> Texture2D
+    {
+        match data {
+            TextureData::Image(image) => Texture2D::from_image(image, options),
+            TextureData::Float(image) => Texture2D::from_float_image(image, FloatPrecision::Half, options),
+        }
+    }
+
+    pub fn from_image(image: &DynamicImage, options: TextureOptions) -> Texture2D
+    {
+        let flipped;
+        let image = match (options.flip_vertically, options.flip_horizontally) {
+            (false, false) => image,
+            (true, false) => {
+                flipped = image.flipv();
+                &flipped
+            },
+            (false, true) => {
+                flipped = image.fliph();
+                &flipped
+            },
+            (true, true) => {
+                flipped = image.rotate180();
+                &flipped
+            },
+        };
+
+        // BGR(A) has no sized GL equivalent, convert it to RGB(A)
+        let converted;
+        let image = match image {
+            DynamicImage::ImageBgr8(_) => {
+                converted = DynamicImage::ImageRgb8(image.to_rgb8());
+                &converted
+            },
+            DynamicImage::ImageBgra8(_) => {
+                converted = DynamicImage::ImageRgba8(image.to_rgba8());
+                &converted
+            },
+            _ => image,
+        };
+
+        let format = pixel_format(image.color(), options.srgb).expect("Unsupported image color type");
+        let (width, height) = image.dimensions();
+
+        let texture = Texture2D::from_pixels(width, height, format, &image.to_bytes(), options);
+
+        // gray images are stored in the red (and green for alpha) channel, spread them back when sampling
+        let swizzle = match image.color() {
+            image::ColorType::L8 | image::ColorType::L16 => Some([gl::RED, gl::RED, gl::RED, gl::ONE]),
+            image::ColorType::La8 | image::ColorType::La16 => Some([gl::RED, gl::RED, gl::RED, gl::GREEN]),
+            _ => None,
+        };
+        if let Some(swizzle) = swizzle {
+            let swizzle: Vec<GLint> = swizzle.iter().map(|&c| c as GLint).collect();
+            unsafe {
+                gl::BindTexture(gl::TEXTURE_2D, texture.id);
+                gl::TexParameteriv(gl::TEXTURE_2D, gl::TEXTURE_SWIZZLE_RGBA, swizzle.as_ptr());
+            }
+        }
+
+        texture
+    }
+
+    // RGB(A)16F or RGB(A)32F texture. sRGB doesn't apply, float data is linear
+    pub fn from_float_image(image: &HdrImage, precision: FloatPrecision, options: TextureOptions) -> Texture2D
+    {
+        let flipped;
+        let image = if options.flip_vertically || options.flip_horizontally {
+            let mut copy = image.clone();
+            if options.flip_vertically {
+                copy.flip_vertically();
+            }
+            if options.flip_horizontally {
+                copy.flip_horizontally();
+            }
+            flipped = copy;
+            &flipped
+        } else {
+            image
+        };
+
+        let (internal_format, format) = match (image.channels, precision) {
+            (4, FloatPrecision::Half) => (gl::RGBA16F, gl::RGBA),
+            (4, FloatPrecision::Full) => (gl::RGBA32F, gl::RGBA),
+            (_, FloatPrecision::Half) => (gl::RGB16F, gl::RGB),
+            (_, FloatPrecision::Full) => (gl::RGB32F, gl::RGB),
+        };
+
+        // the driver converts the f32 data to half floats for the 16F formats
+        let format = PixelFormat { internal_format, format, type_: gl::FLOAT, pixel_size: image.channels * 4 };
+        let bytes = unsafe { std::slice::from_raw_parts(image.pixels.as_ptr() as *const u8, image.pixels.len() * 4) };
+        Texture2D::from_pixels(image.width, image.height, format, bytes, options)
+    }
+
+    // reads level 0 back as RGBA floats. GL rows start at v = 0, they are flipped so the image reads top row first
+    pub fn to_float_image(&self) -> HdrImage
+    {
+        let mut image = HdrImage::new(self.width, self.height, 4);
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, self.id);
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 4);
+            gl::GetTexImage(gl::TEXTURE_2D, 0, gl::RGBA, gl::FLOAT, image.pixels.as_mut_ptr() as *mut std::ffi::c_void);
+        }
+        image.flip_vertically();
+        image
+    }
+
+    // uploads raw pixel rows (bottom row first unless the texture coordinates say otherwise)
+    pub fn from_pixels(width: u32, height: u32, format: PixelFormat, pixels: &[u8], options: TextureOptions) -> Texture2D
+    {
+        let mut id: GLuint = 0;
+
+        unsafe {
+            gl::GenTextures(1, &mut id);
+            gl::BindTexture(gl::TEXTURE_2D, id);
+        }
+
+        let mip_levels = if options.mipmap_filter.is_some() { mip_level_count(width, height) } else { 1 };
+        let texture = Texture2D { id, width, height, internal_format: format.internal_format, mip_levels };
+        texture.set_options(options);
+
+        unsafe {
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, unpack_alignment(width, format.pixel_size));
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                format.internal_format as i32,
+                width as i32,
+                height as i32,
+                0,
+                format.format,
+                format.type_,
+                pixels.as_ptr() as *const std::ffi::c_void
+            );
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 4);
+
+            if options.mipmap_filter.is_some() {
+                gl::GenerateMipmap(gl::TEXTURE_2D);
+            }
+            // keeps the texture complete when a sampler object with a mipmap filter is bound on its unit
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAX_LEVEL, mip_levels as GLint - 1);
+        }
+
+        texture
+    }
+
+    // RGBA8 texture from a mip chain built on the CPU (see mipmap::generate_mips), level 0 first. The chain may stop
+    // before 1x1, sampling is then limited to the given levels
+    pub fn from_mip_chain(levels: &[image::RgbaImage], options: TextureOptions) -> Result<Texture2D, String>
+    {
+        let (width, height) = levels.first().ok_or("Mip chain has no levels")?.dimensions();
+        for (level, image) in levels.iter().enumerate() {
+            let expected = ((width >> level).max(1), (height >> level).max(1));
+            if image.dimensions() != expected {
+                return Err(format!("Mip level {} is {}x{}, expected {}x{}",
+                    level, image.width(), image.height(), expected.0, expected.1));
+            }
+        }
+
+        let mut id: GLuint = 0;
+        unsafe {
+            gl::GenTextures(1, &mut id);
+            gl::BindTexture(gl::TEXTURE_2D, id);
+        }
+
+        let internal_format = if options.srgb { gl::SRGB8_ALPHA8 } else { gl::RGBA8 };
+        let texture = Texture2D { id, width, height, internal_format, mip_levels: levels.len() as u32 };
+        texture.set_options(options);
+
+        for (level, image) in levels.iter().enumerate() {
+            if options.flip_horizontally {
+                texture.upload_level(level as u32, &image::imageops::flip_horizontal(image), options.flip_vertically);
+            } else {
+                texture.upload_level(level as u32, image, options.flip_vertically);
+            }
+        }
+        unsafe { gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAX_LEVEL, levels.len() as GLint - 1); }
+
+        Ok(texture)
+    }
+
+    // (re)specifies one mip level, e.g. to stream in the detailed levels of a texture after the small ones
+    pub fn upload_level(&self, level: u32, image: &image::RgbaImage, flip_vertically: bool)
+    {
+        let flipped;
+        let image = if flip_vertically {
+            flipped = image::imageops::flip_vertical(image);
+            &flipped
+        } else {
+            image
+        };
+
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, self.id);
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, unpack_alignment(image.width(), 4));
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                level as GLint,
+                self.internal_format as GLint,
+                image.width() as i32,
+                image.height() as i32,
+                0,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                image.as_ptr() as *const std::ffi::c_void
+            );
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 4);
+        }
+    }
+
+    // applies wrap and filter settings (the texture is left bound to TEXTURE_2D)
+    pub fn set_options(&self, options: TextureOptions)
+    {
+        unsafe { gl::BindTexture(gl::TEXTURE_2D, self.id); }
+        apply_options(gl::TEXTURE_2D, options);
+    }
+
+    // approximate GPU memory used by every mip level
+    pub fn byte_size(&self) -> usize
+    {
+        let texel_size = internal_format_size(self.internal_format);
+        (0..self.mip_levels)
+            .map(|level| ((self.width >> level).max(1) * (self.height >> level).max(1)) as usize * texel_size)
+            .sum()
+    }
+
+    // bind the texture on the texture unit `unit` (0 for GL_TEXTURE0, ...)
+    pub fn bind(&self, unit: u32)
+    {
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0 + unit);
+            gl::BindTexture(gl::TEXTURE_2D, self.id);
+        }
+    }
+}
+
+impl Drop for Texture2D
+{
+    fn drop(&mut self)
+    {
+        unsafe { gl::DeleteTextures(1, &self.id); }
+    }
+}
+
+// Stack of equally sized RGBA8 images sampled with a sampler2DArray, the layer is the third texture coordinate.
+// Mip levels are generated per layer, layers never bleed into each other
+pub struct Texture2DArray
+{
+    pub id: GLuint,
+    pub width: u32,
+    pub height: u32,
+    pub layers: u32,
+    pub internal_format: GLenum,
+    pub mip_levels: u32,
+}
+
+impl Texture2DArray
+{
+    // one layer per file, in order. Every image must have the size of the first one
+    pub fn from_files(paths: &[&Path], options: TextureOptions) -> Result<Texture2DArray, String>
+    {
+        let mut images = Vec::new();
+        for path in paths {
+            let image = image::open(path).map_err(|e| format!("Failed to load image {}: {}", path.display(), e))?;
+            images.push(image.to_rgba8());
+        }
+        Texture2DArray::from_images(&images, options)
+    }
+
+    pub fn from_images(images: &[image::RgbaImage], options: TextureOptions) -> Result<Texture2DArray, String>
+    {
+        let (width, height) = images.first().ok_or("Texture array needs at least one layer")?.dimensions();
+        if let Some(index) = images.iter().position(|image| image.dimensions() != (width, height)) {
+            let (w, h) = images[index].dimensions();
+            return Err(format!("Texture array layer {} is {}x{}, expected {}x{}", index, w, h, width, height));
+        }
+
+        let mut pixels = Vec::with_capacity(images.len() * width as usize * height as usize * 4);
+        for image in images {
+            match (options.flip_vertically, options.flip_horizontally) {
+                (false, false) => pixels.extend_from_slice(image),
+                (true, false) => pixels.extend_from_slice(&image::imageops::flip_vertical(image)),
+                (false, true) => pixels.extend_from_slice(&image::imageops::flip_horizontal(image)),
+                (true, true) => pixels.extend_from_slice(&image::imageops::rotate180(image)),
+            }
+        }
+
+        let internal_format = if options.srgb { gl::SRGB8_ALPHA8 } else { gl::RGBA8 };
+        let format = PixelFormat { internal_format, format: gl::RGBA, type_: gl::UNSIGNED_BYTE, pixel_size: 4 };
+        Ok(Texture2DArray::from_pixels(width, height, images.len() as u32, format, &pixels, options))
+    }
+
+    // `pixels` holds the layers one after another
+    pub fn from_pixels(width: u32, height: u32, layers: u32, format: PixelFormat, pixels: &[u8], options: TextureOptions) -> Texture2DArray
+    {
+        let mut id: GLuint = 0;
+        let mip_levels = if options.mipmap_filter.is_some() { mip_level_count(width, height) } else { 1 };
+
+        unsafe {
+            gl::GenTextures(1, &mut id);
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, id);
+            apply_options(gl::TEXTURE_2D_ARRAY, options);
+
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, unpack_alignment(width, format.pixel_size));
+            gl::TexImage3D(
+                gl::TEXTURE_2D_ARRAY,
+                0,
+                format.internal_format as i32,
+                width as i32,
+                height as i32,
+                layers as i32,
+                0,
+                format.format,
+                format.type_,
+                pixels.as_ptr() as *const std::ffi::c_void
+            );
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 4);
+
+            if options.mipmap_filter.is_some() {
+                gl::GenerateMipmap(gl::TEXTURE_2D_ARRAY);
+            }
+            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_MAX_LEVEL, mip_levels as GLint - 1);
+        }
+
+        Texture2DArray { id, width, height, layers, internal_format: format.internal_format, mip_levels }
+    }
+
+    pub fn byte_size(&self) -> usize
+    {
+        let texel_size = internal_format_size(self.internal_format);
+        (0..self.mip_levels)
+            .map(|level| ((self.width >> level).max(1) * (self.height >> level).max(1)) as usize * texel_size)
+            .sum::<usize>() * self.layers as usize
+    }
+
+    pub fn bind(&self, unit: u32)
+    {
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0 + unit);
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, self.id);
+        }
+    }
+}
+
+impl Drop for Texture2DArray
+{
+    fn drop(&mut self)
+    {
+        unsafe { gl::DeleteTextures(1, &self.id); }
+    }
+}
@@ -0,0 +1,217 @@
+use gl::types::*;
+
+use std::path::Path;
+
+use super::assets::{ AssetManager, Handle };
+use super::framebuffer::{ Attachment, Framebuffer, FramebufferDesc };
+use super::postprocess::{ PostProcessPass, Uniform };
+use super::shader::Shader;
+use super::utils;
+
+// the frame is averaged at this size, a power of two so the mip chain ends in a single pixel
+const LUMINANCE_SIZE: u32 = 256;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ToneMapper {
+    Reinhard,
+    Aces,
+    Uncharted2,
+}
+
+impl ToneMapper
+{
+    pub const ALL: [ToneMapper; 3] = [ToneMapper::Reinhard, ToneMapper::Aces, ToneMapper::Uncharted2];
+
+    pub fn name(self) -> &'static str
+    {
+        match self {
+            ToneMapper::Reinhard => "reinhard",
+            ToneMapper::Aces => "aces",
+            ToneMapper::Uncharted2 => "uncharted2",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<ToneMapper>
+    {
+        ToneMapper::ALL.iter().copied().find(|operator| operator.name() == name)
+    }
+
+    // the one after this, wrapping around
+    pub fn next(self) -> ToneMapper
+    {
+        let index = ToneMapper::ALL.iter().position(|&operator| operator == self).unwrap_or(0);
+        ToneMapper::ALL[(index + 1) % ToneMapper::ALL.len()]
+    }
+
+    // value of the `operator` uniform of post_tonemap.fs
+    fn to_uniform(self) -> i32
+    {
+        match self {
+            ToneMapper::Reinhard => 0,
+            ToneMapper::Aces => 1,
+            ToneMapper::Uncharted2 => 2,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ToneMapOptions
+{
+    pub operator: ToneMapper,
+    // multiplier applied before the curve. With auto exposure it acts as exposure compensation
+    pub exposure: f32,
+    pub auto_exposure: bool,
+    // luminance the adapted scene average is mapped to, 0.18 is middle gray
+    pub key: f32,
+    // adaptation rates per second towards brighter and darker scenes, eyes adjust to light faster than to dark
+    pub speed_up: f32,
+    pub speed_down: f32,
+}
+
+impl Default for ToneMapOptions
+{
+    fn default() -> ToneMapOptions
+    {
+        ToneMapOptions {
+            operator: ToneMapper::Aces,
+            exposure: 1.0,
+            auto_exposure: false,
+            key: 0.18,
+            speed_up: 3.0,
+            speed_down: 1.0,
+        }
+    }
+}
+
+impl ToneMapOptions
+{
+    // `--tonemap reinhard|aces|uncharted2`, `--exposure <multiplier>`, `--auto-exposure` and `--exposure-key <key>`.
+    // None unless one of them or `--hdr` is given
+    pub fn from_args() -> Option<ToneMapOptions>
+    {
+        let operator = utils::arg_value("--tonemap");
+        let exposure = utils::arg_value("--exposure").and_then(|value| value.parse().ok());
+        let auto_exposure = utils::has_flag("--auto-exposure");
+        let key = utils::arg_value("--exposure-key").and_then(|value| value.parse().ok());
+
+        if !utils::has_flag("--hdr") && operator.is_none() && exposure.is_none() && !auto_exposure && key.is_none() {
+            return None;
+        }
+
+        let defaults = ToneMapOptions::default();
+        Some(ToneMapOptions {
+            operator: operator.map(|name| ToneMapper::from_name(&name).unwrap_or_else(|| {
+                println!("Unknown tone mapper {}, using {}", name, defaults.operator.name());
+                defaults.operator
+            })).unwrap_or(defaults.operator),
+            exposure: exposure.unwrap_or(defaults.exposure),
+            auto_exposure,
+            key: key.unwrap_or(defaults.key),
+            ..defaults
+        })
+    }
+}
+
+// Maps the HDR scene to displayable [0, 1] colors, the first pass of a PostProcessChain. Auto exposure measures the
+// average log luminance of each frame with a mip chain and eases the exposure towards it on the GPU, so nothing is
+// read back
+pub struct ToneMapping
+{
+    pub options: ToneMapOptions,
+    pass: PostProcessPass,
+    luminance_shader: Handle<Shader>,
+    adapt_shader: Handle<Shader>,
+    // log luminance of the frame at LUMINANCE_SIZE², with mips
+    luminance: Framebuffer,
+    // adapted luminance of the last two frames, 1x1
+    adapted: [Framebuffer; 2],
+    current: usize,
+    // whether the last frame measured, the adapted value is stale after auto exposure was off
+    measured: bool,
+}
+
+impl ToneMapping
+{
+    // `shader_dir` holds post.vs, post_tonemap.fs, post_luminance.fs and post_adapt.fs
+    pub fn new(assets: &mut AssetManager, shader_dir: &Path, options: ToneMapOptions) -> Result<ToneMapping, String>
+    {
+        let pass = PostProcessPass::new(assets, shader_dir, "tonemap", &shader_dir.join("post_tonemap.fs"))?;
+        let luminance_shader = assets.shader(&shader_dir.join("post.vs"), &shader_dir.join("post_luminance.fs"))?;
+        let adapt_shader = assets.shader(&shader_dir.join("post.vs"), &shader_dir.join("post_adapt.fs"))?;
+
+        let luminance_desc = FramebufferDesc { color: vec![Attachment::texture(gl::R16F)], depth: None, ..Default::default() };
+        let luminance = Framebuffer::new(LUMINANCE_SIZE, LUMINANCE_SIZE, luminance_desc)?;
+        if let Some(texture) = luminance.color_texture(0) {
+            unsafe {
+                gl::BindTexture(gl::TEXTURE_2D, texture);
+                gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAX_LEVEL, LUMINANCE_SIZE.ilog2() as GLint);
+                gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR_MIPMAP_NEAREST as GLint);
+                gl::BindTexture(gl::TEXTURE_2D, 0);
+            }
+        }
+
+        let adapted_desc = FramebufferDesc { color: vec![Attachment::texture(gl::R32F)], depth: None, ..Default::default() };
+        let adapted = [Framebuffer::new(1, 1, adapted_desc.clone())?, Framebuffer::new(1, 1, adapted_desc)?];
+
+        Ok(ToneMapping { options, pass, luminance_shader, adapt_shader, luminance, adapted, current: 0, measured: false })
+    }
+
+    // Updates the adapted luminance from `scene` and the uniforms of the pass. Draws fullscreen triangles, the
+    // chain has its vertex array bound and depth testing off
+    pub fn update(&mut self, scene: &Framebuffer, delta_time: f32)
+    {
+        if self.options.auto_exposure {
+            self.luminance.bind();
+            self.luminance_shader.use_shader();
+            self.luminance_shader.set_int("image", 0);
+            scene.bind_color_texture(0, 0);
+            unsafe {
+                gl::DrawArrays(gl::TRIANGLES, 0, 3);
+                gl::BindTexture(gl::TEXTURE_2D, self.luminance.color_texture(0).unwrap_or(0));
+                gl::GenerateMipmap(gl::TEXTURE_2D);
+            }
+
+            let next = 1 - self.current;
+            self.adapted[next].bind();
+            self.adapt_shader.use_shader();
+            self.adapt_shader.set_int("luminance", 0);
+            self.adapt_shader.set_float("averageLevel", LUMINANCE_SIZE.ilog2() as f32);
+            self.adapt_shader.set_int("previous", 1);
+            self.adapt_shader.set_float("deltaTime", delta_time);
+            self.adapt_shader.set_float("speedUp", self.options.speed_up);
+            self.adapt_shader.set_float("speedDown", self.options.speed_down);
+            self.adapt_shader.set_bool("reset", !self.measured);
+            self.luminance.bind_color_texture(0, 0);
+            self.adapted[self.current].bind_color_texture(0, 1);
+            unsafe { gl::DrawArrays(gl::TRIANGLES, 0, 3); }
+
+            self.current = next;
+        }
+        self.measured = self.options.auto_exposure;
+
+        let adapted = self.adapted[self.current].color_texture(0).unwrap_or(0);
+        self.pass.set("operator", Uniform::Int(self.options.operator.to_uniform()));
+        self.pass.set("exposure", Uniform::Float(self.options.exposure));
+        self.pass.set("autoExposure", Uniform::Int(self.options.auto_exposure as i32));
+        self.pass.set("key", Uniform::Float(self.options.key));
+        self.pass.set("adaptedLuminance", Uniform::Texture(gl::TEXTURE_2D, adapted));
+    }
+
+    // the tone mapping pass, with the uniforms of the last `update`
+    pub fn pass(&self) -> &PostProcessPass
+    {
+        &self.pass
+    }
+
+    // adapted scene luminance. Reads back from the GPU, meant for debugging output
+    pub fn adapted_luminance(&self) -> f32
+    {
+        let mut value = 0.0f32;
+        unsafe {
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.adapted[self.current].id);
+            gl::ReadPixels(0, 0, 1, 1, gl::RED, gl::FLOAT, &mut value as *mut f32 as *mut std::ffi::c_void);
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, 0);
+        }
+        value
+    }
+}
@@ -13,4 +13,45 @@ fn convert_to_c_string(string: &str) -> *const gl::types::GLchar
 
 pub fn degree_to_radian(degree: f32) -> f32 {
     degree * std::f32::consts::PI/180f32
-}
\ No newline at end of file
+}
+// returns the value following a command line option, e.g. `--gltf scene.glb` -> Some("scene.glb")
+pub fn arg_value(name: &str) -> Option<String> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == name {
+            return args.next();
+        }
+    }
+    None
+}
+
+pub fn has_flag(name: &str) -> bool {
+    std::env::args().skip(1).any(|arg| arg == name)
+}
+
+// "800x600" -> (800, 600)
+pub fn parse_size(value: &str) -> Option<(u32, u32)> {
+    let (width, height) = value.split_once('x')?;
+    Some((width.trim().parse().ok()?, height.trim().parse().ok()?))
+}
+
+// checks the extension list of the current GL context, e.g. "GL_EXT_texture_compression_s3tc"
+pub fn has_gl_extension(name: &str) -> bool {
+    let mut count: gl::types::GLint = 0;
+    unsafe { gl::GetIntegerv(gl::NUM_EXTENSIONS, &mut count); }
+
+    (0..count.max(0) as u32).any(|i| unsafe {
+        let extension = gl::GetStringi(gl::EXTENSIONS, i);
+        !extension.is_null() && std::ffi::CStr::from_ptr(extension as *const std::os::raw::c_char).to_bytes() == name.as_bytes()
+    })
+}
+
+// (major, minor) version of the current GL context
+pub fn gl_version() -> (i32, i32) {
+    let (mut major, mut minor) = (0, 0);
+    unsafe {
+        gl::GetIntegerv(gl::MAJOR_VERSION, &mut major);
+        gl::GetIntegerv(gl::MINOR_VERSION, &mut minor);
+    }
+    (major, minor)
+}
@@ -1,3 +1,5 @@
+use super::mesh::{ Mesh, Vertex };
+
 pub fn get_cube() -> [f32; 180] {
     [
         -0.5f32, -0.5, -0.5,  0.0, 0.0,
@@ -44,3 +46,103 @@ pub fn get_cube() -> [f32; 180] {
     ]
 }
 
+
+// the cube above as a mesh (36 vertices, no normals yet)
+pub fn get_cube_mesh() -> Mesh
+{
+    let data = get_cube();
+    let mut vertices: Vec<Vertex> = data.chunks_exact(5)
+        .map(|v| Vertex::new(nalgebra_glm::vec3(v[0], v[1], v[2]), nalgebra_glm::vec2(v[3], v[4])))
+        .collect();
+
+    // the array above doesn't use a consistent winding, make every triangle counter-clockwise seen from outside
+    // so computed normals point out of the cube
+    for triangle in vertices.chunks_exact_mut(3) {
+        let normal = nalgebra_glm::cross(
+            &(triangle[1].position - triangle[0].position),
+            &(triangle[2].position - triangle[0].position)
+        );
+        if nalgebra_glm::dot(&normal, &triangle[0].position) < 0.0 {
+            triangle.swap(1, 2);
+        }
+    }
+
+    Mesh::from_vertices(vertices, gl::TRIANGLES)
+}
+
+// plane on the XZ axis centered at the origin, facing up, split in `subdivisions` x `subdivisions` quads
+pub fn get_plane_mesh(size: f32, subdivisions: u32) -> Mesh
+{
+    let subdivisions = subdivisions.max(1);
+    let row = subdivisions + 1;
+
+    let mut vertices = Vec::new();
+    for z in 0..row {
+        for x in 0..row {
+            let u = x as f32 / subdivisions as f32;
+            let v = z as f32 / subdivisions as f32;
+            let mut vertex = Vertex::new(
+                nalgebra_glm::vec3((u - 0.5) * size, 0.0, (v - 0.5) * size),
+                nalgebra_glm::vec2(u, 1.0 - v)
+            );
+            vertex.normal = nalgebra_glm::vec3(0.0f32, 1.0, 0.0);
+            vertices.push(vertex);
+        }
+    }
+
+    let mut indices = Vec::new();
+    for z in 0..subdivisions {
+        for x in 0..subdivisions {
+            let i = z * row + x;
+            indices.extend_from_slice(&[i, i + row, i + 1, i + 1, i + row, i + row + 1]);
+        }
+    }
+
+    Mesh::new(vertices, indices, gl::TRIANGLES)
+}
+
+// UV sphere centered at the origin. The seam column is duplicated so texture coordinates wrap correctly
+pub fn get_uv_sphere_mesh(radius: f32, segments: u32, rings: u32) -> Mesh
+{
+    use std::f32::consts::PI;
+
+    let segments = segments.max(3);
+    let rings = rings.max(2);
+
+    let mut vertices = Vec::new();
+    for ring in 0..=rings {
+        let v = ring as f32 / rings as f32;
+        // exact at the poles, sin(π) rounds to a tiny non-zero value
+        let (sin_phi, cos_phi) = match ring {
+            0 => (0.0, 1.0),
+            _ if ring == rings => (0.0, -1.0),
+            _ => ((v * PI).sin(), (v * PI).cos()),
+        };
+        for segment in 0..=segments {
+            let u = segment as f32 / segments as f32;
+            // the seam column repeats the first one exactly, 2π would round to slightly different positions
+            let theta = if segment == segments { 0.0 } else { u * 2.0 * PI };
+            let normal = nalgebra_glm::vec3(sin_phi * theta.cos(), cos_phi, -sin_phi * theta.sin());
+            let mut vertex = Vertex::new(normal * radius, nalgebra_glm::vec2(u, 1.0 - v));
+            vertex.normal = normal;
+            vertices.push(vertex);
+        }
+    }
+
+    let row = segments + 1;
+    let mut indices = Vec::new();
+    for ring in 0..rings {
+        for segment in 0..segments {
+            let i = ring * row + segment;
+            // skip the degenerate triangles at the poles
+            if ring != 0 {
+                indices.extend_from_slice(&[i, i + row, i + 1]);
+            }
+            if ring != rings - 1 {
+                indices.extend_from_slice(&[i + 1, i + row, i + row + 1]);
+            }
+        }
+    }
+
+    Mesh::new(vertices, indices, gl::TRIANGLES)
+}